@@ -6,9 +6,13 @@ mod tests {
     }
 }
 
+mod migration;
+mod negative_cycles;
 mod network;
 mod nodes;
 mod search_stop_settings;
 
+pub use migration::*;
+pub use negative_cycles::*;
 pub use network::*;
 pub use search_stop_settings::SearchStopSettings;
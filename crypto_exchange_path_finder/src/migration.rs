@@ -0,0 +1,249 @@
+use crate::network::*;
+use crypto_exchange_types::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+
+/// Result of migrating a network to a new lexicon
+#[derive(Default, Debug)]
+pub struct NetworkMigrationReport {
+    pub kept_paths: usize,
+    pub dropped_paths: usize,
+    /// Old targets that don't exist in the new lexicon (old IDs)
+    pub dropped_targets: Vec<Target>,
+    /// New targets and targets that lost paths (new IDs). These should be searched again.
+    pub targets_to_research: Vec<Target>,
+    /// False if the new lexicon has markets that weren't in the old one. New markets can create new paths
+    /// for any target so only a full network regeneration finds all of them.
+    pub is_complete: bool,
+}
+
+fn migrate_paths<T, F>(paths: &Option<Vec<T>>, report: &mut NetworkMigrationReport, had_dropped: &mut bool, f: F) -> Option<Vec<T>>
+where
+    F: Fn(&T) -> Option<T>,
+{
+    paths.as_ref().map(|paths| {
+        let migrated: Vec<T> = paths.iter().filter_map(&f).collect();
+        let dropped = paths.len() - migrated.len();
+        report.kept_paths += migrated.len();
+        report.dropped_paths += dropped;
+        *had_dropped |= dropped > 0;
+        migrated
+    })
+}
+
+fn migrate_fixed_length_paths<T, F>(
+    paths: &FixedLengthPaths<T>,
+    report: &mut NetworkMigrationReport,
+    had_dropped: &mut bool,
+    f: F,
+) -> FixedLengthPaths<T>
+where
+    T: Copy,
+    F: Fn(&[T]) -> Option<Vec<T>>,
+{
+    let mut migrated = FixedLengthPaths::default();
+    let mut dropped = 0;
+    for path in paths.iter() {
+        match f(path) {
+            Some(path) => migrated.push(&path),
+            None => dropped += 1,
+        }
+    }
+    report.kept_paths += migrated.len();
+    report.dropped_paths += dropped;
+    *had_dropped |= dropped > 0;
+    migrated
+}
+
+/// Remaps the compact edges of a path starting at the old target
+fn migrate_edges(remapper: &LexiconIdRemapper, start: Target, edges: &[CompactPathEdge]) -> Option<Vec<CompactPathEdge>> {
+    let path = ArbitragePath::try_from(&CompactArbitragePath {
+        start,
+        edges: edges.iter().copied().collect(),
+    })
+    .ok()?;
+    let path = remapper.path(&path)?;
+    CompactArbitragePath::try_from(&path).ok().map(|path| path.edges.to_vec())
+}
+
+impl OptimizedNetworkWithLexicon {
+    /// Remaps every stored path to the IDs of the new lexicon by names. Paths that use markets, exchanges
+    /// or currencies that no longer exist are dropped. The returned network holds the new lexicon.
+    pub fn migrate(&self, lexicon: CryptoExchangeLexicon) -> (OptimizedNetworkWithLexicon, NetworkMigrationReport) {
+        let remapper = LexiconIdRemapper::create(&self.lexicon, &lexicon);
+        let lexicon_checksum = lexicon.checksum();
+        let mut report = NetworkMigrationReport::default();
+        let mut targets_to_research: BTreeSet<Target> = BTreeSet::new();
+        let mut pre_calced_paths = BTreeMap::new();
+        for (target, paths) in self.pre_calced_paths.iter() {
+            let id = match remapper.balance(target) {
+                Some(id) => id,
+                None => {
+                    report.dropped_targets.push(*target);
+                    report.dropped_paths += paths.stats().total_paths();
+                    continue;
+                }
+            };
+            let mut had_dropped = false;
+            let migrated = OptimizedPreCalcedPaths {
+                id,
+                lexicon_checksum: lexicon_checksum.clone(),
+                format_version: paths.format_version,
+                compact_paths: migrate_paths(&paths.compact_paths, &mut report, &mut had_dropped, |p| {
+                    let path = remapper.path(&ArbitragePath::try_from(p).ok()?)?;
+                    CompactArbitragePath::try_from(&path).ok()
+                }),
+                compact_transfer_paths: migrate_fixed_length_paths(
+                    &paths.compact_transfer_paths,
+                    &mut report,
+                    &mut had_dropped,
+                    |edges| migrate_edges(&remapper, *target, edges),
+                ),
+                tx_only_paths: migrate_fixed_length_paths(&paths.tx_only_paths, &mut report, &mut had_dropped, |pairs| {
+                    pairs.iter().map(|pair| remapper.pair(&id.exchange, pair)).collect()
+                }),
+            };
+            if had_dropped {
+                targets_to_research.insert(id);
+            }
+            pre_calced_paths.insert(id, migrated);
+        }
+        targets_to_research.extend(
+            remapper
+                .balances()
+                .iter()
+                .filter(|target| !pre_calced_paths.contains_key(*target)),
+        );
+        report.targets_to_research = targets_to_research.into_iter().collect();
+        let diff = LexiconDiff::create(&self.lexicon, &lexicon);
+        report.is_complete = diff.exchange_pairs.iter().all(|pairs| pairs.added.is_empty());
+
+        let network = OptimizedNetworkWithLexicon {
+            lexicon,
+            pre_calced_paths,
+            search_stop_settings: self.search_stop_settings.clone(),
+            lexicon_checksum,
+        };
+        (network, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto_exchange_types::test_utils::ex_symbols;
+    use crate::search_stop_settings::SearchStopSettings;
+
+    #[test]
+    fn migrate_network_test() {
+        let old_lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&[ex_symbols(
+            "kraken",
+            &["BTC/USD", "ETH/BTC", "ETH/USD", "XRP/USD", "XRP/BTC"],
+        )]).unwrap();
+        let kraken = ExchangeID(0);
+        let (btc, usd, eth, xrp) = (CurrencyID(0), CurrencyID(1), CurrencyID(2), CurrencyID(3));
+        let mut tx_only_paths = FixedLengthPaths::default();
+        tx_only_paths.push(&[CurrencyIDPair::new(btc, usd), CurrencyIDPair::new(eth, btc), CurrencyIDPair::new(eth, usd)]);
+        tx_only_paths.push(&[CurrencyIDPair::new(btc, usd), CurrencyIDPair::new(xrp, btc), CurrencyIDPair::new(xrp, usd)]);
+        let target = kraken.with_currency(&usd);
+        let mut pre_calced_paths = BTreeMap::new();
+        pre_calced_paths.insert(
+            target,
+            OptimizedPreCalcedPaths {
+                id: target,
+                lexicon_checksum: old_lexicon.checksum(),
+                format_version: PRE_CALCED_PATHS_FORMAT_VERSION,
+                compact_paths: None,
+                compact_transfer_paths: FixedLengthPaths::default(),
+                tx_only_paths,
+            },
+        );
+        let network = OptimizedNetworkWithLexicon {
+            lexicon_checksum: old_lexicon.checksum(),
+            lexicon: old_lexicon,
+            pre_calced_paths,
+            search_stop_settings: SearchStopSettings::new_default(),
+        };
+        assert!(network.verify_checksum().is_ok());
+
+        // XRP is delisted and the currencies are listed in a different order
+        let new_lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&[ex_symbols(
+            "kraken",
+            &["ETH/USD", "ETH/BTC", "BTC/USD"],
+        )]).unwrap();
+        let (migrated, report) = network.migrate(new_lexicon);
+        assert!(migrated.verify_checksum().is_ok());
+        assert_eq!(report.kept_paths, 1);
+        assert_eq!(report.dropped_paths, 1);
+        assert!(report.is_complete);
+        let (new_eth, new_usd, new_btc) = (CurrencyID(0), CurrencyID(1), CurrencyID(2));
+        let new_target = kraken.with_currency(&new_usd);
+        let paths = &migrated.pre_calced_paths[&new_target];
+        assert_eq!(
+            paths.tx_only_paths_n::<3>().next().unwrap().0,
+            [
+                CurrencyIDPair::new(new_btc, new_usd),
+                CurrencyIDPair::new(new_eth, new_btc),
+                CurrencyIDPair::new(new_eth, new_usd),
+            ]
+        );
+        // the target lost a path and the ETH and BTC targets were never searched
+        assert_eq!(
+            report.targets_to_research,
+            vec![
+                kraken.with_currency(&new_eth),
+                new_target,
+                kraken.with_currency(&new_btc)
+            ]
+        );
+    }
+
+    fn transfer_path(start: Target, edges: &[CompactPathEdge]) -> ArbitragePath {
+        let compact = CompactArbitragePath {
+            start,
+            edges: edges.iter().copied().collect(),
+        };
+        ArbitragePath::try_from(&compact).unwrap()
+    }
+
+    #[test]
+    fn migrate_transfer_paths_test() {
+        let old_lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&[
+            ex_symbols("kraken", &["BTC/USD"]),
+            ex_symbols("bitstamp", &["BTC/USD"]),
+        ]).unwrap();
+        let (btc, usd) = (CurrencyID(0), CurrencyID(1));
+        let pair = CurrencyIDPair::new(btc, usd);
+        let (kraken, bitstamp) = (ExchangeID(0), ExchangeID(1));
+        let target = kraken.with_currency(&usd);
+        // USD -> BTC on kraken, BTC transfer to bitstamp and BTC -> USD on bitstamp
+        let path = transfer_path(
+            target,
+            &[CompactPathEdge::Transaction(pair), CompactPathEdge::Transfer(bitstamp, None), CompactPathEdge::Transaction(pair)],
+        );
+        let paths = OptimizedPreCalcedPaths::create(target, vec![path], old_lexicon.checksum()).unwrap();
+        assert_eq!(paths.stats().transfer_paths, BTreeMap::from([(7, 1)]));
+        let network = OptimizedNetworkWithLexicon {
+            lexicon_checksum: old_lexicon.checksum(),
+            lexicon: old_lexicon,
+            pre_calced_paths: BTreeMap::from([(target, paths)]),
+            search_stop_settings: SearchStopSettings::new_default(),
+        };
+
+        // the exchanges are listed in a different order
+        let new_lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&[
+            ex_symbols("bitstamp", &["BTC/USD"]),
+            ex_symbols("kraken", &["BTC/USD"]),
+        ]).unwrap();
+        let (migrated, report) = network.migrate(new_lexicon);
+        assert_eq!(report.kept_paths, 1);
+        let (new_bitstamp, new_kraken) = (ExchangeID(0), ExchangeID(1));
+        let new_target = new_kraken.with_currency(&usd);
+        let expected = transfer_path(
+            new_target,
+            &[CompactPathEdge::Transaction(pair), CompactPathEdge::Transfer(new_bitstamp, None), CompactPathEdge::Transaction(pair)],
+        );
+        let paths: Vec<_> = migrated.pre_calced_paths[&new_target].transfer_paths().collect::<Result<_, _>>().unwrap();
+        assert_eq!(paths, vec![expected]);
+    }
+}
@@ -0,0 +1,427 @@
+use crate::network::Network;
+use crypto_exchange_types::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Best bid and ask for an exchange market. Prices are expressed in the second/quote currency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarketRate {
+    pub best_bid: f64,
+    pub best_ask: f64,
+}
+
+/// Live rates and fees used for weighting the network edges. Fees are fractions e.g. 0.001 is 0.1%.
+#[derive(Default)]
+pub struct LiveRates {
+    markets: HashMap<(ExchangeID, CurrencyIDPair), MarketRate>,
+    taker_fees: HashMap<ExchangeID, f64>,
+    transfer_fees: HashMap<(ExchangeID, CurrencyID), f64>,
+    default_taker_fee: f64,
+}
+
+impl LiveRates {
+    pub fn new(default_taker_fee: f64) -> Self {
+        LiveRates {
+            default_taker_fee,
+            ..Default::default()
+        }
+    }
+
+    /// Creates the rates from the order books top levels. Books with an empty side are skipped.
+    pub fn from_order_books<'a, I>(books: I, default_taker_fee: f64) -> Self
+    where
+        I: IntoIterator<Item = &'a ExchangeMarketOrderBook>,
+    {
+        let mut rates = Self::new(default_taker_fee);
+        books.into_iter().for_each(|book| {
+            if let (Some(bid), Some(ask)) = (book.best_bid(), book.best_ask()) {
+                rates.set_market_rate(
+                    book.exchange,
+                    book.pair,
+                    MarketRate {
+                        best_bid: bid.price.0,
+                        best_ask: ask.price.0,
+                    },
+                );
+            }
+        });
+        rates
+    }
+
+    pub fn set_market_rate(&mut self, exchange: ExchangeID, pair: CurrencyIDPair, rate: MarketRate) {
+        self.markets.insert((exchange, pair), rate);
+    }
+
+    pub fn set_taker_fee(&mut self, exchange: ExchangeID, fee: f64) {
+        self.taker_fees.insert(exchange, fee);
+    }
+
+    /// Transfer fee as a fraction of the transfered amount. Transfers without a fee are free.
+    pub fn set_transfer_fee(&mut self, withdraw_exchange: ExchangeID, currency: CurrencyID, fee: f64) {
+        self.transfer_fees.insert((withdraw_exchange, currency), fee);
+    }
+
+    fn taker_fee(&self, exchange: &ExchangeID) -> f64 {
+        self.taker_fees.get(exchange).copied().unwrap_or(self.default_taker_fee)
+    }
+
+    /// Rate (received amount for one unit spent) of a transaction including the taker fee.
+    pub fn transaction_rate(&self, tx: &TransactionExchangeCurrenciesBuySellInfo) -> Option<f64> {
+        let fee_rate = 1.0 - self.taker_fee(&tx.exchange);
+        let base_to_quote = CurrencyIDPair::new(tx.currency_from, tx.currency_to);
+        let quote_to_base = CurrencyIDPair::new(tx.currency_to, tx.currency_from);
+        // spending the base currency gets the bid price, spending the quote currency pays the ask price
+        let rate = if let Some(market) = self.markets.get(&(tx.exchange, base_to_quote)) {
+            market.best_bid
+        } else {
+            let market = self.markets.get(&(tx.exchange, quote_to_base))?;
+            1.0 / market.best_ask
+        };
+        if rate.is_finite() && rate > 0.0 {
+            Some(rate * fee_rate)
+        } else {
+            None
+        }
+    }
+
+    /// Rate (received amount for one unit withdrawn) of a transfer including the transfer fee.
+    pub fn transfer_rate(&self, tr: &TransferExchangeToExchangeCurrencyInfo) -> f64 {
+        1.0 - self
+            .transfer_fees
+            .get(&(tr.withdraw_exchange, tr.currency))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Rate of the whole path. This can be used to cross-check the precalculated paths with the live rates.
+    pub fn path_rate(&self, path: &ArbitragePath) -> Option<f64> {
+        path.0.iter().try_fold(1.0, |rate, op| match op {
+            ExchangeOperationType::Balance(_) => Some(rate),
+            ExchangeOperationType::Transaction(tx) => self.transaction_rate(tx).map(|r| rate * r),
+            ExchangeOperationType::Transfer(tr) => Some(rate * self.transfer_rate(tr)),
+        })
+    }
+}
+
+/// Settings for the negative cycle search mode
+pub struct NegativeCycleSearchSettings {
+    /// Include transfers between exchanges as edges
+    pub allow_transfers: bool,
+    /// Stop after this many distinct cycles were found
+    pub max_cycles: usize,
+}
+
+impl NegativeCycleSearchSettings {
+    pub fn new_default() -> Self {
+        NegativeCycleSearchSettings {
+            allow_transfers: true,
+            max_cycles: 100,
+        }
+    }
+}
+
+/// Profitable cycle found on the live rates. A rate above 1.0 means profit.
+pub struct NegativeCycle {
+    pub path: ArbitragePath,
+    pub rate: f64,
+}
+
+struct WeightedEdge {
+    from: usize,
+    to: usize,
+    weight: f64,
+    operation: ExchangeOperationType,
+}
+
+/// Weighted graph over the balance nodes where every edge weight is `-ln(rate * (1 - fee))`.
+/// A cycle with a negative sum of weights is a cycle where the product of the rates is above 1.0.
+struct WeightedGraph {
+    vertices: Vec<BalanceExchangeCurrencyInfo>,
+    edges: Vec<WeightedEdge>,
+}
+
+impl WeightedGraph {
+    fn build(network: &Network, rates: &LiveRates, settings: &NegativeCycleSearchSettings) -> Self {
+        let mut operations: BTreeSet<ExchangeOperationType> = BTreeSet::new();
+        network.exchange_pairs().for_each(|(exchange, pair)| {
+            if pair.has_same_currencies() {
+                return;
+            }
+            let sides = [(pair.first, pair.second), (pair.second, pair.first)];
+            sides.iter().for_each(|(currency_from, currency_to)| {
+                if let Ok((_, side)) = pair.next_currency_and_side(*currency_from) {
+                    operations.insert(
+                        TransactionExchangeCurrenciesBuySellInfo {
+                            exchange,
+                            side,
+                            currency_from: *currency_from,
+                            currency_to: *currency_to,
+                        }
+                        .into(),
+                    );
+                }
+            });
+        });
+        if settings.allow_transfers {
+            network.exchange_transfers().for_each(|tr| {
+                operations.insert(tr.into());
+            });
+        }
+
+        let mut vertex_index: BTreeMap<BalanceExchangeCurrencyInfo, usize> = BTreeMap::new();
+        let mut vertices = Vec::new();
+        let mut index_of = |b: BalanceExchangeCurrencyInfo| -> usize {
+            *vertex_index.entry(b).or_insert_with(|| {
+                vertices.push(b);
+                vertices.len() - 1
+            })
+        };
+        let edges: Vec<WeightedEdge> = operations
+            .into_iter()
+            .filter_map(|operation| {
+                let (from, to, rate) = match &operation {
+                    ExchangeOperationType::Transaction(tx) => (
+                        tx.exchange.with_currency(&tx.currency_from),
+                        tx.exchange.with_currency(&tx.currency_to),
+                        rates.transaction_rate(tx)?,
+                    ),
+                    ExchangeOperationType::Transfer(tr) => (
+                        tr.withdraw_exchange.with_currency(&tr.currency),
+                        tr.deposit_exchange.with_currency(&tr.currency),
+                        rates.transfer_rate(tr),
+                    ),
+                    ExchangeOperationType::Balance(_) => return None,
+                };
+                if rate <= 0.0 {
+                    return None;
+                }
+                Some(WeightedEdge {
+                    from: index_of(from),
+                    to: index_of(to),
+                    weight: -rate.ln(),
+                    operation,
+                })
+            })
+            .collect();
+        WeightedGraph { vertices, edges }
+    }
+
+    /// Bellman-Ford from a virtual source connected to every vertex. Returns the predecessor edges
+    /// and the vertices that were still relaxed in the last iteration (these reach a negative cycle).
+    fn bellman_ford(&self) -> (Vec<Option<usize>>, Vec<usize>) {
+        const EPSILON: f64 = 1e-12;
+        let n = self.vertices.len();
+        let mut distances = vec![0.0f64; n];
+        let mut predecessors: Vec<Option<usize>> = vec![None; n];
+        let mut relaxed = Vec::new();
+        for _ in 0..n {
+            relaxed.clear();
+            for (edge_index, edge) in self.edges.iter().enumerate() {
+                let candidate = distances[edge.from] + edge.weight;
+                if candidate < distances[edge.to] - EPSILON {
+                    distances[edge.to] = candidate;
+                    predecessors[edge.to] = Some(edge_index);
+                    relaxed.push(edge.to);
+                }
+            }
+            if relaxed.is_empty() {
+                break;
+            }
+        }
+        (predecessors, relaxed)
+    }
+
+    /// Walks the predecessors from a relaxed vertex into the cycle and returns the cycle edges in order
+    fn extract_cycle(&self, predecessors: &[Option<usize>], relaxed_vertex: usize) -> Option<Vec<usize>> {
+        let mut v = relaxed_vertex;
+        for _ in 0..self.vertices.len() {
+            v = self.edges[predecessors[v]?].from;
+        }
+        let start = v;
+        let mut cycle_edges = Vec::new();
+        loop {
+            let edge_index = predecessors[v]?;
+            cycle_edges.push(edge_index);
+            v = self.edges[edge_index].from;
+            if v == start || cycle_edges.len() > self.vertices.len() {
+                break;
+            }
+        }
+        if v != start {
+            return None;
+        }
+        cycle_edges.reverse();
+        // rotate so the cycle starts at the smallest balance node, this makes the cycles comparable
+        let min_at = (0..cycle_edges.len())
+            .min_by_key(|i| self.vertices[self.edges[cycle_edges[*i]].from])
+            .unwrap_or(0);
+        cycle_edges.rotate_left(min_at);
+        Some(cycle_edges)
+    }
+
+    fn to_negative_cycle(&self, cycle_edges: &[usize]) -> NegativeCycle {
+        let mut path = ArbitragePath::new();
+        let start = self.vertices[self.edges[cycle_edges[0]].from];
+        path.0.push_back(start.into());
+        let weight: f64 = cycle_edges
+            .iter()
+            .map(|edge_index| {
+                let edge = &self.edges[*edge_index];
+                path.0.push_back(edge.operation);
+                path.0.push_back(self.vertices[edge.to].into());
+                edge.weight
+            })
+            .sum();
+        NegativeCycle {
+            path,
+            rate: (-weight).exp(),
+        }
+    }
+}
+
+impl Network {
+    /// Negative cycle detection mode. Instead of searching all precalculated paths this builds a weighted graph
+    /// from the network topology and the live rates and detects the profitable cycles directly (Bellman-Ford).
+    /// The cycles are not limited by a max level. The network hubs must be updated with `update_exchange_hubs`.
+    /// Returned cycles are sorted by the rate, the most profitable first.
+    pub fn find_negative_cycles(&self, rates: &LiveRates, settings: &NegativeCycleSearchSettings) -> Vec<NegativeCycle> {
+        let graph = WeightedGraph::build(self, rates, settings);
+        let (predecessors, relaxed) = graph.bellman_ford();
+        let mut found: BTreeSet<Vec<usize>> = BTreeSet::new();
+        for v in relaxed {
+            if found.len() >= settings.max_cycles {
+                break;
+            }
+            if let Some(cycle_edges) = graph.extract_cycle(&predecessors, v) {
+                found.insert(cycle_edges);
+            }
+        }
+        let mut cycles: Vec<NegativeCycle> = found
+            .iter()
+            .map(|cycle_edges| graph.to_negative_cycle(cycle_edges))
+            .collect();
+        cycles.sort_by(|a, b| b.rate.partial_cmp(&a.rate).unwrap_or(std::cmp::Ordering::Equal));
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC: CurrencyID = CurrencyID(0);
+    const USDT: CurrencyID = CurrencyID(1);
+    const ETH: CurrencyID = CurrencyID(2);
+
+    fn triangle_network(exchanges: &[ExchangeID]) -> Network {
+        let mut network = Network::new();
+        exchanges.iter().for_each(|e| {
+            network.add_pairs(
+                *e,
+                &[
+                    CurrencyIDPair::new(BTC, USDT),
+                    CurrencyIDPair::new(ETH, BTC),
+                    CurrencyIDPair::new(ETH, USDT),
+                ],
+            );
+        });
+        network.update_exchange_hubs();
+        network
+    }
+
+    fn rate(bid: f64, ask: f64) -> MarketRate {
+        MarketRate {
+            best_bid: bid,
+            best_ask: ask,
+        }
+    }
+
+    #[test]
+    fn detects_profitable_triangle_test() {
+        let e = ExchangeID(0);
+        let network = triangle_network(&[e]);
+        let mut rates = LiveRates::new(0.001);
+        rates.set_market_rate(e, CurrencyIDPair::new(BTC, USDT), rate(9999.0, 10000.0));
+        rates.set_market_rate(e, CurrencyIDPair::new(ETH, BTC), rate(0.0499, 0.05));
+        rates.set_market_rate(e, CurrencyIDPair::new(ETH, USDT), rate(600.0, 601.0));
+
+        let cycles = network.find_negative_cycles(&rates, &NegativeCycleSearchSettings::new_default());
+        assert_eq!(cycles.len(), 1);
+        let cycle = &cycles[0];
+        // USDT -> BTC -> ETH -> USDT
+        let expected = (1.0 / 10000.0) * (1.0 / 0.05) * 600.0 * 0.999f64.powi(3);
+        assert!((cycle.rate - expected).abs() < 1e-9);
+        assert_eq!(cycle.path.0.len(), 7);
+        assert!(cycle.path.is_same_exchange_path());
+        assert!((rates.path_rate(&cycle.path).unwrap() - cycle.rate).abs() < 1e-9);
+        assert_eq!(cycle.path.0.front(), cycle.path.0.back());
+    }
+
+    #[test]
+    fn no_cycles_on_consistent_rates_test() {
+        let exchanges = [ExchangeID(0), ExchangeID(1)];
+        let network = triangle_network(&exchanges);
+        let mut rates = LiveRates::new(0.001);
+        exchanges.iter().for_each(|e| {
+            rates.set_market_rate(*e, CurrencyIDPair::new(BTC, USDT), rate(9999.0, 10001.0));
+            rates.set_market_rate(*e, CurrencyIDPair::new(ETH, BTC), rate(0.0599, 0.0601));
+            rates.set_market_rate(*e, CurrencyIDPair::new(ETH, USDT), rate(599.0, 601.0));
+        });
+        let cycles = network.find_negative_cycles(&rates, &NegativeCycleSearchSettings::new_default());
+        assert!(cycles.is_empty());
+    }
+
+    #[test]
+    fn detects_cycles_with_transfers_test() {
+        let exchanges = [ExchangeID(0), ExchangeID(1)];
+        let network = triangle_network(&exchanges);
+        let mut rates = LiveRates::new(0.0);
+        rates.set_market_rate(exchanges[0], CurrencyIDPair::new(BTC, USDT), rate(9999.0, 10000.0));
+        rates.set_market_rate(exchanges[1], CurrencyIDPair::new(BTC, USDT), rate(10500.0, 10501.0));
+        exchanges.iter().for_each(|e| rates.set_transfer_fee(*e, BTC, 0.01));
+        exchanges.iter().for_each(|e| rates.set_transfer_fee(*e, USDT, 0.01));
+
+        let settings = NegativeCycleSearchSettings {
+            allow_transfers: false,
+            max_cycles: 10,
+        };
+        assert!(network.find_negative_cycles(&rates, &settings).is_empty());
+
+        let cycles = network.find_negative_cycles(&rates, &NegativeCycleSearchSettings::new_default());
+        assert!(!cycles.is_empty());
+        assert!(cycles[0].path.is_path_with_transfer());
+        assert!(cycles[0].rate > 1.0);
+    }
+
+    #[test]
+    fn transfer_edges_per_network_test() {
+        let exchanges = [ExchangeID(0), ExchangeID(1)];
+        let mut network = triangle_network(&exchanges);
+        let (erc20, trc20, bep20) = (NetworkID(0), NetworkID(1), NetworkID(2));
+        network.add_currency_networks(exchanges[0], USDT, &[erc20, trc20]);
+        network.add_currency_networks(exchanges[1], USDT, &[trc20, bep20]);
+        network.add_currency_networks(exchanges[0], BTC, &[bep20]);
+        network.add_currency_networks(exchanges[1], BTC, &[erc20]);
+
+        let networks_of = |network: &Network, currency: CurrencyID| -> Vec<Option<NetworkID>> {
+            network
+                .exchange_transfers()
+                .filter(|tr| tr.withdraw_exchange == exchanges[0] && tr.currency == currency)
+                .map(|tr| tr.network)
+                .collect()
+        };
+        assert_eq!(networks_of(&network, USDT), vec![Some(trc20)]);
+        // no shared network, no transfer
+        assert!(networks_of(&network, BTC).is_empty());
+        // unknown networks
+        assert_eq!(networks_of(&network, ETH), vec![None]);
+        assert!(network.one_sided_transfers().is_empty());
+
+        // only one exchange reports networks, the transfers are skipped and reported
+        network.add_currency_networks(exchanges[0], ETH, &[erc20]);
+        assert!(networks_of(&network, ETH).is_empty());
+        let skipped = network.one_sided_transfers();
+        assert_eq!(skipped.len(), 2);
+        assert!(matches!(skipped[0], CryptoTypesError::OneSidedTransferNetworks(from, to, ETH) if from == exchanges[0] && to == exchanges[1]));
+        assert!(matches!(skipped[1], CryptoTypesError::OneSidedTransferNetworks(from, to, ETH) if from == exchanges[1] && to == exchanges[0]));
+    }
+}
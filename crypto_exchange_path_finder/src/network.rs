@@ -4,14 +4,15 @@ use crypto_exchange_types::*;
 use rayon::prelude::*;
 use serde::Deserialize;
 use serde::Serialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::collections::{HashMap, HashSet, LinkedList};
+use std::convert::TryFrom;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[inline]
 fn walk_up_linearized(n: FromNode) -> (ArbitragePath, bool) {
@@ -50,6 +51,12 @@ struct ExchangeNetworkHub {
 // instead of the network have a network search struct
 pub struct Network {
     exchange_hubs: HashMap<ExchangeID, ExchangeNetworkHub>,
+    /// Networks (chains) the exchange supports for the currency transfers. Missing entries are unknown networks.
+    currency_networks: HashMap<ExchangeIDCurrencyIDPair, BTreeSet<NetworkID>>,
+    /// Markets skipped by `add_pairs` e.g. from a corrupted lexicon
+    invalid_markets: BTreeMap<ExchangeID, BTreeSet<CurrencyIDPair>>,
+    /// Messages of the errors that skipped search steps, shared by the parallel searches
+    search_errors: Mutex<BTreeSet<String>>,
     is_update_exchange_hubs: bool,
 }
 
@@ -57,10 +64,66 @@ impl Network {
     pub fn new() -> Network {
         Network {
             exchange_hubs: HashMap::new(),
+            currency_networks: HashMap::new(),
+            invalid_markets: BTreeMap::new(),
+            search_errors: Mutex::new(BTreeSet::new()),
             is_update_exchange_hubs: false,
         }
     }
 
+    /// Markets skipped by `add_pairs` as `CryptoTypesError::InvalidMarket` errors
+    pub fn invalid_markets(&self) -> Vec<CryptoTypesError> {
+        self.invalid_markets
+            .iter()
+            .flat_map(|(exchange, pairs)| {
+                pairs.iter().map(move |pair| CryptoTypesError::InvalidMarket(*exchange, *pair))
+            })
+            .collect()
+    }
+
+    /// Distinct errors of the search steps that were skipped by all the searches so far
+    pub fn search_errors(&self) -> Vec<String> {
+        self.search_errors.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Sets the networks the exchange supports for the currency transfers
+    pub fn add_currency_networks(&mut self, exchange: ExchangeID, currency: CurrencyID, networks: &[NetworkID]) {
+        self.currency_networks
+            .insert(exchange.with_currency(&currency), networks.iter().copied().collect());
+    }
+
+    fn transfer_networks(&self, from: ExchangeID, to: ExchangeID, currency: CurrencyID) -> TransferNetworks {
+        TransferNetworks::between(
+            self.currency_networks.get(&from.with_currency(&currency)),
+            self.currency_networks.get(&to.with_currency(&currency)),
+        )
+    }
+
+    /// Transfers skipped because only one of the exchanges reports the currency networks, as
+    /// `CryptoTypesError::OneSidedTransferNetworks` errors. Requires `update_exchange_hubs` to be called first.
+    pub fn one_sided_transfers(&self) -> Vec<CryptoTypesError> {
+        let mut skipped: Vec<_> = self
+            .exchange_hubs
+            .iter()
+            .flat_map(|(exchange, exchange_hub)| {
+                exchange_hub
+                    .currency_to_exchanges
+                    .iter()
+                    .flat_map(move |(currency, to_exchanges)| {
+                        to_exchanges.iter().map(move |to| (*exchange, *to, *currency))
+                    })
+            })
+            .filter(|(from, to, currency)| self.transfer_networks(*from, *to, *currency) == TransferNetworks::OneSided)
+            .collect();
+        skipped.sort();
+        skipped
+            .into_iter()
+            .map(|(from, to, currency)| CryptoTypesError::OneSidedTransferNetworks(from, to, currency))
+            .collect()
+    }
+
+    /// Adds the exchange markets. Invalid markets (same currency on both sides) are skipped,
+    /// they are reported by `invalid_markets`.
     pub fn add_pairs(&mut self, exchange: ExchangeID, cps: &[CurrencyIDPair]) -> bool {
         let mut is_updated = false;
         if !self.exchange_hubs.contains_key(&exchange) {
@@ -78,6 +141,10 @@ impl Network {
         // it should exist
         let exchange_entry = self.exchange_hubs.get_mut(&exchange).unwrap();
         for cp in cps.iter() {
+            if ExchangeMarketKey::create_from_pair(exchange, *cp).is_err() {
+                self.invalid_markets.entry(exchange).or_default().insert(*cp);
+                continue;
+            }
             let new_inserted = exchange_entry.all_supported_pairs.insert(*cp);
             is_updated = is_updated || new_inserted;
         }
@@ -177,6 +244,38 @@ impl Network {
         }
     }
 
+    /// All exchange markets/currency pairs known to the network
+    pub(crate) fn exchange_pairs(&self) -> impl Iterator<Item = (ExchangeID, CurrencyIDPair)> + '_ {
+        self.exchange_hubs.iter().flat_map(|(exchange, exchange_hub)| {
+            exchange_hub
+                .all_supported_pairs
+                .iter()
+                .map(move |pair| (*exchange, *pair))
+        })
+    }
+
+    /// All transfers, one per shared network. Requires `update_exchange_hubs` to be called first.
+    pub(crate) fn exchange_transfers(&self) -> impl Iterator<Item = TransferExchangeToExchangeCurrencyInfo> + '_ {
+        self.exchange_hubs.iter().flat_map(move |(exchange, exchange_hub)| {
+            exchange_hub
+                .currency_to_exchanges
+                .iter()
+                .flat_map(move |(currency, to_exchanges)| {
+                    to_exchanges.iter().flat_map(move |to_exchange| {
+                        self.transfer_networks(*exchange, *to_exchange, *currency)
+                            .transfers()
+                            .into_iter()
+                            .map(move |network| TransferExchangeToExchangeCurrencyInfo {
+                                withdraw_exchange: *exchange,
+                                deposit_exchange: *to_exchange,
+                                currency: *currency,
+                                network,
+                            })
+                    })
+                })
+        })
+    }
+
     // TODO make it so we decide parallel or single threded
     // probably max leve 2 or 3 single threaded => TEST TEST TEST!!!
     pub fn search_targets(
@@ -314,11 +413,20 @@ impl Network {
                 .get(&next_data.currency)
             {
                 for currency_pair in transaction_pairs {
-                    if let Some(new_next) = execute_and_connect_transaction(
+                    // invalid markets are filtered by add_pairs, a failing pair is skipped and reported
+                    let new_next = match execute_and_connect_transaction(
                         Rc::clone(&next),
                         *currency_pair,
                         &search_stop_settings,
                     ) {
+                        Ok(new_next) => new_next,
+                        Err(err) => {
+                            let message = format!("Transaction on exchange '{:?}' is skipped. {}", next_data.exchange, err);
+                            self.search_errors.lock().unwrap().insert(message);
+                            None
+                        }
+                    };
+                    if let Some(new_next) = new_next {
                         let mut leafs_append = self.search_filter(
                             !is_last_transfer,
                             target_currency,
@@ -341,18 +449,22 @@ impl Network {
             {
                 // TODO par_iter Rayon test
                 transfer_pairs.iter().for_each(|exchange| {
-                    if let Some(new_next) = execute_and_connect_transfer(
-                        Rc::clone(&next),
-                        *exchange,
-                        &search_stop_settings,
-                    ) {
-                        let mut new_leafs = self.search_filter(
-                            !is_last_transfer,
-                            target_currency,
-                            Rc::new(new_next),
+                    let networks = self.transfer_networks(next_data.exchange, *exchange, next_data.currency);
+                    for network in networks.transfers() {
+                        if let Some(new_next) = execute_and_connect_transfer(
+                            Rc::clone(&next),
+                            *exchange,
+                            network,
                             &search_stop_settings,
-                        );
-                        leafs.append(&mut new_leafs);
+                        ) {
+                            let mut new_leafs = self.search_filter(
+                                !is_last_transfer,
+                                target_currency,
+                                Rc::new(new_next),
+                                &search_stop_settings,
+                            );
+                            leafs.append(&mut new_leafs);
+                        }
                     }
                 });
             }
@@ -362,67 +474,395 @@ impl Network {
 }
 
 
+/// Version 1 stores the paths with 7 and 11 nodes and the transactions only paths with 3 and 5 pairs as arrays,
+/// every other path as an `ArbitragePath` linked list (`unknown_paths`).
+/// Version 2 stores the paths of any length by their length, the paths with transfers as the compact edges
+/// from the target (`compact_transfer_paths`) and the transactions only paths as their pairs (`tx_only_paths`).
+/// `compact_paths` holds the remaining paths e.g. multi exchange paths without transfers.
+pub const PRE_CALCED_PATHS_FORMAT_VERSION: u32 = 2;
+
+fn legacy_format_version() -> u32 {
+    1
+}
+
+/// Stored layout of both format versions. The version 1 paths are upgraded when read.
+#[derive(Deserialize)]
+struct StoredPreCalcedPaths {
+    id: BalanceExchangeCurrencyInfo,
+    /// Only used by the format version 1
+    #[serde(default)]
+    tr_7_paths: Option<Vec<[ExchangeOperationType; 7]>>,
+    /// Only used by the format version 1
+    #[serde(default)]
+    tr_11_paths: Option<Vec<[ExchangeOperationType; 11]>>,
+    /// Only used by the format version 1
+    #[serde(default)]
+    tx_only_3pairs_paths: Option<Vec<SingleExchangeTransactionOnly3PairsPath>>,
+    /// Only used by the format version 1
+    #[serde(default)]
+    tx_only_5pairs_paths: Option<Vec<SingleExchangeTransactionOnly5PairsPath>>,
+    /// Only used by the format version 1
+    #[serde(default)]
+    unknown_paths: Option<Vec<ArbitragePath>>,
+    #[serde(default)]
+    lexicon_checksum: String,
+    /// Files written before the format was versioned are version 1
+    #[serde(default = "legacy_format_version")]
+    format_version: u32,
+    #[serde(default)]
+    compact_paths: Option<Vec<CompactArbitragePath>>,
+    #[serde(default)]
+    compact_transfer_paths: FixedLengthPaths<CompactPathEdge>,
+    #[serde(default)]
+    tx_only_paths: FixedLengthPaths<CurrencyIDPair>,
+}
+
+/// Fails for unknown (newer) versions and for stored paths that can't be converted
+impl TryFrom<StoredPreCalcedPaths> for OptimizedPreCalcedPaths {
+    type Error = CryptoTypesError;
+
+    fn try_from(stored: StoredPreCalcedPaths) -> Result<Self, Self::Error> {
+        if stored.format_version > PRE_CALCED_PATHS_FORMAT_VERSION {
+            return Err(CryptoTypesError::UnsupportedFormatVersion(
+                stored.format_version,
+                PRE_CALCED_PATHS_FORMAT_VERSION,
+            ));
+        }
+        let mut optimized = OptimizedPreCalcedPaths {
+            id: stored.id,
+            lexicon_checksum: stored.lexicon_checksum,
+            format_version: PRE_CALCED_PATHS_FORMAT_VERSION,
+            compact_paths: None,
+            compact_transfer_paths: stored.compact_transfer_paths,
+            tx_only_paths: stored.tx_only_paths,
+        };
+        if stored.format_version == PRE_CALCED_PATHS_FORMAT_VERSION {
+            optimized.compact_paths = stored.compact_paths;
+            return Ok(optimized);
+        }
+        let mut paths: Vec<ArbitragePath> = stored.unknown_paths.into_iter().flatten().collect();
+        paths.extend(stored.tr_7_paths.into_iter().flatten().map(|nodes| ArbitragePath(nodes.iter().copied().collect())));
+        paths.extend(stored.tr_11_paths.into_iter().flatten().map(|nodes| ArbitragePath(nodes.iter().copied().collect())));
+        stored.tx_only_3pairs_paths.into_iter().flatten().for_each(|p| optimized.tx_only_paths.push(&p.0));
+        stored.tx_only_5pairs_paths.into_iter().flatten().for_each(|p| optimized.tx_only_paths.push(&p.0));
+        optimized.insert_paths(paths)?;
+        Ok(optimized)
+    }
+}
+
+/// Paths of a target in the current format version, the previous versions are upgraded when deserialised
 #[derive(Serialize, Deserialize)]
+#[serde(try_from = "StoredPreCalcedPaths")]
 pub struct OptimizedPreCalcedPaths {
     pub id: BalanceExchangeCurrencyInfo,
-    pub tr_7_paths: Option<Vec<ArbitragePath7Nodes>>,
-    pub tr_11_paths: Option<Vec<ArbitragePath11Nodes>>,
-    pub tx_only_3pairs_paths: Option<Vec<SingleExchangeTransactionOnly3PairsPath>>,
-    pub tx_only_5pairs_paths: Option<Vec<SingleExchangeTransactionOnly5PairsPath>>,
-    pub unknown_paths: Option<Vec<ArbitragePath>>,
+    /// Checksum of the lexicon the paths were generated from. Empty for chunks generated without one.
+    pub lexicon_checksum: String,
+    pub format_version: u32,
+    pub compact_paths: Option<Vec<CompactArbitragePath>>,
+    /// Paths with transfers starting at `id` as their compact edges keyed by the edge count
+    pub compact_transfer_paths: FixedLengthPaths<CompactPathEdge>,
+    /// Single exchange transactions only paths keyed by their pair count. Only one of the reversed paths is stored.
+    pub tx_only_paths: FixedLengthPaths<CurrencyIDPair>,
 }
 
 #[derive(Default, Debug)]
 pub struct OptimizedPreCalcedPathsStats {
     pub estimated_size_in_bytes: usize,
-    pub tr_7_paths: usize,
-    pub tr_11_paths: usize,
-    pub tx_only_3pairs_paths: usize,
-    pub tx_only_5pairs_paths: usize,
+    /// Number of paths with transfers per node count
+    pub transfer_paths: BTreeMap<usize, usize>,
+    /// Number of transactions only paths per pair count
+    pub tx_only_paths: BTreeMap<usize, usize>,
     pub unknown_paths: usize,
 }
 
+impl OptimizedPreCalcedPathsStats {
+    pub fn total_paths(&self) -> usize {
+        self.transfer_paths.values().sum::<usize>() + self.tx_only_paths.values().sum::<usize>() + self.unknown_paths
+    }
+
+    pub fn add(&mut self, other: &OptimizedPreCalcedPathsStats) {
+        self.estimated_size_in_bytes += other.estimated_size_in_bytes;
+        for (len, count) in other.transfer_paths.iter() {
+            *self.transfer_paths.entry(*len).or_insert(0) += count;
+        }
+        for (len, count) in other.tx_only_paths.iter() {
+            *self.tx_only_paths.entry(*len).or_insert(0) += count;
+        }
+        self.unknown_paths += other.unknown_paths;
+    }
+}
+
 // TODO get stats and 
 impl OptimizedPreCalcedPaths {
+    /// Stores the searched paths of the target in the current format version
+    pub fn create<I>(id: BalanceExchangeCurrencyInfo, paths: I, lexicon_checksum: String) -> Result<Self, CryptoTypesError>
+    where
+        I: IntoIterator<Item = ArbitragePath>,
+    {
+        let mut optimized = OptimizedPreCalcedPaths {
+            id,
+            lexicon_checksum,
+            format_version: PRE_CALCED_PATHS_FORMAT_VERSION,
+            compact_paths: None,
+            compact_transfer_paths: FixedLengthPaths::default(),
+            tx_only_paths: FixedLengthPaths::default(),
+        };
+        optimized.insert_paths(paths)?;
+        Ok(optimized)
+    }
+
+    /// Sorts the paths into the fixed length paths by their length. The paths without a fixed layout and the paths
+    /// with transfers that don't start at the target are stored as compact paths. Transactions only paths are
+    /// skipped if their reversed path is already stored.
+    fn insert_paths<I>(&mut self, paths: I) -> Result<(), CryptoTypesError>
+    where
+        I: IntoIterator<Item = ArbitragePath>,
+    {
+        let mut transfer_paths: BTreeSet<Vec<CompactPathEdge>> =
+            self.compact_transfer_paths.iter().map(<[_]>::to_vec).collect();
+        let mut tx_only_paths: BTreeSet<Vec<CurrencyIDPair>> = BTreeSet::new();
+        self.tx_only_paths.iter().for_each(|pairs| insert_unless_reversed(&mut tx_only_paths, pairs.to_vec()));
+        let mut compact_paths = Vec::new();
+        for path in paths {
+            if path.is_same_exchange_path() && !path.is_path_with_transfer() && path.0.len() > 1 {
+                insert_unless_reversed(&mut tx_only_paths, path.transaction_pairs());
+                continue;
+            }
+            let compact = CompactArbitragePath::try_from(&path)?;
+            if path.is_path_with_transfer() && compact.start == self.id {
+                transfer_paths.insert(compact.edges.to_vec());
+            } else {
+                compact_paths.push(compact);
+            }
+        }
+        self.compact_transfer_paths = FixedLengthPaths::default();
+        transfer_paths.iter().for_each(|edges| self.compact_transfer_paths.push(edges));
+        self.tx_only_paths = FixedLengthPaths::default();
+        tx_only_paths.iter().for_each(|pairs| self.tx_only_paths.push(pairs));
+        if !compact_paths.is_empty() {
+            self.compact_paths.get_or_insert_with(Vec::new).extend(compact_paths);
+        }
+        Ok(())
+    }
+
+    /// Paths without a fixed layout
+    pub fn arbitrage_paths(&self) -> impl Iterator<Item = Result<ArbitragePath, CryptoTypesError>> + '_ {
+        self.compact_paths.iter().flatten().map(ArbitragePath::try_from)
+    }
+
+    /// Paths with transfers starting at the target. Fails for corrupted edges.
+    pub fn transfer_paths(&self) -> impl Iterator<Item = Result<ArbitragePath, CryptoTypesError>> + '_ {
+        self.compact_transfer_paths.iter().map(move |edges| {
+            ArbitragePath::try_from(&CompactArbitragePath {
+                start: self.id,
+                edges: edges.iter().copied().collect(),
+            })
+        })
+    }
+
+    /// Transactions only paths of N pairs
+    pub fn tx_only_paths_n<const N: usize>(&self) -> impl Iterator<Item = SingleExchangeTransactionOnlyPairsPath<N>> + '_ {
+        self.tx_only_paths.arrays::<N>().map(SingleExchangeTransactionOnlyPairsPath)
+    }
+
     pub fn stats(&self) -> OptimizedPreCalcedPathsStats {
-        let tr_7_paths: usize = self.tr_7_paths.as_ref().map_or(0usize, |v| v.len());
-        let tr_11_paths: usize = self.tr_11_paths.as_ref().map_or(0usize, |v| v.len());
-        let tx_only_3pairs_paths: usize = self.tx_only_3pairs_paths.as_ref().map_or(0usize, |v| v.len());
-        let tx_only_5pairs_paths: usize = self.tx_only_5pairs_paths.as_ref().map_or(0usize, |v| v.len());
-        let unknown_paths: usize = self.unknown_paths.as_ref().map_or(0usize, |v| v.len());
+        let transfer_paths: BTreeMap<usize, usize> = self
+            .compact_transfer_paths
+            .counts()
+            .into_iter()
+            .map(|(edges, count)| (2 * edges + 1, count))
+            .collect();
+        let tx_only_paths = self.tx_only_paths.counts();
+        let unknown_paths: usize = self.compact_paths.as_ref().map_or(0usize, |v| v.len());
+        let spilled_edges: usize = self
+            .compact_paths
+            .iter()
+            .flatten()
+            .filter(|p| p.spilled())
+            .map(|p| p.edges.len())
+            .sum();
+        let transfer_edges: usize = self.compact_transfer_paths.counts().iter().map(|(len, count)| len * count).sum();
+        let tx_only_pairs: usize = tx_only_paths.iter().map(|(len, count)| len * count).sum();
 
         let estimated_size_in_bytes = 
         std::mem::size_of::<BalanceExchangeCurrencyInfo>() + 
-        std::mem::size_of::<Option<Vec<ArbitragePath7Nodes>>>() + std::mem::size_of::<ArbitragePath7Nodes>() * tr_7_paths +
-        std::mem::size_of::<Option<Vec<ArbitragePath11Nodes>>>() + std::mem::size_of::<ArbitragePath11Nodes>() * tr_11_paths +
-        std::mem::size_of::<Option<Vec<SingleExchangeTransactionOnly3PairsPath>>>() + std::mem::size_of::<SingleExchangeTransactionOnly3PairsPath>() * tx_only_3pairs_paths +
-        std::mem::size_of::<Option<Vec<SingleExchangeTransactionOnly5PairsPath>>>() + std::mem::size_of::<SingleExchangeTransactionOnly5PairsPath>() * tx_only_5pairs_paths +
-        std::mem::size_of::<Option<Vec<ArbitragePath>>>() + self.unknown_paths.iter().flat_map(|p| p.iter().map(|p| p.0.len())).sum::<usize>() * std::mem::size_of::<ArbitragePath>() ; // 
+        std::mem::size_of::<FixedLengthPaths<CompactPathEdge>>() + std::mem::size_of::<CompactPathEdge>() * transfer_edges +
+        std::mem::size_of::<FixedLengthPaths<CurrencyIDPair>>() + std::mem::size_of::<CurrencyIDPair>() * tx_only_pairs +
+        std::mem::size_of::<Option<Vec<CompactArbitragePath>>>() + std::mem::size_of::<CompactArbitragePath>() * unknown_paths +
+        std::mem::size_of::<CompactPathEdge>() * spilled_edges;
         
         OptimizedPreCalcedPathsStats {
             estimated_size_in_bytes,
-            tr_7_paths,
-            tr_11_paths,
-            tx_only_3pairs_paths,
-            tx_only_5pairs_paths,
+            transfer_paths,
+            tx_only_paths,
             unknown_paths,
         }
     }
 }
 
+fn insert_unless_reversed(paths: &mut BTreeSet<Vec<CurrencyIDPair>>, pairs: Vec<CurrencyIDPair>) {
+    let reversed: Vec<_> = pairs.iter().rev().copied().collect();
+    if !paths.contains(&reversed) {
+        paths.insert(pairs);
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct OptimizedNetworkWithLexicon {
     pub lexicon: CryptoExchangeLexicon,
     pub pre_calced_paths: BTreeMap<BalanceExchangeCurrencyInfo, OptimizedPreCalcedPaths>,
     pub search_stop_settings: SearchStopSettings,
+    /// Checksum of the lexicon the paths were generated from. Empty for networks generated without one.
+    #[serde(default)]
+    pub lexicon_checksum: String,
 }
 
 impl OptimizedNetworkWithLexicon {
+    /// Reads the network, the paths are upgraded to the current format version when deserialised.
+    /// Fails if the paths were generated from a different lexicon than the one the network holds.
     pub fn read_from_file<P: AsRef<Path>>(file_path: &P) -> anyhow::Result<OptimizedNetworkWithLexicon> {
         let mut file = File::open(file_path)?;
         let mut contents: Vec<u8> = Vec::new();
         file.read_to_end(&mut contents)?;
-        let ret = crypto_serializer::cbor_from_slice_lz4(&contents)?;
+        let ret: OptimizedNetworkWithLexicon = crypto_serializer::cbor_from_slice_lz4(&contents)?;
+        ret.verify_checksum()?;
         Ok(ret)
     }
-}
\ No newline at end of file
+
+    /// Fails if the network or any of its paths were generated from a different lexicon than the one it holds.
+    /// Empty (missing) checksums are not checked.
+    pub fn verify_checksum(&self) -> Result<(), CryptoTypesError> {
+        let checksum = self.lexicon.checksum();
+        std::iter::once(&self.lexicon_checksum)
+            .chain(self.pre_calced_paths.values().map(|paths| &paths.lexicon_checksum))
+            .filter(|found| !found.is_empty() && **found != checksum)
+            .try_for_each(|found| Err(CryptoTypesError::LexiconChecksumMismatch(checksum.clone(), found.clone())))
+    }
+
+    /// Returns the precalculated targets we can start searching from with our current balances
+    pub fn startable_targets(&self, portfolio: &Portfolio, min_available: CurrencyAmount) -> Vec<Target> {
+        portfolio.startable_targets(self.pre_calced_paths.keys(), min_available)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_skips_invalid_markets_test() {
+        let mut lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&[ExchangeSymbolsJson {
+            exchange: "kraken".to_string(),
+            symbols: ["BTC/USD", "ETH/BTC", "ETH/USD"].iter().map(|s| s.to_string()).collect(),
+        }])
+        .unwrap();
+        let kraken = ExchangeID(0);
+        let (btc, usd) = (CurrencyID(0), CurrencyID(1));
+        // corrupted after generation, generate skips same currency markets
+        let kraken_pairs = lexicon.exchange_currency_pairs.get_mut(&kraken).unwrap();
+        kraken_pairs.insert(CurrencyIDPair::new(btc, btc));
+        kraken_pairs.insert(CurrencyIDPair::new(usd, usd));
+
+        let mut network = Network::new();
+        lexicon.exchange_currency_pairs_iter().for_each(|(exchange, pairs)| {
+            network.add_pairs(*exchange, &pairs.iter().copied().collect::<Vec<_>>());
+        });
+        network.update_exchange_hubs();
+        let invalid_markets = network.invalid_markets();
+        assert_eq!(invalid_markets.len(), 2);
+        assert!(matches!(invalid_markets[0], CryptoTypesError::InvalidMarket(e, pair) if e == kraken && pair.first == btc));
+        assert!(matches!(invalid_markets[1], CryptoTypesError::InvalidMarket(e, pair) if e == kraken && pair.first == usd));
+
+        let target = kraken.with_currency(&usd);
+        let paths = network.search_targets_sync(HashSet::from([target]), &SearchStopSettings::new_default());
+        // USD -> BTC -> ETH -> USD and the reversed path
+        assert_eq!(paths[&target].len(), 2);
+
+        // only one of the reversed paths is stored
+        let optimized = OptimizedPreCalcedPaths::create(target, paths[&target].iter().cloned(), String::new()).unwrap();
+        assert_eq!(optimized.stats().tx_only_paths, BTreeMap::from([(3, 1)]));
+        assert!(optimized.compact_paths.is_none());
+        let stored = optimized.tx_only_paths_n::<3>().next().unwrap();
+        let (first, second) = interpolate_reversed_paths(kraken, usd, &stored.0).unwrap();
+        assert!(paths[&target].contains(&first) && paths[&target].contains(&second));
+        assert!(network.search_errors().is_empty());
+
+        // a failing transaction is skipped and reported once
+        let eth = CurrencyID(2);
+        let kraken_hub = network.exchange_hubs.get_mut(&kraken).unwrap();
+        kraken_hub.currency_transaction_pairs.get_mut(&usd).unwrap().insert(CurrencyIDPair::new(eth, btc));
+        let paths = network.search_targets_sync(HashSet::from([target]), &SearchStopSettings::new_default());
+        assert_eq!(paths[&target].len(), 2);
+        let search_errors = network.search_errors();
+        assert_eq!(search_errors.len(), 1);
+        assert!(search_errors[0].contains("Next currency error"));
+    }
+
+    #[derive(Serialize)]
+    struct LegacyPath7Nodes([ExchangeOperationType; 7]);
+
+    /// Chunk layout before the format version was added
+    #[derive(Serialize)]
+    struct LegacyPreCalcedPaths {
+        id: BalanceExchangeCurrencyInfo,
+        tr_7_paths: Option<Vec<LegacyPath7Nodes>>,
+        tr_11_paths: Option<Vec<[ExchangeOperationType; 11]>>,
+        tx_only_3pairs_paths: Option<Vec<SingleExchangeTransactionOnly3PairsPath>>,
+        tx_only_5pairs_paths: Option<Vec<SingleExchangeTransactionOnly5PairsPath>>,
+        unknown_paths: Option<Vec<ArbitragePath>>,
+    }
+
+    #[derive(Serialize)]
+    struct VersionedPreCalcedPaths {
+        id: BalanceExchangeCurrencyInfo,
+        format_version: u32,
+    }
+
+    #[test]
+    fn upgrade_legacy_paths_format_test() {
+        let (kraken, bitstamp, btc, usd, eth) = (ExchangeID(0), ExchangeID(1), CurrencyID(0), CurrencyID(1), CurrencyID(2));
+        let pairs = [CurrencyIDPair::new(btc, usd), CurrencyIDPair::new(eth, usd), CurrencyIDPair::new(eth, btc)];
+        let path = ArbitragePath(LinkedList::from([
+            kraken.with_currency(&btc).into(),
+            TransferExchangeToExchangeCurrencyInfo {
+                withdraw_exchange: kraken,
+                deposit_exchange: bitstamp,
+                currency: btc,
+                network: None,
+            }
+            .into(),
+            bitstamp.with_currency(&btc).into(),
+        ]));
+        // BTC -> USD -> BTC on kraken and the BTC transfer to bitstamp
+        let (mut path_7_nodes, _) = interpolate_reversed_paths(kraken, btc, &[pairs[0], pairs[0]]).unwrap();
+        path_7_nodes.0.extend(path.0.iter().skip(1).copied());
+        let nodes: Vec<_> = path_7_nodes.0.iter().copied().collect();
+        let legacy = LegacyPreCalcedPaths {
+            id: kraken.with_currency(&btc),
+            tr_7_paths: Some(vec![LegacyPath7Nodes(<[_; 7]>::try_from(nodes).unwrap())]),
+            tr_11_paths: None,
+            tx_only_3pairs_paths: Some(vec![SingleExchangeTransactionOnlyPairsPath(pairs)]),
+            tx_only_5pairs_paths: None,
+            unknown_paths: Some(vec![path.clone()]),
+        };
+        let bytes = crypto_serializer::cbor_to_vec(&legacy).unwrap();
+        let paths: OptimizedPreCalcedPaths = crypto_serializer::cbor_from_slice(&bytes).unwrap();
+        assert_eq!(paths.format_version, PRE_CALCED_PATHS_FORMAT_VERSION);
+        assert!(paths.compact_paths.is_none());
+        assert_eq!(paths.stats().transfer_paths, BTreeMap::from([(3, 1), (7, 1)]));
+        assert_eq!(paths.stats().total_paths(), 3);
+        assert_eq!(paths.transfer_paths().collect::<Result<Vec<_>, _>>().unwrap(), vec![path, path_7_nodes]);
+        assert_eq!(paths.tx_only_paths_n::<3>().next().map(|p| p.0), Some(pairs));
+
+        // the current version is read back as it is written
+        let bytes = crypto_serializer::cbor_to_vec(&paths).unwrap();
+        let read: OptimizedPreCalcedPaths = crypto_serializer::cbor_from_slice(&bytes).unwrap();
+        assert_eq!(read.compact_transfer_paths, paths.compact_transfer_paths);
+
+        let newer = VersionedPreCalcedPaths {
+            id: kraken.with_currency(&btc),
+            format_version: PRE_CALCED_PATHS_FORMAT_VERSION + 1,
+        };
+        let bytes = crypto_serializer::cbor_to_vec(&newer).unwrap();
+        assert!(crypto_serializer::cbor_from_slice::<OptimizedPreCalcedPaths>(&bytes).is_err());
+    }
+}
@@ -99,13 +99,12 @@ pub fn execute_and_connect_transaction(
     b: Rc<BalanceNode>,
     pair: CurrencyIDPair,
     search_stop_settings: &SearchStopSettings,
-) -> Option<BalanceNode> {
+) -> Result<Option<BalanceNode>, CryptoTypesError> {
     let exchange = b.operation_data.exchange;
     let c = b.operation_data.currency;
     let next_level_depth = b.level() + 1u8;
 
-    // TODO maybe return error
-    let (new_balance_currency, side) = pair.next_currency_and_side(c).expect("executeAndConnectTransaction currency is bid and sell are equal FATAL ERROR");
+    let (new_balance_currency, side) = pair.next_currency_and_side(c)?;
 
     let t_info = TransactionExchangeCurrenciesBuySellInfo {
         exchange: exchange,
@@ -117,20 +116,21 @@ pub fn execute_and_connect_transaction(
     // filter
     let filter_by_operation = Rc::clone(&t);
     if search_stop_settings.is_skip_search_node(filter_by_operation) {
-        return None;
+        return Ok(None);
     }
     let tx_balance_info = BalanceExchangeCurrencyInfo {
         exchange: exchange,
         currency: new_balance_currency,
     };
     let tx_b = BalanceNode::create(Some(t), next_level_depth, tx_balance_info);
-    Some(tx_b)
+    Ok(Some(tx_b))
 }
 
 #[inline]
 pub fn execute_and_connect_transfer(
     b: Rc<BalanceNode>,
     to_exchange: ExchangeID,
+    network: Option<NetworkID>,
     search_stop_settings: &SearchStopSettings,
 ) -> Option<BalanceNode> {
     let exchange = b.operation_data.exchange;
@@ -141,6 +141,7 @@ pub fn execute_and_connect_transfer(
         withdraw_exchange: exchange,
         deposit_exchange: to_exchange,
         currency: currency,
+        network,
     };
     let tr = Rc::new(TransferNode::create(Some(b), next_level_depth, tr_info));
     // filter
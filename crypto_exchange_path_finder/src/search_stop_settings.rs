@@ -3,7 +3,7 @@ use crypto_exchange_types::ExchangeOperationType;
 use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SearchStopSettings {
     /// Max node level indicates to stop searching further/ to deeper levels
     /// when the this node is encountered
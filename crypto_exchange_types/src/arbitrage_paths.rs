@@ -1,7 +1,7 @@
 
 use crate::{lexicon::*, id_types::*};
 use serde::{Deserialize, Serialize};
-use std::{collections::{HashSet, LinkedList, BTreeMap}, convert::TryInto};
+use std::{collections::{HashSet, LinkedList, BTreeMap}, convert::{TryFrom, TryInto}};
 
 // target from go is the BalanceExchangeCurrencyInfo 'static' Info type
 // from this target you search out the paths
@@ -10,20 +10,13 @@ pub type Target = BalanceExchangeCurrencyInfo;
 pub struct ArbitragePath(pub LinkedList<ExchangeOperationType>);
 pub type TargetKnownPaths = HashSet<ArbitragePath>;
 
-// add optimized paths
-
-#[derive(PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
-pub struct ArbitragePath7Nodes(pub [ExchangeOperationType; 7]);
-
-#[derive(PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
-pub struct ArbitragePath11Nodes(pub [ExchangeOperationType; 11]);
-
-struct SingleExchangeTransactionOnlyNPairsPath<const N: usize>([CurrencyIDPair; N]);
-
 // TODO this interpolate is used to get next
-fn interpolate_next_nodes(exchange: ExchangeID, c: CurrencyID, pair: CurrencyIDPair) -> (TransactionExchangeCurrenciesBuySellInfo, BalanceExchangeCurrencyInfo) {
-    // TODO maybe return error
-    let (new_balance_currency, side) = pair.next_currency_and_side(c).expect("interpolate_next_nodes currency is bid and sell are equal FATAL ERROR");
+fn interpolate_next_nodes(
+    exchange: ExchangeID,
+    c: CurrencyID,
+    pair: CurrencyIDPair,
+) -> Result<(TransactionExchangeCurrenciesBuySellInfo, BalanceExchangeCurrencyInfo), CryptoTypesError> {
+    let (new_balance_currency, side) = pair.next_currency_and_side(c)?;
     let tx = TransactionExchangeCurrenciesBuySellInfo {
         currency_from: c,
         currency_to: new_balance_currency,
@@ -31,10 +24,10 @@ fn interpolate_next_nodes(exchange: ExchangeID, c: CurrencyID, pair: CurrencyIDP
         side: side
     };
     let b = BalanceExchangeCurrencyInfo {currency: new_balance_currency, exchange: exchange};
-    (tx, b)
+    Ok((tx, b))
 }
 
-fn tx_to_currency_id_pairs(tx: &TransactionExchangeCurrenciesBuySellInfo) -> CurrencyIDPair {
+pub(crate) fn tx_to_currency_id_pairs(tx: &TransactionExchangeCurrenciesBuySellInfo) -> CurrencyIDPair {
     let (first, second) = match tx.side {
         TransactionSide::BUY => (tx.currency_from, tx.currency_to),
         TransactionSide::SELL => (tx.currency_to, tx.currency_from),
@@ -42,59 +35,24 @@ fn tx_to_currency_id_pairs(tx: &TransactionExchangeCurrenciesBuySellInfo) -> Cur
     CurrencyIDPair::new(first, second)
 }
 
-impl<const N: usize> SingleExchangeTransactionOnlyNPairsPath<N> {
-    #[inline(always)]
-    fn get_start_end_currency(arr: &[CurrencyIDPair; N]) -> Option<CurrencyID> {
-        let first_pair = arr.first()?;
-        let last_pair = arr.last()?;
-        let (first_c1, first_c2) = (first_pair.first, first_pair.second);
-        let (last_c1, last_c2) = (last_pair.first, last_pair.second);
-        if first_c1.eq(&last_c1) || first_c1.eq(&last_c2) {
-            Some(first_c1)
-        } else if first_c2.eq(&last_c1) || first_c2.eq(&last_c2) {
-            Some(first_c2)
-        } else {
-            None
-        }
-    }
-
-    #[inline(always)]
-    pub fn is_arbitrage_path_reversed(lhs: &[CurrencyIDPair; N], rhs: &[CurrencyIDPair; N]) -> bool {
-        let mut zipped = lhs.iter().zip(rhs.iter().rev());
-        zipped.all(|(a,b)| a.eq(&b))
-    }
-}
 
 pub trait SingleExchangeTransactionOnlyPath {
     fn get_start_end_currency(&self) -> Option<CurrencyID>;
     fn is_arbitrage_path_reversed(&self, rhs: &Self) -> bool;
 }
 
-pub fn merge_reversed_paths<T: SingleExchangeTransactionOnlyPath>(vs: Vec<T>) -> Vec<T> {
-    let mut ret_paths = Vec::new();
-    let mut tx_only_3pairs_path_drain: Vec<T> = vs;
-    let size = tx_only_3pairs_path_drain.len();
-    for _ in 0..size {
-        let poped = tx_only_3pairs_path_drain.pop();
-        if let Some(poped) = poped {
-            // retain those that aren't duplicates
-            tx_only_3pairs_path_drain.retain(|other| poped.is_arbitrage_path_reversed(&other) == false);
-            // TODO push the poped one back
-            ret_paths.push(poped);
-        } else {
-            break;
-        }
-    }
-    ret_paths
-}
-
-pub fn interpolate_reversed_paths(exchange: ExchangeID, c: CurrencyID, pairs: &[CurrencyIDPair]) -> (ArbitragePath, ArbitragePath) {
+/// Fails if a pair doesn't continue from the previous currency e.g. a corrupted path or an invalid market.
+pub fn interpolate_reversed_paths(
+    exchange: ExchangeID,
+    c: CurrencyID,
+    pairs: &[CurrencyIDPair],
+) -> Result<(ArbitragePath, ArbitragePath), CryptoTypesError> {
     let start = BalanceExchangeCurrencyInfo {currency: c, exchange: exchange};
     let mut first: LinkedList<ExchangeOperationType> = LinkedList::default();
     let mut start_c = c;
     first.push_back(start.into());
     for pair in pairs.iter() {
-        let (tx, b) = interpolate_next_nodes(exchange, start_c, pair.clone());
+        let (tx, b) = interpolate_next_nodes(exchange, start_c, pair.clone())?;
         start_c = b.currency;
         first.push_back(tx.into());
         first.push_back(b.into());
@@ -106,14 +64,14 @@ pub fn interpolate_reversed_paths(exchange: ExchangeID, c: CurrencyID, pairs: &[
     let mut start_c = c;
     second.push_back(start.into());
     for pair in pairs.iter().rev() {
-        let (tx, b) = interpolate_next_nodes(exchange, start_c, pair.clone());
+        let (tx, b) = interpolate_next_nodes(exchange, start_c, pair.clone())?;
         start_c = b.currency;
         second.push_back(tx.into());
         second.push_back(b.into());
     }
     let second = ArbitragePath(second);
 
-    (first, second)
+    Ok((first, second))
 }
 
 
@@ -122,132 +80,152 @@ pub fn interpolate_reversed_paths(exchange: ExchangeID, c: CurrencyID, pairs: &[
 /// Exchange id is saved outside. CurrencyIDPair can tell us what is the side.
 /// The first start end currency can be also deducted from the shared first and last pair
 /// but we should probably keep this known start-end currency outside. TODO think about this.
-#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
-pub struct SingleExchangeTransactionOnly3PairsPath(pub [CurrencyIDPair; 3]);
-
-impl SingleExchangeTransactionOnlyPath for SingleExchangeTransactionOnly3PairsPath {
-    #[inline(always)]
-    fn get_start_end_currency(&self) -> Option<CurrencyID> {
-        SingleExchangeTransactionOnlyNPairsPath::get_start_end_currency(&self.0)
-    }
-    #[inline(always)]
-    fn is_arbitrage_path_reversed(&self, rhs: &Self) -> bool {
-        SingleExchangeTransactionOnlyNPairsPath::is_arbitrage_path_reversed(&self.0, &rhs.0)
-    }
-}
+#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct SingleExchangeTransactionOnlyPairsPath<const N: usize>(
+    #[serde(with = "fixed_array")] pub [CurrencyIDPair; N],
+);
 
-#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
-pub struct SingleExchangeTransactionOnly5PairsPath(pub [CurrencyIDPair; 5]);
+pub type SingleExchangeTransactionOnly3PairsPath = SingleExchangeTransactionOnlyPairsPath<3>;
+pub type SingleExchangeTransactionOnly5PairsPath = SingleExchangeTransactionOnlyPairsPath<5>;
 
-impl SingleExchangeTransactionOnlyPath for SingleExchangeTransactionOnly5PairsPath {
+impl<const N: usize> SingleExchangeTransactionOnlyPath for SingleExchangeTransactionOnlyPairsPath<N> {
     #[inline(always)]
     fn get_start_end_currency(&self) -> Option<CurrencyID> {
-        SingleExchangeTransactionOnlyNPairsPath::get_start_end_currency(&self.0)
+        let first_pair = self.0.first()?;
+        let last_pair = self.0.last()?;
+        let (first_c1, first_c2) = (first_pair.first, first_pair.second);
+        let (last_c1, last_c2) = (last_pair.first, last_pair.second);
+        if first_c1.eq(&last_c1) || first_c1.eq(&last_c2) {
+            Some(first_c1)
+        } else if first_c2.eq(&last_c1) || first_c2.eq(&last_c2) {
+            Some(first_c2)
+        } else {
+            None
+        }
     }
+
     #[inline(always)]
     fn is_arbitrage_path_reversed(&self, rhs: &Self) -> bool {
-        SingleExchangeTransactionOnlyNPairsPath::is_arbitrage_path_reversed(&self.0, &rhs.0)
+        let mut zipped = self.0.iter().zip(rhs.0.iter().rev());
+        zipped.all(|(a,b)| a.eq(&b))
     }
 }
 
-impl TryInto<ArbitragePath7Nodes> for ArbitragePath {
+/// Succeeds for single exchange paths of exactly N transactions
+impl<const N: usize> TryFrom<ArbitragePath> for SingleExchangeTransactionOnlyPairsPath<N> {
     type Error = ArbitragePath;
 
-    fn try_into(self) -> Result<ArbitragePath7Nodes, Self::Error> {
-        let len = self.0.len();
-        if len == 7 && self.is_path_with_transfer() {
-            let v: Vec<_> = self.0.into_iter().collect();
-            let ret: [ExchangeOperationType; 7] = [
-                v[0],
-                v[1],
-                v[2],
-                v[3],
-                v[4],
-                v[5],
-                v[6],
-            ];
-            Ok(ArbitragePath7Nodes(ret))
-        } else {
-            Err(self)
+    fn try_from(path: ArbitragePath) -> Result<Self, Self::Error> {
+        if path.0.len() != 2 * N + 1 || !path.is_same_exchange_path() {
+            return Err(path);
         }
+        path.transaction_pairs()
+            .try_into()
+            .map(SingleExchangeTransactionOnlyPairsPath)
+            .map_err(|_| path)
     }
 }
 
-impl TryInto<ArbitragePath11Nodes> for ArbitragePath {
-    type Error = ArbitragePath;
+/// Fixed length paths of any length, keyed by the length of one path (nodes of the paths with transfers,
+/// pairs of the transactions only paths). The paths of a length are stored flattened in one buffer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixedLengthPaths<T>(pub BTreeMap<usize, Vec<T>>);
 
-    fn try_into(self) -> Result<ArbitragePath11Nodes, Self::Error> {
-        let len = self.0.len();
-        if len == 11 && self.is_path_with_transfer() {
-            let v: Vec<_> = self.0.into_iter().collect();
-            let ret: [ExchangeOperationType; 11] = [
-                v[0],
-                v[1],
-                v[2],
-                v[3],
-                v[4],
-                v[5],
-                v[6],
-                v[7],
-                v[8],
-                v[9],
-                v[10],
-            ];
-            Ok(ArbitragePath11Nodes(ret))
-        } else {
-            Err(self)
-        }
+impl<T> Default for FixedLengthPaths<T> {
+    fn default() -> Self {
+        FixedLengthPaths(BTreeMap::new())
     }
 }
 
-impl TryInto<SingleExchangeTransactionOnly3PairsPath> for ArbitragePath {
-    type Error = ArbitragePath;
-
-    fn try_into(self) -> Result<SingleExchangeTransactionOnly3PairsPath, Self::Error> {
-        let len = self.0.len();
-        if len == 7 && self.is_same_exchange_path() {
-            let v: Vec<_> = self.0.into_iter().flat_map(|op| if let ExchangeOperationType::Transaction(tx) = op {
-                Some(tx_to_currency_id_pairs(&tx))
-            } else {
-                None
-            }).collect();
-            let ret: [CurrencyIDPair; 3] = [
-                v[0],
-                v[1],
-                v[2],
-            ];
-            
-            Ok(SingleExchangeTransactionOnly3PairsPath(ret))
-        } else {
-            Err(self)
+impl<T: Copy> FixedLengthPaths<T> {
+    /// Empty paths are skipped
+    pub fn push(&mut self, path: &[T]) {
+        if !path.is_empty() {
+            self.0.entry(path.len()).or_default().extend_from_slice(path);
         }
     }
+
+    /// Paths of the length. A trailing incomplete path of a corrupted buffer is skipped.
+    pub fn paths(&self, len: usize) -> impl Iterator<Item = &[T]> + '_ {
+        self.0
+            .get(&len)
+            .filter(|_| len > 0)
+            .into_iter()
+            .flat_map(move |buffer| buffer.chunks_exact(len))
+    }
+
+    /// Paths of the length N as arrays
+    pub fn arrays<const N: usize>(&self) -> impl Iterator<Item = [T; N]> + '_ {
+        self.paths(N).filter_map(|path| path.try_into().ok())
+    }
+
+    /// All paths ordered by their length
+    pub fn iter(&self) -> impl Iterator<Item = &[T]> + '_ {
+        self.0.keys().flat_map(move |len| self.paths(*len))
+    }
+
+    /// Number of paths per length
+    pub fn counts(&self) -> BTreeMap<usize, usize> {
+        self.0
+            .iter()
+            .filter(|(len, _)| **len > 0)
+            .map(|(len, buffer)| (*len, buffer.len() / len))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts().values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
-impl TryInto<SingleExchangeTransactionOnly5PairsPath> for ArbitragePath {
-    type Error = ArbitragePath;
+/// Serde of arrays of any length, serde derives arrays only up to 32 elements.
+/// Arrays are tuples of N elements like the arrays serde derives so the format is the same.
+mod fixed_array {
+    use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+    use serde::ser::{Serialize, SerializeTuple, Serializer};
+    use std::{convert::TryInto, fmt, marker::PhantomData};
+
+    pub fn serialize<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for item in array.iter() {
+            tuple.serialize_element(item)?;
+        }
+        tuple.end()
+    }
 
-    fn try_into(self) -> Result<SingleExchangeTransactionOnly5PairsPath, Self::Error> {
-        let len = self.0.len();
-        if len == 11 && self.is_same_exchange_path() {
-            let v: Vec<_> = self.0.into_iter().flat_map(|op| if let ExchangeOperationType::Transaction(tx) = op {
-                Some(tx_to_currency_id_pairs(&tx))
-            } else {
-                None
-            }).collect();
-            let ret: [CurrencyIDPair; 5] = [
-                v[0],
-                v[1],
-                v[2],
-                v[3],
-                v[4],
-            ];
-            
-            Ok(SingleExchangeTransactionOnly5PairsPath(ret))
-        } else {
-            Err(self)
+    struct ArrayVisitor<T, const N: usize>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>, const N: usize> Visitor<'de> for ArrayVisitor<T, N> {
+        type Value = [T; N];
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "an array of {} elements", N)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut items = Vec::with_capacity(N);
+            for i in 0..N {
+                items.push(seq.next_element()?.ok_or_else(|| A::Error::invalid_length(i, &self))?);
+            }
+            items.try_into().map_err(|_| A::Error::invalid_length(N, &self))
         }
     }
+
+    pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        deserializer.deserialize_tuple(N, ArrayVisitor::<T, N>(PhantomData))
+    }
 }
 
 impl ArbitragePath {
@@ -255,6 +233,17 @@ impl ArbitragePath {
         ArbitragePath(LinkedList::new())
     }
 
+    /// Currency pairs of the transactions in the path order
+    pub fn transaction_pairs(&self) -> Vec<CurrencyIDPair> {
+        self.0
+            .iter()
+            .filter_map(|op| match op {
+                ExchangeOperationType::Transaction(tx) => Some(tx_to_currency_id_pairs(tx)),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn is_path_with_transfer(&self) -> bool {
         self.0.iter().any(|op| {
             matches!(op, ExchangeOperationType::Transfer(_))
@@ -279,6 +268,42 @@ impl ArbitragePath {
         }
     }
 
+    /// Splits the path into (from balance, operation, to balance) steps. Fails if the path doesn't alternate
+    /// between balance and operation nodes or if an operation doesn't connect its surrounding balances.
+    pub fn operation_steps(
+        &self,
+    ) -> Result<Vec<(BalanceExchangeCurrencyInfo, ExchangeOperationType, BalanceExchangeCurrencyInfo)>, CryptoTypesError> {
+        let nodes: Vec<_> = self.0.iter().collect();
+        nodes
+            .iter()
+            .enumerate()
+            .skip(1)
+            .step_by(2)
+            .map(|(i, op)| {
+                let (from, to) = match (nodes[i - 1], nodes.get(i + 1)) {
+                    (ExchangeOperationType::Balance(from), Some(ExchangeOperationType::Balance(to))) => (*from, *to),
+                    _ => return Err(CryptoTypesError::DisconnectedPath(i, **op)),
+                };
+                let is_connected = match op {
+                    ExchangeOperationType::Balance(_) => false,
+                    ExchangeOperationType::Transaction(tx) => {
+                        from == tx.exchange.with_currency(&tx.currency_from)
+                            && to == tx.exchange.with_currency(&tx.currency_to)
+                    }
+                    ExchangeOperationType::Transfer(tr) => {
+                        from == tr.withdraw_exchange.with_currency(&tr.currency)
+                            && to == tr.deposit_exchange.with_currency(&tr.currency)
+                    }
+                };
+                if is_connected {
+                    Ok((from, **op, to))
+                } else {
+                    Err(CryptoTypesError::DisconnectedPath(i, **op))
+                }
+            })
+            .collect()
+    }
+
     pub fn is_arbitrage_path_reversed(&self, cmp: &Self) -> bool {
         let mut zipped = self.0.iter().zip(cmp.0.iter().rev());
         zipped.all(|(a,b)| {
@@ -404,40 +429,6 @@ impl PrintPath for ArbitragePath {
     }
 }
 
-impl PrintPath for ArbitragePath7Nodes {
-    fn string_id(&self) -> String {
-        print_path::string_id(self.0.iter())
-    }
-
-    fn to_named_exchanges_currency_json(
-        &self,
-        lexicon: &CryptoExchangeLexicon,
-    ) -> Result<String, String> {
-        print_path::to_named_exchanges_currency_json(self.0.iter(), &lexicon) 
-    }
-
-    fn path_node_count(&self) -> usize {
-        self.0.len()
-    }
-}
-
-impl PrintPath for ArbitragePath11Nodes {
-    fn string_id(&self) -> String {
-        print_path::string_id(self.0.iter())
-    }
-
-    fn to_named_exchanges_currency_json(
-        &self,
-        lexicon: &CryptoExchangeLexicon,
-    ) -> Result<String, String> {
-        print_path::to_named_exchanges_currency_json(self.0.iter(), &lexicon) 
-    }
-
-    fn path_node_count(&self) -> usize {
-        self.0.len()
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -447,4 +438,68 @@ mod tests {
         
     }
 
+    #[test]
+    fn interpolate_corrupted_pairs_test() {
+        let (e, btc, usd, eth) = (ExchangeID(0), CurrencyID(0), CurrencyID(1), CurrencyID(2));
+        let pairs = [CurrencyIDPair::new(btc, usd), CurrencyIDPair::new(eth, btc), CurrencyIDPair::new(eth, usd)];
+        let (first, second) = interpolate_reversed_paths(e, usd, &pairs).unwrap();
+        assert_eq!(first.0.len(), 7);
+        assert!(first.is_arbitrage_path_reversed(&second));
+
+        // the second pair doesn't continue from BTC
+        let corrupted = [CurrencyIDPair::new(btc, usd), CurrencyIDPair::new(eth, usd), CurrencyIDPair::new(eth, usd)];
+        assert!(matches!(
+            interpolate_reversed_paths(e, usd, &corrupted),
+            Err(CryptoTypesError::NextCurrency(c, _)) if c == btc
+        ));
+        let same_currency = [CurrencyIDPair::new(usd, usd)];
+        assert!(interpolate_reversed_paths(e, usd, &same_currency).is_err());
+    }
+
+    #[test]
+    fn fixed_length_paths_test() {
+        let (kraken, bitstamp, btc, usd, eth) = (ExchangeID(0), ExchangeID(1), CurrencyID(0), CurrencyID(1), CurrencyID(2));
+        let pairs = [
+            CurrencyIDPair::new(btc, usd),
+            CurrencyIDPair::new(eth, btc),
+            CurrencyIDPair::new(eth, usd),
+            CurrencyIDPair::new(btc, usd),
+            CurrencyIDPair::new(btc, usd),
+            CurrencyIDPair::new(btc, usd),
+            CurrencyIDPair::new(btc, usd),
+        ];
+        let (tx_only, _) = interpolate_reversed_paths(kraken, usd, &pairs).unwrap();
+        let path = SingleExchangeTransactionOnlyPairsPath::<7>::try_from(tx_only.clone()).unwrap();
+        assert_eq!(path.0, pairs);
+        assert!(SingleExchangeTransactionOnly5PairsPath::try_from(tx_only.clone()).is_err());
+
+        let mut with_transfer = tx_only;
+        with_transfer.0.push_back(TransferExchangeToExchangeCurrencyInfo {
+            withdraw_exchange: kraken,
+            deposit_exchange: bitstamp,
+            currency: usd,
+            network: None,
+        }.into());
+        with_transfer.0.push_back(bitstamp.with_currency(&usd).into());
+        assert!(SingleExchangeTransactionOnlyPairsPath::<8>::try_from(with_transfer).is_err());
+
+        // arrays longer than 32 elements keep the serde format of the derived arrays
+        let long_path = SingleExchangeTransactionOnlyPairsPath([CurrencyIDPair::new(btc, usd); 40]);
+        let bytes = serde_json::to_vec(&long_path).unwrap();
+        assert_eq!(serde_json::from_slice::<SingleExchangeTransactionOnlyPairsPath<40>>(&bytes).unwrap(), long_path);
+        assert!(serde_json::from_slice::<SingleExchangeTransactionOnlyPairsPath<41>>(&bytes).is_err());
+        let short_path = SingleExchangeTransactionOnlyPairsPath([pairs[0], pairs[1], pairs[2]]);
+        assert_eq!(serde_json::to_string(&short_path).unwrap(), serde_json::to_string(&short_path.0).unwrap());
+
+        let mut fixed = FixedLengthPaths::default();
+        fixed.push(&pairs);
+        fixed.push(&pairs[..3]);
+        fixed.push(&pairs[3..]);
+        fixed.push(&[]);
+        assert_eq!(fixed.len(), 3);
+        assert_eq!(fixed.counts(), BTreeMap::from([(3, 1), (4, 1), (7, 1)]));
+        assert_eq!(fixed.arrays::<7>().next(), Some(pairs));
+        assert_eq!(fixed.iter().next(), Some(&pairs[..3]));
+        assert_eq!(fixed.paths(0).count(), 0);
+    }
 }
@@ -0,0 +1,250 @@
+use crate::id_types::*;
+use crate::lexicon::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CcxtMinMaxJson {
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+/// CCXT precision. Depending on the exchange `precisionMode` this is the number of decimals or the tick size.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CcxtPrecisionJson {
+    #[serde(default)]
+    pub amount: Option<f64>,
+    #[serde(default)]
+    pub price: Option<f64>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CcxtLimitsJson {
+    #[serde(default)]
+    pub amount: CcxtMinMaxJson,
+    #[serde(default)]
+    pub price: CcxtMinMaxJson,
+    #[serde(default)]
+    pub cost: CcxtMinMaxJson,
+}
+
+/// Single market of the CCXT `exchange.markets` structure. Only the fields we use are deserialized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CcxtMarketJson {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub symbol: String,
+    #[serde(default)]
+    pub base: Option<String>,
+    #[serde(default)]
+    pub quote: Option<String>,
+    #[serde(default)]
+    pub settle: Option<String>,
+    #[serde(default, rename = "type")]
+    pub market_type: Option<String>,
+    #[serde(default)]
+    pub spot: Option<bool>,
+    /// CCXT leaves this undefined when the exchange doesn't report it so a missing value is treated as active
+    #[serde(default)]
+    pub active: Option<bool>,
+    #[serde(default)]
+    pub precision: CcxtPrecisionJson,
+    #[serde(default)]
+    pub limits: CcxtLimitsJson,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CcxtExchangeMarketsJson {
+    pub exchange: String,
+    /// Markets keyed by the CCXT unified symbol
+    pub markets: BTreeMap<String, CcxtMarketJson>,
+}
+
+/// Exchange market data stored in the lexicon next to the market currency pair
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketInfo {
+    /// Exchange specific market ID used by the exchange API e.g. 'XXBTZUSD'
+    pub exchange_market_id: Option<String>,
+    pub precision: CcxtPrecisionJson,
+    pub limits: CcxtLimitsJson,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkipReason {
+    Inactive,
+    /// Swap, future, option etc.
+    NotSpot(String),
+    /// Spot markets don't have a settle currency
+    Settled(String),
+    MissingBaseOrQuote,
+    SameBaseAndQuote,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Inactive => write!(f, "inactive market"),
+            SkipReason::NotSpot(market_type) => write!(f, "not a spot market (type '{}')", market_type),
+            SkipReason::Settled(settle) => write!(f, "settled in '{}'", settle),
+            SkipReason::MissingBaseOrQuote => write!(f, "missing base or quote currency"),
+            SkipReason::SameBaseAndQuote => write!(f, "same base and quote currency"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedSymbol {
+    pub exchange: String,
+    pub symbol: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct CcxtImportReport {
+    pub skipped: Vec<SkippedSymbol>,
+    #[serde(skip)]
+    pub warnings: Vec<LexiconWarning>,
+}
+
+impl CcxtMarketJson {
+    fn skip_reason(&self) -> Option<SkipReason> {
+        if self.active == Some(false) {
+            return Some(SkipReason::Inactive);
+        }
+        match (&self.market_type, self.spot) {
+            (Some(market_type), _) if market_type != "spot" => return Some(SkipReason::NotSpot(market_type.clone())),
+            (None, Some(false)) => return Some(SkipReason::NotSpot("unknown".to_string())),
+            _ => (),
+        }
+        if let Some(settle) = &self.settle {
+            return Some(SkipReason::Settled(settle.clone()));
+        }
+        match (&self.base, &self.quote) {
+            (Some(base), Some(quote)) if base.is_empty() || quote.is_empty() => Some(SkipReason::MissingBaseOrQuote),
+            (Some(base), Some(quote)) if base == quote => Some(SkipReason::SameBaseAndQuote),
+            (Some(_), Some(_)) => None,
+            _ => Some(SkipReason::MissingBaseOrQuote),
+        }
+    }
+}
+
+impl CryptoExchangeLexicon {
+    /// Creates the lexicon from the full CCXT markets structure. Only active spot markets are used,
+    /// the currencies are taken from the market base and quote (not from the symbol) and the market
+    /// precision and limits are stored in the lexicon. Every skipped market is reported.
+    pub fn create_from_ccxt_markets(
+        exchanges_markets: &[CcxtExchangeMarketsJson],
+        normalisation: &SymbolNormalisationJson,
+        fiat_currencies: &[CurrencyDefinitionJson],
+        stable_currencies: &[CurrencyDefinitionJson],
+    ) -> Result<(Self, CcxtImportReport), CryptoTypesError> {
+        let mut report = CcxtImportReport::default();
+        let mut ex_symbols: Vec<ExchangeSymbolsJson> = Vec::with_capacity(exchanges_markets.len());
+        let mut spot_markets: Vec<(&str, &CcxtMarketJson)> = Vec::new();
+        for exchange_markets in exchanges_markets {
+            let mut symbols = Vec::with_capacity(exchange_markets.markets.len());
+            for market in exchange_markets.markets.values() {
+                match market.skip_reason() {
+                    Some(reason) => report.skipped.push(SkippedSymbol {
+                        exchange: exchange_markets.exchange.clone(),
+                        symbol: market.symbol.clone(),
+                        reason,
+                    }),
+                    None => {
+                        let (base, quote) = (market.base.as_ref().unwrap(), market.quote.as_ref().unwrap());
+                        symbols.push(format!("{}/{}", base, quote));
+                        spot_markets.push((&exchange_markets.exchange, market));
+                    }
+                }
+            }
+            ex_symbols.push(ExchangeSymbolsJson {
+                exchange: exchange_markets.exchange.clone(),
+                symbols,
+            });
+        }
+        let (mut lexicon, warnings) = Self::create_from_exchange_symbols_full(
+            &ex_symbols,
+            normalisation,
+            fiat_currencies,
+            stable_currencies,
+        )?;
+        report.warnings = warnings;
+        for (exchange, market) in spot_markets {
+            let currency_id = |symbol: &Option<String>| {
+                let symbol = symbol.as_deref().unwrap_or_default();
+                lexicon.currencies.get_id(normalisation.canonical(exchange, symbol)).copied()
+            };
+            let (exchange, base, quote) = match (
+                lexicon.exchanges.get_id(exchange).copied(),
+                currency_id(&market.base),
+                currency_id(&market.quote),
+            ) {
+                (Some(exchange), Some(base), Some(quote)) => (exchange, base, quote),
+                _ => continue,
+            };
+            // the first market is kept if the exchange lists the same market under different aliases
+            lexicon
+                .markets
+                .entry(exchange)
+                .or_default()
+                .entry(CurrencyIDPair::new(base, quote))
+                .or_insert_with(|| MarketInfo {
+                    exchange_market_id: market.id.clone(),
+                    precision: market.precision,
+                    limits: market.limits,
+                });
+        }
+        Ok((lexicon, report))
+    }
+
+    pub fn market_info(&self, exchange: &ExchangeID, pair: &CurrencyIDPair) -> Option<&MarketInfo> {
+        self.markets.get(exchange)?.get(pair)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CCXT_MARKETS: &str = r#"[{
+        "exchange": "kraken",
+        "markets": {
+            "BTC/USD": {"id": "XXBTZUSD", "symbol": "BTC/USD", "base": "BTC", "quote": "USD", "type": "spot", "spot": true,
+                "active": true, "precision": {"amount": 8, "price": 1}, "limits": {"amount": {"min": 0.0001}}},
+            "ETH/USD": {"symbol": "ETH/USD", "base": "ETH", "quote": "USD"},
+            "LUNA/USD": {"symbol": "LUNA/USD", "base": "LUNA", "quote": "USD", "active": false},
+            "BTC/USD:USD": {"symbol": "BTC/USD:USD", "base": "BTC", "quote": "USD", "settle": "USD", "type": "swap"},
+            "ETH/BTC:BTC": {"symbol": "ETH/BTC:BTC", "base": "ETH", "quote": "BTC", "settle": "BTC", "spot": true}
+        }
+    }]"#;
+
+    #[test]
+    fn import_active_spot_markets_test() {
+        let markets: Vec<CcxtExchangeMarketsJson> = serde_json::from_str(CCXT_MARKETS).unwrap();
+        let (lexicon, report) =
+            CryptoExchangeLexicon::create_from_ccxt_markets(&markets, &SymbolNormalisationJson::default(), &[], &[]).unwrap();
+        let kraken = *lexicon.exchanges.get_id("kraken").unwrap();
+        assert_eq!(lexicon.exchange_currency_pairs[&kraken].len(), 2);
+        assert_eq!(lexicon.currencies.get_id("LUNA"), None);
+
+        let btc = *lexicon.currencies.get_id("BTC").unwrap();
+        let usd = *lexicon.currencies.get_id("USD").unwrap();
+        let info = lexicon.market_info(&kraken, &CurrencyIDPair::new(btc, usd)).unwrap();
+        assert_eq!(info.exchange_market_id.as_deref(), Some("XXBTZUSD"));
+        assert_eq!(info.precision.amount, Some(8.0));
+        assert_eq!(info.limits.amount.min, Some(0.0001));
+
+        let reasons: Vec<_> = report.skipped.iter().map(|s| (s.symbol.as_str(), s.reason.clone())).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("BTC/USD:USD", SkipReason::NotSpot("swap".to_string())),
+                ("ETH/BTC:BTC", SkipReason::Settled("BTC".to_string())),
+                ("LUNA/USD", SkipReason::Inactive),
+            ]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::arbitrage_paths::*;
+use crate::id_types::*;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use std::collections::LinkedList;
+use std::convert::TryFrom;
+
+/// Edges stored inline before the path spills to the heap. Covers the paths of networks up to `max_level` 6.
+pub const COMPACT_PATH_INLINE_EDGES: usize = 6;
+
+/// Edge of a compact path. The exchange and the currency the edge starts from are the ones of the previous balance.
+#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Copy, Clone, Serialize, Deserialize)]
+pub enum CompactPathEdge {
+    /// Transaction on the market of the current exchange. The side is derived from the current currency.
+    Transaction(CurrencyIDPair),
+    /// Transfer of the current currency to the deposit exchange
+    Transfer(ExchangeID, Option<NetworkID>),
+}
+
+/// `ArbitragePath` without the intermediate balance nodes and the repeated exchange and currency IDs.
+/// The balances are derived by walking the edges from the start balance.
+#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Clone, Serialize, Deserialize)]
+pub struct CompactArbitragePath {
+    pub start: BalanceExchangeCurrencyInfo,
+    pub edges: SmallVec<[CompactPathEdge; COMPACT_PATH_INLINE_EDGES]>,
+}
+
+impl CompactArbitragePath {
+    /// Number of nodes of the equivalent `ArbitragePath`
+    pub fn path_node_count(&self) -> usize {
+        2 * self.edges.len() + 1
+    }
+
+    /// True if the edges are stored on the heap
+    pub fn spilled(&self) -> bool {
+        self.edges.spilled()
+    }
+}
+
+/// Fails if the path doesn't start with a balance, isn't connected or has a market with the same currency on both sides
+impl TryFrom<&ArbitragePath> for CompactArbitragePath {
+    type Error = CryptoTypesError;
+
+    fn try_from(path: &ArbitragePath) -> Result<Self, Self::Error> {
+        let start = match path.0.front() {
+            Some(ExchangeOperationType::Balance(start)) => *start,
+            _ => return Err(CryptoTypesError::InvalidPathStart),
+        };
+        let edges = path
+            .operation_steps()?
+            .into_iter()
+            .map(|(_, op, _)| match op {
+                ExchangeOperationType::Transaction(tx) if tx.currency_from == tx.currency_to => {
+                    Err(CryptoTypesError::InvalidMarket(tx.exchange, tx_to_currency_id_pairs(&tx)))
+                }
+                ExchangeOperationType::Transaction(tx) => Ok(CompactPathEdge::Transaction(tx_to_currency_id_pairs(&tx))),
+                ExchangeOperationType::Transfer(tr) => Ok(CompactPathEdge::Transfer(tr.deposit_exchange, tr.network)),
+                ExchangeOperationType::Balance(_) => unreachable!("operation steps don't yield balances"),
+            })
+            .collect::<Result<_, CryptoTypesError>>()?;
+        Ok(CompactArbitragePath { start, edges })
+    }
+}
+
+/// Fails if a transaction pair doesn't contain the current currency e.g. a corrupted path
+impl TryFrom<&CompactArbitragePath> for ArbitragePath {
+    type Error = CryptoTypesError;
+
+    fn try_from(path: &CompactArbitragePath) -> Result<Self, Self::Error> {
+        let mut nodes: LinkedList<ExchangeOperationType> = LinkedList::new();
+        let mut balance = path.start;
+        nodes.push_back(balance.into());
+        for edge in path.edges.iter() {
+            match *edge {
+                CompactPathEdge::Transaction(pair) => {
+                    let (currency_to, side) = pair.next_currency_and_side(balance.currency)?;
+                    nodes.push_back(
+                        TransactionExchangeCurrenciesBuySellInfo {
+                            exchange: balance.exchange,
+                            side,
+                            currency_from: balance.currency,
+                            currency_to,
+                        }
+                        .into(),
+                    );
+                    balance.currency = currency_to;
+                }
+                CompactPathEdge::Transfer(deposit_exchange, network) => {
+                    nodes.push_back(
+                        TransferExchangeToExchangeCurrencyInfo {
+                            withdraw_exchange: balance.exchange,
+                            deposit_exchange,
+                            currency: balance.currency,
+                            network,
+                        }
+                        .into(),
+                    );
+                    balance.exchange = deposit_exchange;
+                }
+            }
+            nodes.push_back(balance.into());
+        }
+        Ok(ArbitragePath(nodes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC: CurrencyID = CurrencyID(0);
+    const USD: CurrencyID = CurrencyID(1);
+    const ETH: CurrencyID = CurrencyID(2);
+
+    #[test]
+    fn compact_path_round_trip_test() {
+        let (kraken, bitstamp) = (ExchangeID(0), ExchangeID(1));
+        let path = ArbitragePath::try_from(&CompactArbitragePath {
+            start: kraken.with_currency(&USD),
+            edges: SmallVec::from_slice(&[
+                CompactPathEdge::Transaction(CurrencyIDPair::new(BTC, USD)),
+                CompactPathEdge::Transfer(bitstamp, Some(NetworkID(3))),
+                CompactPathEdge::Transaction(CurrencyIDPair::new(ETH, BTC)),
+                CompactPathEdge::Transaction(CurrencyIDPair::new(ETH, USD)),
+            ]),
+        })
+        .unwrap();
+        assert_eq!(path.0.len(), 9);
+        assert!(path.operation_steps().is_ok());
+        assert_eq!(path.0.back(), Some(&bitstamp.with_currency(&USD).into()));
+
+        let compact = CompactArbitragePath::try_from(&path).unwrap();
+        assert_eq!(compact.path_node_count(), 9);
+        assert!(!compact.spilled());
+        assert_eq!(ArbitragePath::try_from(&compact).unwrap(), path);
+    }
+
+    #[test]
+    fn compact_path_invalid_test() {
+        assert!(matches!(
+            CompactArbitragePath::try_from(&ArbitragePath::new()),
+            Err(CryptoTypesError::InvalidPathStart)
+        ));
+        let corrupted = CompactArbitragePath {
+            start: ExchangeID(0).with_currency(&USD),
+            edges: SmallVec::from_slice(&[CompactPathEdge::Transaction(CurrencyIDPair::new(ETH, BTC))]),
+        };
+        assert!(matches!(
+            ArbitragePath::try_from(&corrupted),
+            Err(CryptoTypesError::NextCurrency(USD, _))
+        ));
+    }
+}
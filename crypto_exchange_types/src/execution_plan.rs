@@ -0,0 +1,538 @@
+use crate::arbitrage_paths::*;
+use crate::id_types::*;
+use crate::lexicon::*;
+use crate::portfolio::*;
+use crate::price_amounts::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use string_to_int_mapper::MappedKey;
+
+/// Sizing result for a path. Holds the amount for every balance node of the path
+/// (the first one is the start amount) as calculated from the order books.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathSizing {
+    pub balance_amounts: Vec<CurrencyAmount>,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum OrderType {
+    Market,
+    Limit,
+}
+
+/// Order side in exchange (market) terms. Spending the base/first currency of a market is a SELL
+/// and spending the quote/second currency is a BUY.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+/// Number of decimals used for rounding the order quantity and price of a market
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub struct MarketPrecision {
+    pub amount_decimals: u32,
+    pub price_decimals: u32,
+}
+
+/// Where the transfered funds should be deposited
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct DepositDestination {
+    pub address: String,
+    pub tag: Option<String>,
+}
+
+pub struct ExecutionPlanSettings {
+    pub order_type: OrderType,
+    /// Used for markets without a known precision
+    pub default_precision: MarketPrecision,
+    pub market_precisions: BTreeMap<(ExchangeID, CurrencyIDPair), MarketPrecision>,
+    /// Deposit addresses differ per network, transfers without a network use the `None` entries
+    pub deposit_destinations: BTreeMap<(ExchangeIDCurrencyIDPair, Option<NetworkID>), DepositDestination>,
+}
+
+impl ExecutionPlanSettings {
+    pub fn new_default() -> Self {
+        ExecutionPlanSettings {
+            order_type: OrderType::Limit,
+            default_precision: MarketPrecision {
+                amount_decimals: 8,
+                price_decimals: 8,
+            },
+            market_precisions: BTreeMap::new(),
+            deposit_destinations: BTreeMap::new(),
+        }
+    }
+
+    fn precision(&self, exchange: ExchangeID, pair: CurrencyIDPair) -> MarketPrecision {
+        self.market_precisions
+            .get(&(exchange, pair))
+            .copied()
+            .unwrap_or(self.default_precision)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderStep {
+    pub exchange_id: ExchangeID,
+    pub exchange: String,
+    pub pair: CurrencyIDPair,
+    /// Exchange symbol with the original exchange currency symbols e.g. 'XBT/USDT'
+    pub symbol: String,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    /// Quantity in the base/first currency rounded down to the market precision
+    pub quantity: f64,
+    /// Price in the quote/second currency rounded towards being filled
+    /// (down for sells and up for buys). For market orders this is the expected price.
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferStep {
+    pub withdraw_exchange_id: ExchangeID,
+    pub withdraw_exchange: String,
+    pub deposit_exchange_id: ExchangeID,
+    pub deposit_exchange: String,
+    pub currency_id: CurrencyID,
+    pub currency: String,
+    /// Network (chain) of the transfer, None if the networks of the exchanges are unknown
+    pub network_id: Option<NetworkID>,
+    pub network: Option<String>,
+    pub amount: f64,
+    pub destination: Option<DepositDestination>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ExecutionStepKind {
+    Order(OrderStep),
+    Transfer(TransferStep),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionStep {
+    pub index: usize,
+    /// Indexes of the steps that must be finished before this step can be executed
+    pub depends_on: Vec<usize>,
+    pub step: ExecutionStepKind,
+}
+
+/// Ordered execution plan of a selected path. Steps without dependencies can be sent in parallel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionPlan {
+    pub start: BalanceExchangeCurrencyInfo,
+    pub start_amount: CurrencyAmount,
+    pub steps: Vec<ExecutionStep>,
+}
+
+fn round_down(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    // the epsilon protects from 0.3 * 10 being 2.9999999999999996
+    ((value * factor) + 1e-9).floor() / factor
+}
+
+fn round_up(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    ((value * factor) - 1e-9).ceil() / factor
+}
+
+fn exchange_name(lexicon: &CryptoExchangeLexicon, e: ExchangeID) -> Result<String, CryptoTypesError> {
+    lexicon
+        .exchanges
+        .get_key(&e)
+        .and_then(MappedKey::live)
+        .map(str::to_string)
+        .ok_or(CryptoTypesError::UnknownExchange(e))
+}
+
+fn currency_name(lexicon: &CryptoExchangeLexicon, c: CurrencyID) -> Result<String, CryptoTypesError> {
+    lexicon
+        .currencies
+        .get_key(&c)
+        .and_then(MappedKey::live)
+        .map(str::to_string)
+        .ok_or(CryptoTypesError::UnknownCurrency(c))
+}
+
+/// Validates the network of the transfer against the networks both exchanges support
+fn transfer_network_name(
+    lexicon: &CryptoExchangeLexicon,
+    tr: &TransferExchangeToExchangeCurrencyInfo,
+) -> Result<Option<String>, CryptoTypesError> {
+    let networks = lexicon.transfer_networks(&tr.withdraw_exchange, &tr.deposit_exchange, &tr.currency);
+    if !networks.transfers().contains(&tr.network) {
+        return Err(CryptoTypesError::UnsupportedTransferNetwork(
+            tr.withdraw_exchange,
+            tr.deposit_exchange,
+            tr.currency,
+            tr.network,
+        ));
+    }
+    Ok(tr.network.map(|network| lexicon.network_to_string(&network).to_string()))
+}
+
+/// Currency symbol as used by the exchange (not normalised)
+fn exchange_symbol(lexicon: &CryptoExchangeLexicon, e: ExchangeID, c: CurrencyID) -> Result<String, CryptoTypesError> {
+    currency_name(lexicon, c)?;
+    Ok(lexicon.exchange_currency_symbol(&e, &c).to_string())
+}
+
+impl ExecutionPlan {
+    /// Converts the path and its sizing into concrete orders and transfers.
+    /// If a portfolio is provided, steps that can be funded from the currently available balances
+    /// don't wait on the previous step and can be executed in parallel.
+    pub fn create(
+        path: &ArbitragePath,
+        sizing: &PathSizing,
+        lexicon: &CryptoExchangeLexicon,
+        settings: &ExecutionPlanSettings,
+        portfolio: Option<&Portfolio>,
+    ) -> Result<Self, CryptoTypesError> {
+        let start = match path.0.front() {
+            Some(ExchangeOperationType::Balance(start)) => *start,
+            _ => return Err(CryptoTypesError::InvalidPathStart),
+        };
+        let operation_steps = path.operation_steps()?;
+        let amounts = &sizing.balance_amounts;
+        if amounts.len() != operation_steps.len() + 1 {
+            return Err(CryptoTypesError::PathAmountsMismatch(operation_steps.len() + 1, amounts.len()));
+        }
+        // a zero amount would turn into an infinite or NaN limit price
+        if let Some((index, amount)) = amounts.iter().enumerate().find(|(_, amount)| amount.0 <= 0.0 || amount.0.is_nan()) {
+            return Err(CryptoTypesError::InvalidAmount(index, amount.0));
+        }
+        let mut prefunding = portfolio.cloned();
+        let mut steps = Vec::with_capacity(operation_steps.len());
+        for (index, (from, op, _)) in operation_steps.into_iter().enumerate() {
+            let (amount_in, amount_out) = (amounts[index], amounts[index + 1]);
+            let step = match op {
+                ExchangeOperationType::Transaction(tx) => {
+                    let pair = tx_to_currency_id_pairs(&tx);
+                    let is_market = lexicon
+                        .exchange_currency_pairs
+                        .get(&tx.exchange)
+                        .is_some_and(|pairs| pairs.contains(&pair));
+                    if !is_market {
+                        return Err(CryptoTypesError::UnknownMarket(tx.exchange, pair));
+                    }
+                    let precision = settings.precision(tx.exchange, pair);
+                    let (side, quantity, price) = if tx.currency_from == pair.first {
+                        let price = amount_out.0 / amount_in.0;
+                        (OrderSide::Sell, amount_in.0, round_down(price, precision.price_decimals))
+                    } else {
+                        let price = amount_in.0 / amount_out.0;
+                        (OrderSide::Buy, amount_out.0, round_up(price, precision.price_decimals))
+                    };
+                    ExecutionStepKind::Order(OrderStep {
+                        exchange_id: tx.exchange,
+                        exchange: exchange_name(lexicon, tx.exchange)?,
+                        pair,
+                        symbol: format!(
+                            "{}/{}",
+                            exchange_symbol(lexicon, tx.exchange, pair.first)?,
+                            exchange_symbol(lexicon, tx.exchange, pair.second)?
+                        ),
+                        side,
+                        order_type: settings.order_type,
+                        quantity: round_down(quantity, precision.amount_decimals),
+                        price,
+                    })
+                }
+                ExchangeOperationType::Transfer(tr) => ExecutionStepKind::Transfer(TransferStep {
+                    withdraw_exchange_id: tr.withdraw_exchange,
+                    withdraw_exchange: exchange_name(lexicon, tr.withdraw_exchange)?,
+                    deposit_exchange_id: tr.deposit_exchange,
+                    deposit_exchange: exchange_name(lexicon, tr.deposit_exchange)?,
+                    currency_id: tr.currency,
+                    currency: currency_name(lexicon, tr.currency)?,
+                    network_id: tr.network,
+                    network: transfer_network_name(lexicon, &tr)?,
+                    amount: amount_in.0,
+                    destination: settings
+                        .deposit_destinations
+                        .get(&(tr.deposit_exchange.with_currency(&tr.currency), tr.network))
+                        .cloned(),
+                }),
+                ExchangeOperationType::Balance(_) => return Err(CryptoTypesError::UnexpectedBalanceNode(2 * index + 1)),
+            };
+            // the first step is funded by the start balance and the rest wait on the previous one
+            // unless we already hold the funds. The start balance is debited as well so the later
+            // steps drawing on the same balance aren't funded twice.
+            let is_held = prefunding
+                .as_mut()
+                .is_some_and(|portfolio| portfolio.withdraw(from, amount_in).is_ok());
+            let is_prefunded = index == 0 || is_held;
+            let depends_on = if is_prefunded { Vec::new() } else { vec![index - 1] };
+            steps.push(ExecutionStep { index, depends_on, step });
+        }
+        Ok(ExecutionPlan {
+            start,
+            start_amount: amounts[0],
+            steps,
+        })
+    }
+
+    /// Groups the step indexes into rounds. All steps in the same round can be sent in parallel.
+    pub fn parallel_rounds(&self) -> Vec<Vec<usize>> {
+        let mut step_rounds: Vec<usize> = Vec::with_capacity(self.steps.len());
+        let mut rounds: Vec<Vec<usize>> = Vec::new();
+        for step in self.steps.iter() {
+            let round = step
+                .depends_on
+                .iter()
+                .map(|dependency| step_rounds[*dependency] + 1)
+                .max()
+                .unwrap_or(0);
+            step_rounds.push(round);
+            if rounds.len() <= round {
+                rounds.resize_with(round + 1, Vec::new);
+            }
+            rounds[round].push(step.index);
+        }
+        rounds
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lexicon() -> CryptoExchangeLexicon {
+        let ex_symbols = vec![
+            ExchangeSymbolsJson {
+                exchange: "kraken".to_string(),
+                symbols: vec!["BTC/USDT".to_string(), "ETH/BTC".to_string(), "ETH/USDT".to_string()],
+            },
+            ExchangeSymbolsJson {
+                exchange: "binance".to_string(),
+                symbols: vec!["ETH/USDT".to_string()],
+            },
+        ];
+        CryptoExchangeLexicon::create_from_exchange_symbols(&ex_symbols).unwrap()
+    }
+
+    fn balance(lexicon: &CryptoExchangeLexicon, e: &str, c: &str) -> BalanceExchangeCurrencyInfo {
+        let e = *lexicon.exchanges.get_id(e).unwrap();
+        let c = *lexicon.currencies.get_id(c).unwrap();
+        e.with_currency(&c)
+    }
+
+    fn tx(from: BalanceExchangeCurrencyInfo, to: BalanceExchangeCurrencyInfo, side: TransactionSide) -> ExchangeOperationType {
+        TransactionExchangeCurrenciesBuySellInfo {
+            exchange: from.exchange,
+            side,
+            currency_from: from.currency,
+            currency_to: to.currency,
+        }
+        .into()
+    }
+
+    /// USDT -> BTC -> ETH on kraken, ETH transfer to binance and ETH -> USDT on binance
+    fn path(lexicon: &CryptoExchangeLexicon) -> ArbitragePath {
+        let kraken_usdt = balance(lexicon, "kraken", "USDT");
+        let kraken_btc = balance(lexicon, "kraken", "BTC");
+        let kraken_eth = balance(lexicon, "kraken", "ETH");
+        let binance_eth = balance(lexicon, "binance", "ETH");
+        let binance_usdt = balance(lexicon, "binance", "USDT");
+        let mut path = ArbitragePath::new();
+        path.0.push_back(kraken_usdt.into());
+        path.0.push_back(tx(kraken_usdt, kraken_btc, TransactionSide::SELL));
+        path.0.push_back(kraken_btc.into());
+        path.0.push_back(tx(kraken_btc, kraken_eth, TransactionSide::SELL));
+        path.0.push_back(kraken_eth.into());
+        path.0.push_back(
+            TransferExchangeToExchangeCurrencyInfo {
+                withdraw_exchange: kraken_eth.exchange,
+                deposit_exchange: binance_eth.exchange,
+                currency: kraken_eth.currency,
+                network: None,
+            }
+            .into(),
+        );
+        path.0.push_back(binance_eth.into());
+        path.0.push_back(tx(binance_eth, binance_usdt, TransactionSide::BUY));
+        path.0.push_back(binance_usdt.into());
+        path
+    }
+
+    fn sizing() -> PathSizing {
+        PathSizing {
+            balance_amounts: vec![
+                CurrencyAmount(1000.0),
+                CurrencyAmount(0.1),
+                CurrencyAmount(2.0),
+                CurrencyAmount(1.99),
+                CurrencyAmount(1213.9),
+            ],
+        }
+    }
+
+    #[test]
+    fn create_sequential_plan_test() {
+        let lexicon = lexicon();
+        let mut settings = ExecutionPlanSettings::new_default();
+        settings.default_precision = MarketPrecision {
+            amount_decimals: 4,
+            price_decimals: 2,
+        };
+        let binance_eth = balance(&lexicon, "binance", "ETH");
+        settings.deposit_destinations.insert(
+            (binance_eth, None),
+            DepositDestination {
+                address: "0xabc".to_string(),
+                tag: None,
+            },
+        );
+        let plan = ExecutionPlan::create(&path(&lexicon), &sizing(), &lexicon, &settings, None).unwrap();
+        assert_eq!(plan.steps.len(), 4);
+        match &plan.steps[0].step {
+            ExecutionStepKind::Order(order) => {
+                assert_eq!(order.symbol, "BTC/USDT");
+                assert_eq!(order.side, OrderSide::Buy);
+                assert_eq!(order.quantity, 0.1);
+                assert_eq!(order.price, 10000.0);
+            }
+            _ => panic!("expected an order"),
+        }
+        match &plan.steps[2].step {
+            ExecutionStepKind::Transfer(transfer) => {
+                assert_eq!(transfer.withdraw_exchange, "kraken");
+                assert_eq!(transfer.deposit_exchange, "binance");
+                assert_eq!(transfer.currency, "ETH");
+                assert_eq!(transfer.network, None);
+                assert_eq!(transfer.amount, 2.0);
+                assert_eq!(transfer.destination.as_ref().unwrap().address, "0xabc");
+            }
+            _ => panic!("expected a transfer"),
+        }
+        match &plan.steps[3].step {
+            ExecutionStepKind::Order(order) => {
+                assert_eq!(order.exchange, "binance");
+                assert_eq!(order.symbol, "ETH/USDT");
+                assert_eq!(order.side, OrderSide::Sell);
+                assert_eq!(order.quantity, 1.99);
+                assert_eq!(order.price, 610.0);
+            }
+            _ => panic!("expected an order"),
+        }
+        assert_eq!(plan.parallel_rounds(), vec![vec![0], vec![1], vec![2], vec![3]]);
+        assert!(plan.to_json().unwrap().contains("\"symbol\": \"ETH/USDT\""));
+    }
+
+    #[test]
+    fn transfer_network_test() {
+        use crate::lexicon_networks::ExchangeCurrencyNetworksJson;
+        let mut lexicon = lexicon();
+        let networks = |exchange: &str, names: &[&str]| ExchangeCurrencyNetworksJson {
+            exchange: exchange.to_string(),
+            currencies: BTreeMap::from([("ETH".to_string(), names.iter().map(|n| n.to_string()).collect())]),
+        };
+        lexicon
+            .set_currency_networks(&[networks("kraken", &["ERC20", "ARBITRUM"]), networks("binance", &["ERC20"])])
+            .unwrap();
+        let erc20 = *lexicon.networks.get_id("ERC20").unwrap();
+        let binance_eth = balance(&lexicon, "binance", "ETH");
+        let mut settings = ExecutionPlanSettings::new_default();
+        for (network, address) in [(None, "0xabc"), (Some(erc20), "0xdef")] {
+            let destination = DepositDestination {
+                address: address.to_string(),
+                tag: None,
+            };
+            settings.deposit_destinations.insert((binance_eth, network), destination);
+        }
+        // both exchanges report networks, the transfer must use a shared one
+        let mut path = path(&lexicon);
+        let plan = ExecutionPlan::create(&path, &sizing(), &lexicon, &settings, None);
+        assert!(matches!(plan, Err(CryptoTypesError::UnsupportedTransferNetwork(_, _, _, None))));
+
+        if let Some(ExchangeOperationType::Transfer(tr)) = path.0.iter_mut().nth(5) {
+            tr.network = Some(erc20);
+        }
+        let plan = ExecutionPlan::create(&path, &sizing(), &lexicon, &settings, None).unwrap();
+        match &plan.steps[2].step {
+            ExecutionStepKind::Transfer(transfer) => {
+                assert_eq!(transfer.network_id, Some(erc20));
+                assert_eq!(transfer.network.as_deref(), Some("ERC20"));
+                assert_eq!(transfer.destination.as_ref().unwrap().address, "0xdef");
+            }
+            _ => panic!("expected a transfer"),
+        }
+    }
+
+    #[test]
+    fn prefunded_steps_run_in_parallel_test() {
+        let lexicon = lexicon();
+        let mut portfolio = Portfolio::new();
+        portfolio.deposit(balance(&lexicon, "binance", "ETH"), CurrencyAmount(5.0)).unwrap();
+        let plan = ExecutionPlan::create(
+            &path(&lexicon),
+            &sizing(),
+            &lexicon,
+            &ExecutionPlanSettings::new_default(),
+            Some(&portfolio),
+        )
+        .unwrap();
+        assert_eq!(plan.steps[3].depends_on, Vec::<usize>::new());
+        assert_eq!(plan.parallel_rounds(), vec![vec![0, 3], vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn unknown_market_test() {
+        let lexicon = lexicon();
+        let mut path = path(&lexicon);
+        // binance has no BTC market
+        let binance_usdt = balance(&lexicon, "binance", "USDT");
+        let binance_btc = ExchangeID(1).with_currency(&balance(&lexicon, "kraken", "BTC").currency);
+        path.0.clear();
+        path.0.push_back(binance_usdt.into());
+        path.0.push_back(tx(binance_usdt, binance_btc, TransactionSide::SELL));
+        path.0.push_back(binance_btc.into());
+        let sizing = PathSizing {
+            balance_amounts: vec![CurrencyAmount(1.0), CurrencyAmount(1.0)],
+        };
+        let plan = ExecutionPlan::create(&path, &sizing, &lexicon, &ExecutionPlanSettings::new_default(), None);
+        assert!(matches!(plan, Err(CryptoTypesError::UnknownMarket(_, _))));
+    }
+
+    #[test]
+    fn invalid_path_and_amounts_test() {
+        let lexicon = lexicon();
+        let settings = ExecutionPlanSettings::new_default();
+        let plan = ExecutionPlan::create(&ArbitragePath::new(), &sizing(), &lexicon, &settings, None);
+        assert!(matches!(plan, Err(CryptoTypesError::InvalidPathStart)));
+
+        let mut sizing = sizing();
+        sizing.balance_amounts[3] = CurrencyAmount(0.0);
+        let plan = ExecutionPlan::create(&path(&lexicon), &sizing, &lexicon, &settings, None);
+        assert!(matches!(plan, Err(CryptoTypesError::InvalidAmount(3, _))));
+    }
+
+    #[test]
+    fn start_balance_is_debited_test() {
+        let lexicon = lexicon();
+        let kraken_usdt = balance(&lexicon, "kraken", "USDT");
+        let kraken_btc = balance(&lexicon, "kraken", "BTC");
+        let kraken_eth = balance(&lexicon, "kraken", "ETH");
+        // USDT -> BTC -> USDT -> ETH, the third step draws on the start balance again
+        let mut path = ArbitragePath::new();
+        path.0.push_back(kraken_usdt.into());
+        path.0.push_back(tx(kraken_usdt, kraken_btc, TransactionSide::SELL));
+        path.0.push_back(kraken_btc.into());
+        path.0.push_back(tx(kraken_btc, kraken_usdt, TransactionSide::BUY));
+        path.0.push_back(kraken_usdt.into());
+        path.0.push_back(tx(kraken_usdt, kraken_eth, TransactionSide::SELL));
+        path.0.push_back(kraken_eth.into());
+        let sizing = PathSizing {
+            balance_amounts: vec![CurrencyAmount(1000.0), CurrencyAmount(0.1), CurrencyAmount(1000.0), CurrencyAmount(0.5)],
+        };
+        let mut portfolio = Portfolio::new();
+        portfolio.deposit(kraken_usdt, CurrencyAmount(1500.0)).unwrap();
+        let plan = ExecutionPlan::create(&path, &sizing, &lexicon, &ExecutionPlanSettings::new_default(), Some(&portfolio)).unwrap();
+        assert_eq!(plan.steps[2].depends_on, vec![1]);
+        assert_eq!(plan.parallel_rounds(), vec![vec![0], vec![1], vec![2]]);
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use string_to_int_mapper::GetCurrentAndIncrementStringToIntMapperId;
+use string_to_int_mapper::{MapperId, StringToIntMapperError};
 use std::fmt;
 use thiserror::Error;
 
@@ -14,32 +14,58 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum CryptoTypesError {
     #[error("Next currency error. Unknown BUY/SELL relation for currency '{0}' and pair '{1}'")]
-    NextCurrency(CurrencyID, CurrencyIDPair)
+    NextCurrency(CurrencyID, CurrencyIDPair),
+    #[error("Insufficient funds for '{0:?}'. Requested {1} but only {2} is left")]
+    InsufficientFunds(ExchangeIDCurrencyIDPair, f64, f64),
+    #[error("Invalid amount {1} for '{0:?}'. Amounts can't be negative or NaN")]
+    InvalidPortfolioAmount(ExchangeIDCurrencyIDPair, f64),
+    #[error("Invalid path. Expected {0} executed balance amounts but got {1}")]
+    PathAmountsMismatch(usize, usize),
+    #[error("Invalid path. Operation '{1}' at index {0} doesn't connect its surrounding balance nodes")]
+    DisconnectedPath(usize, ExchangeOperationType),
+    #[error("Invalid path. The path doesn't start with a balance node")]
+    InvalidPathStart,
+    #[error("Invalid path. Expected an operation at node index {0} but found a balance node")]
+    UnexpectedBalanceNode(usize),
+    #[error("Invalid path sizing. The balance amount at index {0} is {1} but amounts must be positive")]
+    InvalidAmount(usize, f64),
+    #[error("Invalid order quantity {0}. The quantity must be positive")]
+    InvalidOrderQuantity(f64),
+    #[error("Unsupported paths format version {0}. The newest supported version is {1}")]
+    UnsupportedFormatVersion(u32, u32),
+    #[error("Unknown exchange '{0:?}'. It isn't part of the lexicon")]
+    UnknownExchange(ExchangeID),
+    #[error("Unknown currency '{0:?}'. It isn't part of the lexicon")]
+    UnknownCurrency(CurrencyID),
+    #[error("Unknown market '{1}' on exchange '{0:?}'")]
+    UnknownMarket(ExchangeID, CurrencyIDPair),
+    #[error("Invalid market '{1}' on exchange '{0:?}'. Both sides of the market are the same currency")]
+    InvalidMarket(ExchangeID, CurrencyIDPair),
+    #[error("Unknown networks of the '{2:?}' transfer from '{0:?}' to '{1:?}'. Only one of the exchanges reports its networks")]
+    OneSidedTransferNetworks(ExchangeID, ExchangeID, CurrencyID),
+    #[error("Unsupported network '{3:?}' of the '{2:?}' transfer from '{0:?}' to '{1:?}'")]
+    UnsupportedTransferNetwork(ExchangeID, ExchangeID, CurrencyID, Option<NetworkID>),
+    #[error("Unknown exchange '{0}'. It isn't part of the lexicon")]
+    UnknownExchangeName(String),
+    #[error("Exchange ID {0} is out of range. The exchange ID type can't hold it")]
+    ExchangeIdOutOfRange(usize),
+    #[error("Currency ID {0} is out of range. The currency ID type can't hold it")]
+    CurrencyIdOutOfRange(usize),
+    #[error("Unknown currency '{0}'. It isn't part of the lexicon")]
+    UnknownCurrencyName(String),
+    #[error("Lexicon checksum mismatch. Expected '{0}' but found '{1}'. The data was generated from a different lexicon")]
+    LexiconChecksumMismatch(String, String),
+    #[error(transparent)]
+    Mapper(#[from] StringToIntMapperError),
 }
 
 
 
 /// Immutable strongly typed ExchangeID. This ID is generated by the ('Consensus') Lexicon  
-#[derive(Default, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Copy, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Copy, Clone, Serialize, Deserialize, MapperId)]
+#[mapper_id(prefix = "e")]
 pub struct ExchangeID(pub u8);
 
-impl From<u8> for ExchangeID {
-    fn from(value: u8) -> Self {
-        ExchangeID(value)
-    }
-}
-
-impl GetCurrentAndIncrementStringToIntMapperId for ExchangeID {
-    fn get_and_increment(&mut self) -> Self {
-        let ret = self.clone();
-        self.0 = self.0 + 1u8;
-        ret
-    }
-    fn to_usize(&self) -> usize {
-        self.0 as usize
-    }
-}
-
 impl ExchangeID {
     pub fn with_currency(&self, c: &CurrencyID) -> ExchangeIDCurrencyIDPair {
         (self.clone(), c.clone()).into()
@@ -48,31 +74,15 @@ impl ExchangeID {
 
 /// Immutable strongly typed CurrencyID. This is used to identify Currencies (Crypto and Fiat)
 /// This ID is generated by the ('Consensus') Lexicon
-#[derive(Default, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Copy, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Copy, Clone, Serialize, Deserialize, MapperId)]
+#[mapper_id(prefix = "c")]
 pub struct CurrencyID(pub u16);
 
-impl From<u16> for CurrencyID {
-    fn from(value: u16) -> Self {
-        CurrencyID(value)
-    }
-}
-
-impl GetCurrentAndIncrementStringToIntMapperId for CurrencyID {
-    fn get_and_increment(&mut self) -> Self {
-        let ret = self.clone();
-        self.0 = self.0 + 1u16;
-        ret
-    }
-    fn to_usize(&self) -> usize {
-        self.0 as usize
-    }
-}
-
-impl fmt::Display for CurrencyID {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "c={}", self.0)
-    }
-}
+/// Immutable strongly typed NetworkID of a blockchain network (chain) a currency is transferred on e.g. 'ERC20'.
+/// This ID is generated by the ('Consensus') Lexicon
+#[derive(Default, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Copy, Clone, Serialize, Deserialize, MapperId)]
+#[mapper_id(prefix = "n")]
+pub struct NetworkID(pub u16);
 
 // TODO https://www.investopedia.com/terms/c/currencypair.asp
 // TODO doesn't add up the bid ask sell buy stuff
@@ -196,11 +206,18 @@ impl fmt::Display for ExchangeOperationType {
                 "tx(e={},s={},cf={},ct={})",
                 tx.exchange.0, tx.side as i32, tx.currency_from.0, tx.currency_to.0
             ),
-            ExchangeOperationType::Transfer(tr) => write!(
-                f,
-                "tr(we={},de={},c={})",
-                tr.withdraw_exchange.0, tr.deposit_exchange.0, tr.currency.0
-            ),
+            ExchangeOperationType::Transfer(tr) => match tr.network {
+                Some(network) => write!(
+                    f,
+                    "tr(we={},de={},c={},n={})",
+                    tr.withdraw_exchange.0, tr.deposit_exchange.0, tr.currency.0, network.0
+                ),
+                None => write!(
+                    f,
+                    "tr(we={},de={},c={})",
+                    tr.withdraw_exchange.0, tr.deposit_exchange.0, tr.currency.0
+                ),
+            },
         }
     }
 }
@@ -269,6 +286,9 @@ pub struct TransferExchangeToExchangeCurrencyInfo {
     pub withdraw_exchange: ExchangeID,
     pub deposit_exchange: ExchangeID,
     pub currency: CurrencyID,
+    /// Network (chain) the currency is sent on. None if the exchanges don't report their networks.
+    #[serde(default)]
+    pub network: Option<NetworkID>,
 }
 
 impl ExchangeOperationTypeInfo for TransferExchangeToExchangeCurrencyInfo {
@@ -283,6 +303,7 @@ impl ExchangeOperationTypeInfo for TransferExchangeToExchangeCurrencyInfo {
 impl TransferExchangeToExchangeCurrencyInfo {
     pub fn is_transfer_inverse(&self, a: &TransferExchangeToExchangeCurrencyInfo) -> bool {
         return a.currency == self.currency
+            && a.network == self.network
             && a.withdraw_exchange == self.deposit_exchange
             && a.deposit_exchange == self.withdraw_exchange;
     }
@@ -320,6 +341,18 @@ mod tests {
 
     struct Pair(u16, u16);
 
+    #[test]
+    fn mapper_id_test() {
+        use string_to_int_mapper::GetCurrentAndIncrementStringToIntMapperId;
+
+        assert_eq!(CurrencyID(7).to_string(), "c=7");
+        assert_eq!(ExchangeID::from(3).to_string(), "e=3");
+        assert_eq!(ExchangeID(254).next_id(), Some(ExchangeID(255)));
+        assert_eq!(ExchangeID(255).next_id(), None);
+        assert_eq!(CurrencyID(1).next_id(), Some(CurrencyID(2)));
+        assert_eq!(CurrencyID(2).to_usize(), 2);
+    }
+
     struct Path3([Pair; 3]);
 
     struct Path5([Pair; 5]);
@@ -372,6 +405,7 @@ mod tests {
     }
     
     use crate::arbitrage_paths::*;
+    use crate::compact_path::*;
 
     #[test]
     fn print_memory_usages_for_experimental() {
@@ -423,6 +457,8 @@ mod tests {
         
         print_struct_size!(Target);
         print_struct_size!(ArbitragePath);
+        print_struct_size!(CompactArbitragePath);
+        print_struct_size!(CompactPathEdge);
         print_struct_size!(TargetKnownPaths);
 
         println!("{}", std::mem::size_of_val(&pairs[0]));
@@ -1,12 +1,104 @@
+use crate::ccxt_markets::MarketInfo;
 use crate::id_types::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use string_to_int_mapper::*;
 #[derive(Serialize, Deserialize)]
 pub struct ExchangeSymbolsJson {
     pub exchange: String,
     pub symbols: Vec<String>, // symbols are split by '/'
 }
+
+/// Fiat or stable currency definition. Exchanges could use different names for the same currency
+/// so every alias that is known to the lexicon is classified e.g. 'BTC' with the alias 'XBT'.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencyDefinitionJson {
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+impl From<&str> for CurrencyDefinitionJson {
+    fn from(name: &str) -> Self {
+        CurrencyDefinitionJson {
+            name: name.to_string(),
+            aliases: Vec::new(),
+        }
+    }
+}
+
+/// Maps the exchange specific currency symbols to the canonical currency names used by the lexicon.
+/// Per exchange overrides have precedence over the aliases, they are used for tickers that collide
+/// for different assets on different exchanges.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolNormalisationJson {
+    /// Exchange symbol to canonical name e.g. 'XBT' to 'BTC'
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// Exchange name to (exchange symbol to canonical name)
+    #[serde(default)]
+    pub exchange_overrides: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl SymbolNormalisationJson {
+    pub fn canonical<'a>(&'a self, exchange: &str, symbol: &'a str) -> &'a str {
+        self.exchange_overrides
+            .get(exchange)
+            .and_then(|overrides| overrides.get(symbol))
+            .or_else(|| self.aliases.get(symbol))
+            .map_or(symbol, |canonical| canonical.as_str())
+    }
+}
+
+/// Non fatal issues found while generating a lexicon
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LexiconWarning {
+    UnknownFiatCurrency(String),
+    UnknownStableCurrency(String),
+    UnknownNetworkExchange(String),
+    UnknownNetworkCurrency(String, String),
+    /// (exchange, skipped symbol, kept symbol)
+    DuplicateMarket(String, String, String),
+    /// (exchange, currency, kept symbol, other symbol)
+    ConflictingCurrencySymbols(String, String, String, String),
+    /// (exchange, symbol) the symbol isn't a 'BASE/QUOTE' pair
+    InvalidSymbol(String, String),
+    /// (exchange, symbol) both sides of the market are the same currency
+    InvalidMarket(String, String),
+}
+
+impl fmt::Display for LexiconWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexiconWarning::UnknownFiatCurrency(name) => {
+                write!(f, "Fiat currency '{}' or any of its aliases isn't traded on any exchange", name)
+            }
+            LexiconWarning::UnknownStableCurrency(name) => {
+                write!(f, "Stable currency '{}' or any of its aliases isn't traded on any exchange", name)
+            }
+            LexiconWarning::UnknownNetworkExchange(exchange) => {
+                write!(f, "Networks of exchange '{}' are skipped. The exchange isn't part of the lexicon", exchange)
+            }
+            LexiconWarning::UnknownNetworkCurrency(exchange, currency) => {
+                write!(f, "Networks of currency '{}' on exchange '{}' are skipped. The currency isn't traded on the exchange", currency, exchange)
+            }
+            LexiconWarning::DuplicateMarket(exchange, symbol, kept) => {
+                write!(f, "Market '{}' on exchange '{}' is skipped. It is the same market as '{}' after normalisation", symbol, exchange, kept)
+            }
+            LexiconWarning::InvalidSymbol(exchange, symbol) => {
+                write!(f, "Symbol '{}' on exchange '{}' is skipped. It has more than 2 currencies", symbol, exchange)
+            }
+            LexiconWarning::InvalidMarket(exchange, symbol) => {
+                write!(f, "Market '{}' on exchange '{}' is skipped. Both sides are the same currency after normalisation", symbol, exchange)
+            }
+            LexiconWarning::ConflictingCurrencySymbols(exchange, currency, kept, other) => {
+                write!(f, "Exchange '{}' lists currency '{}' as both '{}' and '{}'. Orders are placed with '{}'", exchange, currency, kept, other, kept)
+            }
+        }
+    }
+}
 // struct SymbolPair(String, String);
 
 // TODO split the lexicon to fundamental and non-fundamental part.
@@ -24,105 +116,364 @@ pub struct CryptoExchangeLexicon {
     pub exchange_currency_pairs: BTreeMap<ExchangeID, BTreeSet<CurrencyIDPair>>,
     pub fiat_currencies: BTreeSet<CurrencyID>,
     pub stable_currencies: BTreeSet<CurrencyID>,
+    /// Normalisation table used for generating the lexicon
+    #[serde(default)]
+    pub normalisation: SymbolNormalisationJson,
+    /// Original exchange symbols that differ from the canonical currency names. Orders must be placed with these.
+    #[serde(default)]
+    pub exchange_symbols: BTreeMap<ExchangeIDCurrencyIDPair, String>,
+    /// Market precision and limits. Only available for lexicons imported from CCXT markets.
+    #[serde(default)]
+    pub markets: BTreeMap<ExchangeID, BTreeMap<CurrencyIDPair, MarketInfo>>,
+    /// Blockchain networks (chains) the currencies are transferred on
+    #[serde(default)]
+    pub networks: StringToIntMapper<NetworkID, Reading>,
+    /// Networks the exchange supports for the currency deposits and withdrawals
+    #[serde(default)]
+    pub exchange_currency_networks: BTreeMap<ExchangeIDCurrencyIDPair, BTreeSet<NetworkID>>,
 }
 
 // Vec<exchange_symbols>
 impl CryptoExchangeLexicon {
-    pub fn create_from_exchange_symbols(ex_symbols: &[ExchangeSymbolsJson]) -> Self {
-        let fiat_currencies = Vec::new();
-        let stable_currencies = Vec::new();
-        Self::create_from_exchange_symbols_full(&ex_symbols, &fiat_currencies, &stable_currencies)
+    /// Creates the lexicon without normalisation. Use `create_from_exchange_symbols_full` for the generation warnings.
+    pub fn create_from_exchange_symbols(ex_symbols: &[ExchangeSymbolsJson]) -> Result<Self, CryptoTypesError> {
+        let exchanges = StringToIntMapper::<ExchangeID, Editing>::new();
+        let currencies = StringToIntMapper::<CurrencyID, Editing>::new();
+        let (lexicon, _) = Self::generate(exchanges, currencies, ex_symbols, SymbolNormalisationJson::default())?;
+        Ok(lexicon)
     }
+
+    /// Creates the lexicon with the exchange symbols normalised to canonical currency names
+    /// and classifies the fiat and stable currencies (by canonical names).
+    /// Currencies that aren't traded on any exchange and markets that normalise to the same market
+    /// are skipped and reported as warnings.
     pub fn create_from_exchange_symbols_full(
         ex_symbols: &[ExchangeSymbolsJson],
-        fiat_currencies: &[&str],
-        stable_currencies: &[&str],
-    ) -> Self {
-        let mut exchanges = StringToIntMapper::<ExchangeID, Editing>::new();
-        let mut currencies = StringToIntMapper::<CurrencyID, Editing>::new();
-        // populate exchanges and currencies
-        ex_symbols.iter().for_each(|exchange_symbols| {
-            exchanges.add(&exchange_symbols.exchange);
-            exchange_symbols
-                .symbols
-                .iter()
-                .filter(|symbol| symbol.contains("/"))
-                .for_each(|symbol_pair| {
-                    let pair: Vec<_> = symbol_pair.split("/").collect();
-
-                    if pair.len() != 2 {
-                        println!("symbol more than 2 {}", symbol_pair);
-                        return;
-                    }
-                    pair.iter().for_each(|c| {
-                        currencies.add(c);
-                    });
-                })
-        });
-        let exchanges = exchanges.to_reader();
-        let currencies = currencies.to_reader();
-        let exchange_currency_pairs: BTreeMap<ExchangeID, BTreeSet<CurrencyIDPair>> = ex_symbols
-            .iter()
-            .map(|exchange_symbols| {
-                let exchange = exchanges.get_id(&exchange_symbols.exchange).unwrap();
-                let exchange = *exchange;
-                let pairs: BTreeSet<_> = exchange_symbols
-                    .symbols
-                    .iter()
-                    .filter(|symbol| symbol.contains("/"))
-                    .map(|symbol_pair| {
-                        let pair: Vec<_> = symbol_pair.split("/").collect();
-                        pair
-                    })
-                    .filter(|pair| pair.len() == 2)
-                    .map(|pair| {
-                        let c1 = currencies.get_id(pair[0]).unwrap();
-                        let c2 = currencies.get_id(pair[1]).unwrap();
-                        let c1 = *c1;
-                        let c2 = *c2;
-                        CurrencyIDPair {
-                            first: c1,
-                            second: c2,
-                        }
-                    })
+        normalisation: &SymbolNormalisationJson,
+        fiat_currencies: &[CurrencyDefinitionJson],
+        stable_currencies: &[CurrencyDefinitionJson],
+    ) -> Result<(Self, Vec<LexiconWarning>), CryptoTypesError> {
+        let exchanges = StringToIntMapper::<ExchangeID, Editing>::new();
+        let currencies = StringToIntMapper::<CurrencyID, Editing>::new();
+        let (mut lexicon, mut warnings) = Self::generate(exchanges, currencies, ex_symbols, normalisation.clone())?;
+        warnings.append(&mut lexicon.set_fiat_currencies(fiat_currencies));
+        warnings.append(&mut lexicon.set_stable_currencies(stable_currencies));
+        Ok((lexicon, warnings))
+    }
+
+    /// Returns the IDs of the definition name and aliases known to the lexicon
+    fn resolve_currency_definition(&self, definition: &CurrencyDefinitionJson) -> Vec<CurrencyID> {
+        std::iter::once(&definition.name)
+            .chain(definition.aliases.iter())
+            .filter_map(|name| self.currencies.get_id(name).copied())
+            .collect()
+    }
+
+    pub fn set_fiat_currencies(&mut self, fiat_currencies: &[CurrencyDefinitionJson]) -> Vec<LexiconWarning> {
+        self.fiat_currencies.clear();
+        let mut warnings = Vec::new();
+        for definition in fiat_currencies {
+            let ids = self.resolve_currency_definition(definition);
+            if ids.is_empty() {
+                warnings.push(LexiconWarning::UnknownFiatCurrency(definition.name.clone()));
+            }
+            self.fiat_currencies.extend(ids);
+        }
+        warnings
+    }
+
+    pub fn set_stable_currencies(&mut self, stable_currencies: &[CurrencyDefinitionJson]) -> Vec<LexiconWarning> {
+        self.stable_currencies.clear();
+        let mut warnings = Vec::new();
+        for definition in stable_currencies {
+            let ids = self.resolve_currency_definition(definition);
+            if ids.is_empty() {
+                warnings.push(LexiconWarning::UnknownStableCurrency(definition.name.clone()));
+            }
+            self.stable_currencies.extend(ids);
+        }
+        warnings
+    }
+
+    /// Regenerates the lexicon from newer exchange symbols while keeping the IDs of the previous lexicon stable.
+    /// New exchanges and currencies are appended, the ones that are no longer present are marked as removed
+    /// (deprecated) and keep their IDs so previously generated network files stay valid.
+    /// Fiat and stable currencies, networks, market info and the normalisation table are carried over from the
+    /// previous lexicon.
+    pub fn regenerate_from_previous(
+        previous: CryptoExchangeLexicon,
+        ex_symbols: &[ExchangeSymbolsJson],
+    ) -> Result<(Self, Vec<LexiconWarning>), CryptoTypesError> {
+        let CryptoExchangeLexicon {
+            exchanges,
+            currencies,
+            fiat_currencies,
+            stable_currencies,
+            normalisation,
+            networks,
+            exchange_currency_networks,
+            markets,
+            ..
+        } = previous;
+        let (mut lexicon, warnings) = Self::generate(exchanges.to_editer(), currencies.to_editer(), ex_symbols, normalisation)?;
+        let is_live = |c: &&CurrencyID| lexicon.currencies.get_key(c).is_some_and(|name| !name.is_tombstoned());
+        let fiat_currencies = fiat_currencies.iter().filter(is_live).copied().collect();
+        let stable_currencies = stable_currencies.iter().filter(is_live).copied().collect();
+        lexicon.fiat_currencies = fiat_currencies;
+        lexicon.stable_currencies = stable_currencies;
+        let exchange_currency_networks = exchange_currency_networks
+            .into_iter()
+            .filter(|(ec, _)| lexicon.exchange_has_currency(&ec.exchange, &ec.currency))
+            .collect();
+        lexicon.networks = networks;
+        lexicon.exchange_currency_networks = exchange_currency_networks;
+        let markets = markets
+            .into_iter()
+            .filter_map(|(exchange, markets)| {
+                let markets: BTreeMap<_, _> = markets
+                    .into_iter()
+                    .filter(|(pair, _)| lexicon.exchange_has_pair(&exchange, pair))
                     .collect();
-                (exchange, pairs)
+                (!markets.is_empty()).then_some((exchange, markets))
             })
             .collect();
+        lexicon.markets = markets;
+        Ok((lexicon, warnings))
+    }
 
-        let fiat_currencies: BTreeSet<CurrencyID> = fiat_currencies
-            .iter()
-            .map(|fiat| currencies.get_id(fiat).unwrap())
-            .map(|c| *c)
-            .collect();
-        let stable_currencies: BTreeSet<CurrencyID> = stable_currencies
-            .iter()
-            .map(|c| currencies.get_id(c).unwrap())
-            .map(|c| *c)
-            .collect();
-        CryptoExchangeLexicon {
+    /// Both sides of the market are the same currency after normalisation e.g. 'BTC/BTC' or 'XBT/BTC'
+    fn is_same_currency_market(
+        normalisation: &SymbolNormalisationJson,
+        normaliser: KeyNormaliser,
+        exchange: &str,
+        s1: &str,
+        s2: &str,
+    ) -> bool {
+        normaliser.normalise(normalisation.canonical(exchange, s1)) == normaliser.normalise(normalisation.canonical(exchange, s2))
+    }
+
+    fn split_symbol(symbol_pair: &str) -> Option<(&str, &str)> {
+        let pair: Vec<_> = symbol_pair.split("/").collect();
+        if pair.len() != 2 {
+            return None;
+        }
+        Some((pair[0], pair[1]))
+    }
+
+    /// Adds the exchanges and (normalised) currencies to the mappers and marks the existing ones that aren't in the
+    /// `ex_symbols` as removed. Exchange currency pairs are generated only from the `ex_symbols`.
+    /// If an exchange lists the same market under different aliases only the first one is kept, the first
+    /// exchange symbol of a currency is the one the orders are placed with. Markets with the same currency on
+    /// both sides are skipped. All cases are reported as warnings.
+    /// Fails if the exchange or currency IDs are exhausted.
+    fn generate(
+        mut exchanges: StringToIntMapper<ExchangeID, Editing>,
+        mut currencies: StringToIntMapper<CurrencyID, Editing>,
+        ex_symbols: &[ExchangeSymbolsJson],
+        normalisation: SymbolNormalisationJson,
+    ) -> Result<(Self, Vec<LexiconWarning>), CryptoTypesError> {
+        let mut warnings = Vec::new();
+        let mut present_exchanges: BTreeSet<&str> = BTreeSet::new();
+        let mut present_currencies: BTreeSet<&str> = BTreeSet::new();
+        // populate exchanges and currencies, the new IDs are added in the input order
+        for exchange_symbols in ex_symbols {
+            let exchange = &exchange_symbols.exchange;
+            exchanges.add(exchange)?;
+            present_exchanges.insert(exchange);
+            for symbol_pair in exchange_symbols.symbols.iter().filter(|symbol| symbol.contains("/")) {
+                match Self::split_symbol(symbol_pair) {
+                    Some((c1, c2)) if Self::is_same_currency_market(&normalisation, currencies.normaliser(), exchange, c1, c2) => {
+                        warnings.push(LexiconWarning::InvalidMarket(exchange.clone(), symbol_pair.clone()))
+                    }
+                    Some((c1, c2)) => {
+                        let c1 = normalisation.canonical(exchange, c1);
+                        let c2 = normalisation.canonical(exchange, c2);
+                        currencies.add(c1)?;
+                        currencies.add(c2)?;
+                        present_currencies.insert(c1);
+                        present_currencies.insert(c2);
+                    }
+                    None => warnings.push(LexiconWarning::InvalidSymbol(exchange.clone(), symbol_pair.clone())),
+                }
+            }
+        }
+        let exchanges = Self::update_removed(exchanges, &present_exchanges);
+        let currencies = Self::update_removed(currencies, &present_currencies);
+        let mut exchange_symbols: BTreeMap<ExchangeIDCurrencyIDPair, String> = BTreeMap::new();
+        let mut exchange_currency_pairs: BTreeMap<ExchangeID, BTreeSet<CurrencyIDPair>> = BTreeMap::new();
+        for ex_symbols in ex_symbols {
+            let exchange = *exchanges
+                .get_id(&ex_symbols.exchange)
+                .ok_or_else(|| CryptoTypesError::UnknownExchangeName(ex_symbols.exchange.clone()))?;
+            let normalised_id = |symbol: &str| -> Result<CurrencyID, CryptoTypesError> {
+                let canonical = normalisation.canonical(&ex_symbols.exchange, symbol);
+                currencies
+                    .get_id(canonical)
+                    .copied()
+                    .ok_or_else(|| CryptoTypesError::UnknownCurrencyName(canonical.to_string()))
+            };
+            let mut market_symbols: BTreeMap<CurrencyIDPair, &str> = BTreeMap::new();
+            let mut currency_symbols: BTreeMap<CurrencyID, &str> = BTreeMap::new();
+            for symbol_pair in ex_symbols.symbols.iter() {
+                // invalid symbols and markets are reported while populating the currencies
+                let (s1, s2) = match Self::split_symbol(symbol_pair) {
+                    Some(symbols) => symbols,
+                    None => continue,
+                };
+                if Self::is_same_currency_market(&normalisation, currencies.normaliser(), &ex_symbols.exchange, s1, s2) {
+                    continue;
+                }
+                let pair = CurrencyIDPair {
+                    first: normalised_id(s1)?,
+                    second: normalised_id(s2)?,
+                };
+                if let Some(kept) = market_symbols.get(&pair) {
+                    if *kept != symbol_pair {
+                        warnings.push(LexiconWarning::DuplicateMarket(
+                            ex_symbols.exchange.clone(),
+                            symbol_pair.clone(),
+                            kept.to_string(),
+                        ));
+                    }
+                    continue;
+                }
+                market_symbols.insert(pair, symbol_pair);
+                for (c, symbol) in [(pair.first, s1), (pair.second, s2)] {
+                    match currency_symbols.get(&c) {
+                        None => {
+                            currency_symbols.insert(c, symbol);
+                        }
+                        Some(kept) if *kept != symbol => warnings.push(LexiconWarning::ConflictingCurrencySymbols(
+                            ex_symbols.exchange.clone(),
+                            currencies.get_key(&c).map_or("", |name| name.key()).to_string(),
+                            kept.to_string(),
+                            symbol.to_string(),
+                        )),
+                        Some(_) => (),
+                    }
+                }
+            }
+            for (c, symbol) in currency_symbols {
+                if currencies.get_key(&c).map(|name| name.key()) != Some(symbol) {
+                    exchange_symbols.insert(exchange.with_currency(&c), symbol.to_string());
+                }
+            }
+            exchange_currency_pairs.insert(exchange, market_symbols.into_keys().collect());
+        }
+        let lexicon = CryptoExchangeLexicon {
             exchanges: exchanges,
             currencies: currencies,
             exchange_currency_pairs: exchange_currency_pairs,
-            fiat_currencies: fiat_currencies,
-            stable_currencies,
+            fiat_currencies: BTreeSet::new(),
+            stable_currencies: BTreeSet::new(),
+            normalisation,
+            exchange_symbols,
+            markets: BTreeMap::new(),
+            networks: StringToIntMapper::new_reading(),
+            exchange_currency_networks: BTreeMap::new(),
+        };
+        Ok((lexicon, warnings))
+    }
+
+    fn update_removed<T>(mapper: StringToIntMapper<T, Editing>, present: &BTreeSet<&str>) -> StringToIntMapper<T, Reading>
+    where
+        T: Default + Copy + PartialEq + GetCurrentAndIncrementStringToIntMapperId,
+    {
+        let mapper = mapper.to_reader();
+        let keys: Vec<String> = mapper.iter_with_status().map(|key| key.key().to_string()).collect();
+        let mut mapper = mapper.to_editer();
+        keys.iter().for_each(|key| {
+            if present.contains(key.as_str()) {
+                mapper.restore(key);
+            } else {
+                mapper.remove(key);
+            }
+        });
+        mapper.to_reader()
+    }
+
+    /// Exchanges that were removed from the exchange symbols on one of the regenerations
+    pub fn deprecated_exchanges(&self) -> &[ExchangeID] {
+        self.exchanges.removed_ids_in_order()
+    }
+
+    /// Currencies that were removed from the exchange symbols on one of the regenerations
+    pub fn deprecated_currencies(&self) -> &[CurrencyID] {
+        self.currencies.removed_ids_in_order()
+    }
+
+    /// Deterministic SHA-256 hex digest of the exchanges, currencies (names in ID order), the exchange pairs
+    /// and the fiat and stable currencies. Networks and path chunks store it to detect a different lexicon.
+    pub fn checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"lexicon-checksum-v1");
+        let update_names = |hasher: &mut Sha256, names: Vec<&str>| {
+            hasher.update((names.len() as u64).to_le_bytes());
+            names.iter().for_each(|name| {
+                hasher.update((name.len() as u64).to_le_bytes());
+                hasher.update(name.as_bytes());
+            });
+        };
+        update_names(&mut hasher, self.exchanges.iter_with_status().map(|name| name.key()).collect());
+        update_names(&mut hasher, self.currencies.iter_with_status().map(|name| name.key()).collect());
+        hasher.update((self.exchange_currency_pairs.len() as u64).to_le_bytes());
+        self.exchange_currency_pairs.iter().for_each(|(exchange, pairs)| {
+            hasher.update([exchange.0]);
+            hasher.update((pairs.len() as u64).to_le_bytes());
+            pairs.iter().for_each(|pair| {
+                hasher.update(pair.first.0.to_le_bytes());
+                hasher.update(pair.second.0.to_le_bytes());
+            });
+        });
+        [&self.fiat_currencies, &self.stable_currencies].iter().for_each(|currencies| {
+            hasher.update((currencies.len() as u64).to_le_bytes());
+            currencies.iter().for_each(|c| hasher.update(c.0.to_le_bytes()));
+        });
+        // networks are only part of the checksum if they are set so older checksums stay valid
+        if !self.exchange_currency_networks.is_empty() {
+            update_names(&mut hasher, self.networks.iter_with_status().map(|name| name.key()).collect());
+            hasher.update((self.exchange_currency_networks.len() as u64).to_le_bytes());
+            self.exchange_currency_networks.iter().for_each(|(ec, networks)| {
+                hasher.update([ec.exchange.0]);
+                hasher.update(ec.currency.0.to_le_bytes());
+                hasher.update((networks.len() as u64).to_le_bytes());
+                networks.iter().for_each(|n| hasher.update(n.0.to_le_bytes()));
+            });
+        }
+        hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Fails if the checksum was generated from a different lexicon
+    pub fn verify_checksum(&self, checksum: &str) -> Result<(), CryptoTypesError> {
+        let expected = self.checksum();
+        if expected != checksum {
+            return Err(CryptoTypesError::LexiconChecksumMismatch(expected, checksum.to_string()));
         }
+        Ok(())
     }
 
     pub fn exchange_to_string(&self, e: &ExchangeID) -> &str {
         if let Some(name) = self.exchanges.get_key(e) {
-            return name;
+            return name.key();
         }
         "N/A"
     }
 
     pub fn currency_to_string(&self, c: &CurrencyID) -> &str {
         if let Some(name) = self.currencies.get_key(c) {
-            return name;
+            return name.key();
         }
         "N/A"
     }
 
+    /// Currency symbol used by the exchange. This is the canonical currency name unless the exchange uses an alias.
+    pub fn exchange_currency_symbol(&self, e: &ExchangeID, c: &CurrencyID) -> &str {
+        match self.exchange_symbols.get(&e.with_currency(c)) {
+            Some(symbol) => symbol,
+            None => self.currency_to_string(c),
+        }
+    }
+
     pub fn currency_pair_to_string(&self, cp: &CurrencyIDPair) -> String {
         format!(
             "{} : {}",
@@ -132,148 +483,192 @@ impl CryptoExchangeLexicon {
     }
 
     pub fn get_currency_pair_strings(&self, cp: &CurrencyIDPair) -> Option<(&str, &str)> {
-        self.currencies.get_key(&cp.first).zip(self.currencies.get_key(&cp.second))
+        let name = |c: &CurrencyID| self.currencies.get_key(c).map(|name| name.key());
+        name(&cp.first).zip(name(&cp.second))
     }
 
-    pub fn print_all_exchanges_currencies(&self) {
-        self.exchange_currency_pairs
-            .iter()
-            .for_each(|exchange_currency_pair| {
-                let (exchange, currency_pairs) = exchange_currency_pair;
-                let exchange_name = self.exchange_to_string(&exchange);
-                println!("Exchange id '{}' name '{}'", exchange.0, exchange_name);
-                currency_pairs.iter().for_each(|currency_pair| {
-                    let currency_pair_name = self.currency_pair_to_string(&currency_pair);
-                    println!(
-                        "\tcurrency pair '{} : {}'\tname '{}'",
-                        currency_pair.first.0, currency_pair.second.0, currency_pair_name
-                    )
-                });
-            });
+    pub fn exchange_currency_pairs_iter(
+        &self,
+    ) -> std::collections::btree_map::Iter<'_, ExchangeID, BTreeSet<CurrencyIDPair>> {
+        self.exchange_currency_pairs.iter()
     }
+}
 
-    pub fn print_all_exchanges_currencies_in_json_format(&self) {
-        let map: HashMap<String, Vec<String>> = self.exchange_currency_pairs
-            .iter()
-            .map(|exchange_currency_pair| {
-                let (exchange, currency_pairs) = exchange_currency_pair;
-                let exchange_name = self.exchange_to_string(&exchange);
-                let pairs: Vec<String> = currency_pairs.iter().map(|currency_pair| {
-                    let currency_pair_name = self.currency_pair_to_string(&currency_pair);
-                    currency_pair_name.replace(" : ", "-")
-                }).collect();
-                (exchange_name.to_string(), pairs)
-            }).collect();
-        println!("{}", serde_json::to_string_pretty(&map).unwrap());
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ex_symbols;
 
-    pub fn print_all_currencies(&self) {
-        self.currencies.iter_in_order().for_each(|c_name| {
-            let c = self.currencies.get_id(&c_name).unwrap();
-            println!("Currency id '{}' name '{}'", c, c_name);
-        });
-    }
+    #[test]
+    fn exchange_ids_exhausted_test() {
+        let exchanges: Vec<_> = (0..256).map(|e| ex_symbols(&format!("exchange{}", e), &["BTC/USD"])).collect();
+        let lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&exchanges).unwrap();
+        assert_eq!(lexicon.exchanges.get_id("exchange255"), Some(&ExchangeID(255)));
 
-    pub fn print_all_currencies_in_json_format(&self) {
-        let currencies: Vec<String> = self.currencies.iter_in_order().map(|c_name| c_name.to_string()).collect();
-        println!("{}", serde_json::to_string_pretty(&currencies).unwrap());
-    }
-    
-    pub fn print_exchanges(&self) {
-        self.exchange_currency_pairs
-            .iter()
-            .for_each(|exchange_currency_pair| {
-                let (exchange, _) = exchange_currency_pair;
-                let exchange_name = self.exchange_to_string(&exchange);
-                println!("Exchange id '{}' name '{}'", exchange.0, exchange_name);
-            });
+        let lexicon = CryptoExchangeLexicon::regenerate_from_previous(lexicon, &[ex_symbols("exchange256", &["BTC/USD"])]);
+        assert!(matches!(
+            lexicon,
+            Err(CryptoTypesError::Mapper(StringToIntMapperError::CapacityExhausted(name))) if name == "exchange256"
+        ));
     }
 
-    pub fn print_exchanges_in_json_format(&self) {
-        let exchanges: Vec<String> = self.exchange_currency_pairs
-            .iter()
-            .map(|exchange_currency_pair| {
-                let (exchange, _) = exchange_currency_pair;
-                let exchange_name = self.exchange_to_string(&exchange);
-                exchange_name.to_string()
-            })
-            .collect();
-        println!("{}", serde_json::to_string_pretty(&exchanges).unwrap());
+    #[test]
+    fn checksum_test() {
+        let lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&[ex_symbols("kraken", &["BTC/USD", "ETH/BTC"])]).unwrap();
+        let same = CryptoExchangeLexicon::create_from_exchange_symbols(&[ex_symbols("kraken", &["ETH/BTC", "BTC/USD"])]).unwrap();
+        let other = CryptoExchangeLexicon::create_from_exchange_symbols(&[ex_symbols("kraken", &["ETH/BTC", "BTC/ETH"])]).unwrap();
+        assert_eq!(lexicon.checksum().len(), 64);
+        assert!(lexicon.verify_checksum(&lexicon.checksum()).is_ok());
+        // same pairs but the currencies got different IDs
+        assert_ne!(lexicon.checksum(), same.checksum());
+        assert!(matches!(
+            lexicon.verify_checksum(&other.checksum()),
+            Err(CryptoTypesError::LexiconChecksumMismatch(_, _))
+        ));
+
+        let mut with_fiat = CryptoExchangeLexicon::create_from_exchange_symbols(&[ex_symbols("kraken", &["BTC/USD", "ETH/BTC"])]).unwrap();
+        assert_eq!(lexicon.checksum(), with_fiat.checksum());
+        with_fiat.set_fiat_currencies(&["USD".into()]);
+        assert_ne!(lexicon.checksum(), with_fiat.checksum());
     }
 
-    pub fn print_exchange_pairs(&self, exchange: &str) {
-        let exchange_id_op = self.exchanges.get_id(&exchange);
-        match exchange_id_op {
-            Some(exchange_id) => {
-                // let exchange_id = ExchangeID(exchange_id as u8);
-                if let Some(currency_pairs) = self.exchange_currency_pairs.get(&exchange_id) {
-                    let exchange_name = self.exchange_to_string(&exchange_id);
-                    println!("Exchange id '{}' name '{}'", exchange_id.0, exchange_name);
-                    currency_pairs.iter().for_each(|currency_pair| {
-                        let currency_pair_name = self.currency_pair_to_string(&currency_pair);
-                        println!(
-                            "\tcurrency pair '{} : {}'\tname '{}'",
-                            currency_pair.first.0, currency_pair.second.0, currency_pair_name
-                        )
-                    });
-                } else {
-                    println!("No exchange {} found!", exchange);
-                }
-            }
-            None => println!("No exchange {} found!", exchange),
+    #[test]
+    fn regenerate_from_previous_keeps_ids_test() {
+        let (mut previous, _) = CryptoExchangeLexicon::create_from_exchange_symbols_full(
+            &[
+                ex_symbols("kraken", &["BTC/USD", "ETH/BTC"]),
+                ex_symbols("bitstamp", &["BTC/USD"]),
+            ],
+            &SymbolNormalisationJson::default(),
+            &["USD".into()],
+            &[],
+        ).unwrap();
+        let kraken = *previous.exchanges.get_id("kraken").unwrap();
+        let eth = *previous.currencies.get_id("ETH").unwrap();
+        let usd = *previous.currencies.get_id("USD").unwrap();
+        let btc = *previous.currencies.get_id("BTC").unwrap();
+        for pair in [CurrencyIDPair::new(btc, usd), CurrencyIDPair::new(eth, btc)] {
+            previous.markets.entry(kraken).or_default().insert(pair, MarketInfo::default());
         }
+
+        // bitstamp and ETH are gone, binance and USDT are new and the input order is different
+        let newer = [
+            ex_symbols("binance", &["BTC/USDT"]),
+            ex_symbols("kraken", &["BTC/USD", "BTC/USDT"]),
+        ];
+        let (lexicon, _) = CryptoExchangeLexicon::regenerate_from_previous(previous, &newer).unwrap();
+        assert_eq!(lexicon.exchanges.get_id("kraken"), Some(&kraken));
+        assert_eq!(lexicon.exchanges.get_id("binance"), Some(&ExchangeID(2)));
+        assert_eq!(lexicon.currencies.get_id("ETH"), None);
+        assert_eq!(lexicon.currencies.get_key(&eth), Some(MappedKey::Tombstoned("ETH")));
+        assert_eq!(lexicon.currencies.get_id("USDT"), Some(&CurrencyID(3)));
+        assert_eq!(lexicon.deprecated_exchanges(), &[ExchangeID(1)]);
+        assert_eq!(lexicon.deprecated_currencies(), &[eth]);
+        assert!(!lexicon.exchange_currency_pairs.contains_key(&ExchangeID(1)));
+        assert_eq!(lexicon.exchange_currency_pairs[&kraken].len(), 2);
+        assert!(lexicon.fiat_currencies.contains(&usd));
+        assert_eq!(lexicon.markets[&kraken].len(), 1);
+        assert!(lexicon.market_info(&kraken, &CurrencyIDPair::new(btc, usd)).is_some());
+
+        // a removed exchange that reappears gets its old ID back
+        let (lexicon, _) = CryptoExchangeLexicon::regenerate_from_previous(lexicon, &[ex_symbols("bitstamp", &["ETH/USD"])]).unwrap();
+        assert_eq!(lexicon.exchanges.get_id("bitstamp"), Some(&ExchangeID(1)));
+        assert_eq!(lexicon.deprecated_exchanges(), &[ExchangeID(0), ExchangeID(2)]);
+        assert!(!lexicon.deprecated_currencies().contains(&eth));
+        assert_eq!(lexicon.currencies.get_id("ETH"), Some(&eth));
     }
 
-    fn get_inverse_pairs(pairs: &BTreeSet<CurrencyIDPair>) -> BTreeSet<&CurrencyIDPair> {
-        let inverse_pairs: BTreeSet<&CurrencyIDPair> = pairs.iter().enumerate().flat_map(|pair| {
-            let (index_at, pair_a) = pair;
-            let mut inverse_b_pairs: BTreeSet<_> = pairs.iter().skip(index_at).filter(|pair_b| CurrencyIDPair::is_inverse(pair_a, pair_b)).collect();
-            if !inverse_b_pairs.is_empty() {
-                inverse_b_pairs.insert(pair_a);
-            }
-            inverse_b_pairs
-        }).collect();
-        inverse_pairs
+    #[test]
+    fn fiat_and_stable_currencies_test() {
+        let (lexicon, warnings) = CryptoExchangeLexicon::create_from_exchange_symbols_full(
+            &[
+                ex_symbols("kraken", &["XBT/EUR", "XBT/USDT"]),
+                ex_symbols("binance", &["BTC/USDT"]),
+            ],
+            &SymbolNormalisationJson::default(),
+            &[
+                "EUR".into(),
+                CurrencyDefinitionJson {
+                    name: "USD".to_string(),
+                    aliases: vec!["ZUSD".to_string()],
+                },
+            ],
+            &[CurrencyDefinitionJson {
+                name: "USDT".to_string(),
+                aliases: vec!["USDT0".to_string()],
+            }],
+        ).unwrap();
+        let eur = *lexicon.currencies.get_id("EUR").unwrap();
+        let usdt = *lexicon.currencies.get_id("USDT").unwrap();
+        assert_eq!(lexicon.fiat_currencies, BTreeSet::from([eur]));
+        assert_eq!(lexicon.stable_currencies, BTreeSet::from([usdt]));
+        assert_eq!(warnings, vec![LexiconWarning::UnknownFiatCurrency("USD".to_string())]);
     }
 
-    pub fn verify_exchange_currency_pairs(&self) -> bool {
-        let mut ok = true;
-        self.exchange_currency_pairs
-            .iter()
-            .for_each(|exchange_currency_pair| {
-                let (exchange, pairs) = exchange_currency_pair;
-                let bad_pairs: Vec<_> = pairs
-                    .iter()
-                    .filter(|pair| pair.has_same_currencies())
-                    .collect();
-                let inverse_pairs = Self::get_inverse_pairs(pairs);
-
-                if !bad_pairs.is_empty() || !inverse_pairs.is_empty() {
-                    ok = false;
-                    let exchange_name = self.exchange_to_string(&exchange);
-                    println!("Exchange id '{}' name '{}'", exchange.0, exchange_name);
-                    bad_pairs.iter().for_each(|currency_pair| {
-                        let currency_pair_name = self.currency_pair_to_string(&currency_pair);
-                        println!(
-                            "\tBad currency pair '{} : {}'\tname '{}'",
-                            currency_pair.first.0, currency_pair.second.0, currency_pair_name
-                        )
-                    });
-                    inverse_pairs.iter().for_each(|currency_pair| {
-                        let currency_pair_name = self.currency_pair_to_string(&currency_pair);
-                        println!(
-                            "\tInverse currency pair '{} : {}'\tname '{}'",
-                            currency_pair.first.0, currency_pair.second.0, currency_pair_name
-                        )
-                    });
-                }
-            });
-        ok
+    #[test]
+    fn symbol_normalisation_test() {
+        let normalisation = SymbolNormalisationJson {
+            aliases: BTreeMap::from([("XBT".to_string(), "BTC".to_string())]),
+            exchange_overrides: BTreeMap::from([(
+                "hitbtc".to_string(),
+                BTreeMap::from([("BCH".to_string(), "BCHABC".to_string())]),
+            )]),
+        };
+        let (lexicon, _) = CryptoExchangeLexicon::create_from_exchange_symbols_full(
+            &[
+                ex_symbols("kraken", &["XBT/USD", "BCH/USD"]),
+                ex_symbols("hitbtc", &["BTC/USD", "BCH/USD"]),
+            ],
+            &normalisation,
+            &[],
+            &[],
+        ).unwrap();
+        let kraken = *lexicon.exchanges.get_id("kraken").unwrap();
+        let hitbtc = *lexicon.exchanges.get_id("hitbtc").unwrap();
+        let btc = *lexicon.currencies.get_id("BTC").unwrap();
+        let usd = *lexicon.currencies.get_id("USD").unwrap();
+        let bchabc = *lexicon.currencies.get_id("BCHABC").unwrap();
+        assert_eq!(lexicon.currencies.get_id("XBT"), None);
+        assert!(lexicon.exchange_currency_pairs[&kraken].contains(&CurrencyIDPair::new(btc, usd)));
+        assert!(lexicon.exchange_currency_pairs[&hitbtc].contains(&CurrencyIDPair::new(bchabc, usd)));
+        assert_eq!(lexicon.exchange_currency_symbol(&kraken, &btc), "XBT");
+        assert_eq!(lexicon.exchange_currency_symbol(&hitbtc, &btc), "BTC");
+        assert_eq!(lexicon.exchange_currency_symbol(&hitbtc, &bchabc), "BCH");
+        assert_eq!(lexicon.exchange_symbols.len(), 2);
     }
 
-    pub fn exchange_currency_pairs_iter(
-        &self,
-    ) -> std::collections::btree_map::Iter<'_, ExchangeID, BTreeSet<CurrencyIDPair>> {
-        self.exchange_currency_pairs.iter()
+    #[test]
+    fn conflicting_aliases_test() {
+        let normalisation = SymbolNormalisationJson {
+            aliases: BTreeMap::from([("XBT".to_string(), "BTC".to_string())]),
+            ..Default::default()
+        };
+        let (lexicon, warnings) = CryptoExchangeLexicon::create_from_exchange_symbols_full(
+            &[ex_symbols("kraken", &["XBT/USD", "BTC/USD", "ETH/BTC", "BTC/USD/EUR", "XBT/BTC", "LTC/LTC"])],
+            &normalisation,
+            &[],
+            &[],
+        ).unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                LexiconWarning::InvalidSymbol("kraken".to_string(), "BTC/USD/EUR".to_string()),
+                LexiconWarning::InvalidMarket("kraken".to_string(), "XBT/BTC".to_string()),
+                LexiconWarning::InvalidMarket("kraken".to_string(), "LTC/LTC".to_string()),
+                LexiconWarning::DuplicateMarket("kraken".to_string(), "BTC/USD".to_string(), "XBT/USD".to_string()),
+                LexiconWarning::ConflictingCurrencySymbols(
+                    "kraken".to_string(),
+                    "BTC".to_string(),
+                    "XBT".to_string(),
+                    "BTC".to_string()
+                ),
+            ]
+        );
+        let kraken = *lexicon.exchanges.get_id("kraken").unwrap();
+        let btc = *lexicon.currencies.get_id("BTC").unwrap();
+        assert_eq!(lexicon.exchange_currency_pairs[&kraken].len(), 2);
+        assert_eq!(lexicon.exchange_currency_symbol(&kraken, &btc), "XBT");
+        // currencies only traded in invalid markets aren't added
+        assert_eq!(lexicon.currencies.get_id("LTC"), None);
     }
 }
@@ -0,0 +1,354 @@
+use crate::arbitrage_paths::*;
+use crate::id_types::*;
+use crate::lexicon::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use string_to_int_mapper::*;
+
+/// Same name mapped to a different ID in the compared lexicons
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct IdRemap {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+}
+
+/// Added and removed currency pairs/markets of an exchange. Pairs are formatted as 'BASE/QUOTE'.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ExchangePairsDiff {
+    pub exchange: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Differences between two lexicons compared by names. Deprecated exchanges and currencies are treated as absent.
+#[derive(Default, Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct LexiconDiff {
+    pub added_exchanges: Vec<String>,
+    pub removed_exchanges: Vec<String>,
+    pub added_currencies: Vec<String>,
+    pub removed_currencies: Vec<String>,
+    pub exchange_remaps: Vec<IdRemap>,
+    pub currency_remaps: Vec<IdRemap>,
+    pub exchange_pairs: Vec<ExchangePairsDiff>,
+}
+
+fn live_names<T>(mapper: &StringToIntMapper<T, Reading>) -> BTreeMap<String, usize>
+where
+    T: Default + PartialEq + GetCurrentAndIncrementStringToIntMapperId,
+{
+    mapper
+        .iter_in_order()
+        .filter_map(|key| mapper.get_id(key).map(|id| (key.clone(), id.to_usize())))
+        .collect()
+}
+
+fn exchange_pair_names(lexicon: &CryptoExchangeLexicon) -> BTreeMap<String, BTreeSet<String>> {
+    lexicon
+        .exchange_currency_pairs
+        .iter()
+        .map(|(exchange, pairs)| {
+            let pairs = pairs
+                .iter()
+                .map(|pair| {
+                    format!(
+                        "{}/{}",
+                        lexicon.currency_to_string(&pair.first),
+                        lexicon.currency_to_string(&pair.second)
+                    )
+                })
+                .collect();
+            (lexicon.exchange_to_string(exchange).to_string(), pairs)
+        })
+        .collect()
+}
+
+/// Returns (added, removed, remapped) names
+fn diff_names(a: &BTreeMap<String, usize>, b: &BTreeMap<String, usize>) -> (Vec<String>, Vec<String>, Vec<IdRemap>) {
+    let added = b.keys().filter(|name| !a.contains_key(*name)).cloned().collect();
+    let removed = a.keys().filter(|name| !b.contains_key(*name)).cloned().collect();
+    let remaps = a
+        .iter()
+        .filter_map(|(name, from)| match b.get(name) {
+            Some(to) if to != from => Some(IdRemap {
+                name: name.clone(),
+                from: *from,
+                to: *to,
+            }),
+            _ => None,
+        })
+        .collect();
+    (added, removed, remaps)
+}
+
+impl LexiconDiff {
+    /// Compares lexicon `a` (old) with lexicon `b` (new)
+    pub fn create(a: &CryptoExchangeLexicon, b: &CryptoExchangeLexicon) -> Self {
+        let (added_exchanges, removed_exchanges, exchange_remaps) = diff_names(
+            &live_names(&a.exchanges),
+            &live_names(&b.exchanges),
+        );
+        let (added_currencies, removed_currencies, currency_remaps) = diff_names(
+            &live_names(&a.currencies),
+            &live_names(&b.currencies),
+        );
+        let a_pairs = exchange_pair_names(a);
+        let b_pairs = exchange_pair_names(b);
+        let empty = BTreeSet::new();
+        let exchanges: BTreeSet<&String> = a_pairs.keys().chain(b_pairs.keys()).collect();
+        let exchange_pairs = exchanges
+            .into_iter()
+            .filter_map(|exchange| {
+                let a = a_pairs.get(exchange).unwrap_or(&empty);
+                let b = b_pairs.get(exchange).unwrap_or(&empty);
+                let diff = ExchangePairsDiff {
+                    exchange: exchange.clone(),
+                    added: b.difference(a).cloned().collect(),
+                    removed: a.difference(b).cloned().collect(),
+                };
+                if diff.added.is_empty() && diff.removed.is_empty() {
+                    None
+                } else {
+                    Some(diff)
+                }
+            })
+            .collect();
+        LexiconDiff {
+            added_exchanges,
+            removed_exchanges,
+            added_currencies,
+            removed_currencies,
+            exchange_remaps,
+            currency_remaps,
+            exchange_pairs,
+        }
+    }
+
+    /// Same exchanges, currencies and pairs but the IDs could differ
+    pub fn is_same_domain(&self) -> bool {
+        self.added_exchanges.is_empty()
+            && self.removed_exchanges.is_empty()
+            && self.added_currencies.is_empty()
+            && self.removed_currencies.is_empty()
+            && self.exchange_pairs.is_empty()
+    }
+
+    /// Same domain with the same IDs. Networks generated with one lexicon are valid for the other.
+    pub fn is_exact_match(&self) -> bool {
+        self.is_same_domain() && self.exchange_remaps.is_empty() && self.currency_remaps.is_empty()
+    }
+}
+
+/// Maps the IDs of an old lexicon to the IDs of a new lexicon by names. Operations are only mapped if
+/// they are still valid in the new lexicon e.g. the market of a transaction must exist on the new exchange.
+pub struct LexiconIdRemapper {
+    exchanges: BTreeMap<ExchangeID, ExchangeID>,
+    currencies: BTreeMap<CurrencyID, CurrencyID>,
+    exchange_currency_pairs: BTreeMap<ExchangeID, BTreeSet<CurrencyIDPair>>,
+    balances: BTreeSet<BalanceExchangeCurrencyInfo>,
+    networks: BTreeMap<NetworkID, NetworkID>,
+    exchange_currency_networks: BTreeMap<ExchangeIDCurrencyIDPair, BTreeSet<NetworkID>>,
+}
+
+fn remap_ids<T>(old: &StringToIntMapper<T, Reading>, new: &StringToIntMapper<T, Reading>) -> BTreeMap<T, T>
+where
+    T: Default + Copy + Ord + GetCurrentAndIncrementStringToIntMapperId,
+{
+    old.iter_in_order()
+        .filter_map(|key| old.get_id(key).copied().zip(new.get_id(key).copied()))
+        .collect()
+}
+
+impl LexiconIdRemapper {
+    pub fn create(old: &CryptoExchangeLexicon, new: &CryptoExchangeLexicon) -> Self {
+        let balances = new
+            .exchange_currency_pairs
+            .iter()
+            .flat_map(|(exchange, pairs)| {
+                pairs
+                    .iter()
+                    .flat_map(move |pair| [exchange.with_currency(&pair.first), exchange.with_currency(&pair.second)])
+            })
+            .collect();
+        LexiconIdRemapper {
+            exchanges: remap_ids(&old.exchanges, &new.exchanges),
+            currencies: remap_ids(&old.currencies, &new.currencies),
+            exchange_currency_pairs: new.exchange_currency_pairs.clone(),
+            balances,
+            networks: remap_ids(&old.networks, &new.networks),
+            exchange_currency_networks: new.exchange_currency_networks.clone(),
+        }
+    }
+
+    pub fn exchange(&self, e: &ExchangeID) -> Option<ExchangeID> {
+        self.exchanges.get(e).copied()
+    }
+
+    pub fn currency(&self, c: &CurrencyID) -> Option<CurrencyID> {
+        self.currencies.get(c).copied()
+    }
+
+    /// Every exchange currency balance of the new lexicon. These are the search targets of a new network.
+    pub fn balances(&self) -> &BTreeSet<BalanceExchangeCurrencyInfo> {
+        &self.balances
+    }
+
+    /// Maps the pair of an old exchange market where `exchange` is already the new exchange ID
+    pub fn pair(&self, exchange: &ExchangeID, pair: &CurrencyIDPair) -> Option<CurrencyIDPair> {
+        let pair = CurrencyIDPair::new(self.currency(&pair.first)?, self.currency(&pair.second)?);
+        self.exchange_currency_pairs.get(exchange)?.contains(&pair).then_some(pair)
+    }
+
+    pub fn network(&self, n: &NetworkID) -> Option<NetworkID> {
+        self.networks.get(n).copied()
+    }
+
+    /// A transfer over a specific network is only valid if the new lexicon lists the network for the exchange currency
+    fn supports_network(&self, exchange: &ExchangeID, currency: &CurrencyID, network: &Option<NetworkID>) -> bool {
+        match network {
+            Some(network) => self
+                .exchange_currency_networks
+                .get(&(*exchange, *currency).into())
+                .is_some_and(|networks| networks.contains(network)),
+            None => true,
+        }
+    }
+
+    pub fn balance(&self, b: &BalanceExchangeCurrencyInfo) -> Option<BalanceExchangeCurrencyInfo> {
+        let b = self.exchange(&b.exchange)?.with_currency(&self.currency(&b.currency)?);
+        self.balances.contains(&b).then_some(b)
+    }
+
+    pub fn operation(&self, op: &ExchangeOperationType) -> Option<ExchangeOperationType> {
+        match op {
+            ExchangeOperationType::Balance(b) => self.balance(b).map(ExchangeOperationType::Balance),
+            ExchangeOperationType::Transaction(tx) => {
+                let exchange = self.exchange(&tx.exchange)?;
+                self.pair(&exchange, &tx_to_currency_id_pairs(tx))?;
+                let tx = TransactionExchangeCurrenciesBuySellInfo {
+                    exchange,
+                    side: tx.side,
+                    currency_from: self.currency(&tx.currency_from)?,
+                    currency_to: self.currency(&tx.currency_to)?,
+                };
+                Some(tx.into())
+            }
+            ExchangeOperationType::Transfer(tr) => {
+                let tr = TransferExchangeToExchangeCurrencyInfo {
+                    withdraw_exchange: self.exchange(&tr.withdraw_exchange)?,
+                    deposit_exchange: self.exchange(&tr.deposit_exchange)?,
+                    currency: self.currency(&tr.currency)?,
+                    network: match &tr.network {
+                        Some(network) => Some(self.network(network)?),
+                        None => None,
+                    },
+                };
+                let is_valid = self.balances.contains(&tr.withdraw_exchange.with_currency(&tr.currency))
+                    && self.balances.contains(&tr.deposit_exchange.with_currency(&tr.currency))
+                    && self.supports_network(&tr.withdraw_exchange, &tr.currency, &tr.network)
+                    && self.supports_network(&tr.deposit_exchange, &tr.currency, &tr.network);
+                is_valid.then_some(tr.into())
+            }
+        }
+    }
+
+    /// Maps every node of the path. Returns None if any of the nodes isn't valid in the new lexicon.
+    pub fn path(&self, path: &ArbitragePath) -> Option<ArbitragePath> {
+        path.0.iter().map(|op| self.operation(op)).collect::<Option<_>>().map(ArbitragePath)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ex_symbols;
+
+    #[test]
+    fn lexicon_diff_test() {
+        let a = CryptoExchangeLexicon::create_from_exchange_symbols(&[
+            ex_symbols("kraken", &["BTC/USD", "ETH/BTC"]),
+            ex_symbols("bitstamp", &["BTC/USD"]),
+        ]).unwrap();
+        let same = CryptoExchangeLexicon::create_from_exchange_symbols(&[
+            ex_symbols("kraken", &["ETH/BTC", "BTC/USD"]),
+            ex_symbols("bitstamp", &["BTC/USD"]),
+        ]).unwrap();
+        let diff = LexiconDiff::create(&a, &same);
+        assert!(diff.is_same_domain());
+        assert!(!diff.is_exact_match());
+        assert_eq!(
+            diff.currency_remaps,
+            vec![
+                IdRemap {
+                    name: "BTC".to_string(),
+                    from: 0,
+                    to: 1
+                },
+                IdRemap {
+                    name: "ETH".to_string(),
+                    from: 2,
+                    to: 0
+                },
+                IdRemap {
+                    name: "USD".to_string(),
+                    from: 1,
+                    to: 2
+                },
+            ]
+        );
+
+        let b = CryptoExchangeLexicon::create_from_exchange_symbols(&[
+            ex_symbols("kraken", &["BTC/USD", "BTC/EUR"]),
+            ex_symbols("binance", &["BTC/USDT"]),
+        ]).unwrap();
+        let diff = LexiconDiff::create(&a, &b);
+        assert_eq!(diff.added_exchanges, vec!["binance"]);
+        assert_eq!(diff.removed_exchanges, vec!["bitstamp"]);
+        assert_eq!(diff.added_currencies, vec!["EUR", "USDT"]);
+        assert_eq!(diff.removed_currencies, vec!["ETH"]);
+        assert_eq!(diff.exchange_pairs.len(), 3);
+        let kraken = diff.exchange_pairs.iter().find(|p| p.exchange == "kraken").unwrap();
+        assert_eq!(kraken.added, vec!["BTC/EUR"]);
+        assert_eq!(kraken.removed, vec!["ETH/BTC"]);
+
+        assert!(LexiconDiff::create(&a, &a).is_exact_match());
+    }
+
+    #[test]
+    fn lexicon_id_remapper_test() {
+        let old = CryptoExchangeLexicon::create_from_exchange_symbols(&[
+            ex_symbols("kraken", &["BTC/USD", "ETH/BTC"]),
+            ex_symbols("bitstamp", &["BTC/USD"]),
+        ]).unwrap();
+        let new = CryptoExchangeLexicon::create_from_exchange_symbols(&[
+            ex_symbols("bitstamp", &["BTC/USD", "ETH/BTC"]),
+            ex_symbols("kraken", &["BTC/USD"]),
+        ]).unwrap();
+        let remapper = LexiconIdRemapper::create(&old, &new);
+        let (old_kraken, new_kraken) = (ExchangeID(0), ExchangeID(1));
+        let (old_btc, old_usd, old_eth) = (CurrencyID(0), CurrencyID(1), CurrencyID(2));
+        assert_eq!(remapper.exchange(&old_kraken), Some(new_kraken));
+        assert_eq!(
+            remapper.pair(&new_kraken, &CurrencyIDPair::new(old_btc, old_usd)),
+            Some(CurrencyIDPair::new(CurrencyID(0), CurrencyID(1)))
+        );
+        // kraken doesn't list ETH/BTC anymore
+        assert_eq!(remapper.pair(&new_kraken, &CurrencyIDPair::new(old_eth, old_btc)), None);
+        assert_eq!(remapper.balance(&old_kraken.with_currency(&old_eth)), None);
+        let transfer: ExchangeOperationType = TransferExchangeToExchangeCurrencyInfo {
+            withdraw_exchange: old_kraken,
+            deposit_exchange: ExchangeID(1),
+            currency: old_btc,
+            network: None,
+        }
+        .into();
+        let expected: ExchangeOperationType = TransferExchangeToExchangeCurrencyInfo {
+            withdraw_exchange: new_kraken,
+            deposit_exchange: ExchangeID(0),
+            currency: CurrencyID(0),
+            network: None,
+        }
+        .into();
+        assert_eq!(remapper.operation(&transfer), Some(expected));
+    }
+}
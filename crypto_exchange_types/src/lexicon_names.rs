@@ -0,0 +1,68 @@
+use crate::id_types::*;
+use crate::lexicon::*;
+use serde::{Deserialize, Serialize};
+use string_to_int_mapper::*;
+
+/// Read only exchange, currency and network names of a lexicon for the consumers that only resolve names.
+/// The names are kept in frozen mappers so deserialisation (e.g. bincode from a memory mapped file)
+/// borrows them from the input buffer instead of allocating every key twice. Corrupted names fail to deserialise.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrozenLexiconNames<'a> {
+    /// Checksum of the lexicon the names were frozen from
+    pub lexicon_checksum: String,
+    #[serde(borrow)]
+    pub exchanges: FrozenStringToIntMapper<'a, ExchangeID>,
+    #[serde(borrow)]
+    pub currencies: FrozenStringToIntMapper<'a, CurrencyID>,
+    #[serde(borrow)]
+    pub networks: FrozenStringToIntMapper<'a, NetworkID>,
+}
+
+impl<'a> FrozenLexiconNames<'a> {
+    pub fn exchange_to_string(&self, e: &ExchangeID) -> &str {
+        self.exchanges.get_key(e).map_or("", |name| name.key())
+    }
+
+    pub fn currency_to_string(&self, c: &CurrencyID) -> &str {
+        self.currencies.get_key(c).map_or("", |name| name.key())
+    }
+}
+
+impl CryptoExchangeLexicon {
+    pub fn freeze_names(&self) -> Result<FrozenLexiconNames<'static>, CryptoTypesError> {
+        Ok(FrozenLexiconNames {
+            lexicon_checksum: self.checksum(),
+            exchanges: FrozenStringToIntMapper::from_mapper(&self.exchanges)?,
+            currencies: FrozenStringToIntMapper::from_mapper(&self.currencies)?,
+            networks: FrozenStringToIntMapper::from_mapper(&self.networks)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ex_symbols;
+
+    #[test]
+    fn freeze_names_test() {
+        let lexicon = CryptoExchangeLexicon::regenerate_from_previous(
+            CryptoExchangeLexicon::create_from_exchange_symbols(&[ex_symbols("kraken", &["BTC/USD", "ETH/BTC"])]).unwrap(),
+            &[ex_symbols("kraken", &["BTC/USD"])],
+        )
+        .unwrap()
+        .0;
+        let names = lexicon.freeze_names().unwrap();
+        assert_eq!(names.lexicon_checksum, lexicon.checksum());
+        assert_eq!(names.exchanges.get_id("kraken"), Some(ExchangeID(0)));
+        assert_eq!(names.currency_to_string(&CurrencyID(1)), "USD");
+        // deprecated currencies keep their names but don't resolve
+        assert_eq!(names.currencies.get_id("ETH"), None);
+        assert_eq!(names.currency_to_string(&CurrencyID(2)), "ETH");
+
+        let json = serde_json::to_string(&names).unwrap();
+        let borrowed: FrozenLexiconNames = serde_json::from_str(&json).unwrap();
+        assert!(borrowed.currencies.is_borrowed());
+        assert_eq!(borrowed.exchange_to_string(&ExchangeID(0)), "kraken");
+    }
+}
@@ -0,0 +1,182 @@
+use crate::id_types::*;
+use crate::lexicon::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use string_to_int_mapper::*;
+
+/// Networks (chains) an exchange supports for the deposits and withdrawals of its currencies.
+/// Currencies are exchange symbols, they are normalised with the normalisation table of the lexicon.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeCurrencyNetworksJson {
+    pub exchange: String,
+    /// Currency symbol to network names e.g. 'USDT' to ['ERC20', 'TRC20']
+    pub currencies: BTreeMap<String, Vec<String>>,
+}
+
+/// Networks a currency can be transferred on between two exchanges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferNetworks {
+    /// Neither exchange reports networks for the currency
+    Unknown,
+    /// Only one of the exchanges reports networks for the currency
+    OneSided,
+    /// Networks supported by both exchanges, empty if they share no network
+    Shared(BTreeSet<NetworkID>),
+}
+
+impl TransferNetworks {
+    pub fn between(from: Option<&BTreeSet<NetworkID>>, to: Option<&BTreeSet<NetworkID>>) -> Self {
+        match (from, to) {
+            (None, None) => TransferNetworks::Unknown,
+            (Some(from), Some(to)) => TransferNetworks::Shared(from.intersection(to).copied().collect()),
+            _ => TransferNetworks::OneSided,
+        }
+    }
+
+    /// One transfer per shared network. A single transfer without a network if the networks are unknown
+    /// and none if only one of the exchanges reports its networks.
+    pub fn transfers(&self) -> Vec<Option<NetworkID>> {
+        match self {
+            TransferNetworks::Unknown => vec![None],
+            TransferNetworks::OneSided => Vec::new(),
+            TransferNetworks::Shared(networks) => networks.iter().map(|network| Some(*network)).collect(),
+        }
+    }
+}
+
+impl CryptoExchangeLexicon {
+    /// Replaces the supported networks of the exchange currencies. Network IDs of the previous
+    /// networks are kept stable. Exchanges and currencies that aren't part of the lexicon are skipped
+    /// and reported as warnings.
+    pub fn set_currency_networks(
+        &mut self,
+        exchange_networks: &[ExchangeCurrencyNetworksJson],
+    ) -> Result<Vec<LexiconWarning>, CryptoTypesError> {
+        let mut networks = if self.networks.is_empty() {
+            StringToIntMapper::<NetworkID, Editing>::new_with_normaliser(KeyNormaliser::symbols())
+        } else {
+            std::mem::take(&mut self.networks).to_editer()
+        };
+        let mut exchange_currency_networks = BTreeMap::new();
+        let mut warnings = Vec::new();
+        for ex_networks in exchange_networks {
+            let exchange = match self.exchanges.get_id(&ex_networks.exchange) {
+                Some(exchange) => *exchange,
+                None => {
+                    warnings.push(LexiconWarning::UnknownNetworkExchange(ex_networks.exchange.clone()));
+                    continue;
+                }
+            };
+            for (symbol, names) in ex_networks.currencies.iter().filter(|(_, names)| !names.is_empty()) {
+                let currency = self
+                    .currencies
+                    .get_id(self.normalisation.canonical(&ex_networks.exchange, symbol))
+                    .copied()
+                    .filter(|currency| self.exchange_has_currency(&exchange, currency));
+                let currency = match currency {
+                    Some(currency) => currency,
+                    None => {
+                        warnings.push(LexiconWarning::UnknownNetworkCurrency(
+                            ex_networks.exchange.clone(),
+                            symbol.clone(),
+                        ));
+                        continue;
+                    }
+                };
+                let ids = networks.extend(names)?;
+                exchange_currency_networks
+                    .entry(exchange.with_currency(&currency))
+                    .or_insert_with(BTreeSet::new)
+                    .extend(ids);
+            }
+        }
+        self.networks = networks.to_reader();
+        self.exchange_currency_networks = exchange_currency_networks;
+        Ok(warnings)
+    }
+
+    /// Networks the exchange supports for the currency. None if the networks of the currency are unknown.
+    pub fn currency_networks(&self, e: &ExchangeID, c: &CurrencyID) -> Option<&BTreeSet<NetworkID>> {
+        self.exchange_currency_networks.get(&e.with_currency(c))
+    }
+
+    /// Networks the currency can be transferred on between the exchanges
+    pub fn transfer_networks(&self, from: &ExchangeID, to: &ExchangeID, c: &CurrencyID) -> TransferNetworks {
+        TransferNetworks::between(self.currency_networks(from, c), self.currency_networks(to, c))
+    }
+
+    pub fn network_to_string(&self, n: &NetworkID) -> &str {
+        if let Some(name) = self.networks.get_key(n) {
+            return name.key();
+        }
+        "N/A"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks(exchange: &str, currencies: &[(&str, &[&str])]) -> ExchangeCurrencyNetworksJson {
+        ExchangeCurrencyNetworksJson {
+            exchange: exchange.to_string(),
+            currencies: currencies
+                .iter()
+                .map(|(c, names)| (c.to_string(), names.iter().map(|n| n.to_string()).collect()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn transfer_networks_test() {
+        let mut lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&[
+            ExchangeSymbolsJson {
+                exchange: "kraken".to_string(),
+                symbols: vec!["BTC/USDT".to_string()],
+            },
+            ExchangeSymbolsJson {
+                exchange: "binance".to_string(),
+                symbols: vec!["BTC/USDT".to_string()],
+            },
+        ])
+        .unwrap();
+        let checksum = lexicon.checksum();
+        let warnings = lexicon
+            .set_currency_networks(&[
+                networks("kraken", &[("USDT", &["ERC20", "TRC20"]), ("ETH", &["ERC20"])]),
+                networks("binance", &[("USDT", &["trc20 ", "BEP20"]), ("BTC", &["BTC"])]),
+                networks("bitstamp", &[("USDT", &["ERC20"])]),
+            ])
+            .unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                LexiconWarning::UnknownNetworkCurrency("kraken".to_string(), "ETH".to_string()),
+                LexiconWarning::UnknownNetworkExchange("bitstamp".to_string()),
+            ]
+        );
+        assert_ne!(checksum, lexicon.checksum());
+
+        let kraken = *lexicon.exchanges.get_id("kraken").unwrap();
+        let binance = *lexicon.exchanges.get_id("binance").unwrap();
+        let usdt = *lexicon.currencies.get_id("USDT").unwrap();
+        let btc = *lexicon.currencies.get_id("BTC").unwrap();
+        let trc20 = *lexicon.networks.get_id("TRC20").unwrap();
+        assert_eq!(lexicon.network_to_string(&trc20), "TRC20");
+        assert_eq!(lexicon.currency_networks(&binance, &usdt).map(|n| n.len()), Some(2));
+        assert_eq!(
+            lexicon.transfer_networks(&kraken, &binance, &usdt),
+            TransferNetworks::Shared(BTreeSet::from([trc20]))
+        );
+        assert_eq!(lexicon.transfer_networks(&kraken, &binance, &usdt).transfers(), vec![Some(trc20)]);
+        assert_eq!(lexicon.transfer_networks(&kraken, &binance, &btc), TransferNetworks::OneSided);
+        assert!(lexicon.transfer_networks(&kraken, &binance, &btc).transfers().is_empty());
+
+        // IDs are kept when the networks are replaced
+        lexicon.set_currency_networks(&[networks("kraken", &[("USDT", &["TRC20"])])]).unwrap();
+        assert_eq!(lexicon.networks.get_id("trc20"), Some(&trc20));
+        assert_eq!(lexicon.currency_networks(&binance, &usdt), None);
+        assert_eq!(lexicon.transfer_networks(&kraken, &binance, &usdt), TransferNetworks::OneSided);
+        assert_eq!(lexicon.transfer_networks(&binance, &kraken, &btc).transfers(), vec![None]);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::id_types::*;
+use crate::lexicon::*;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// Currencies that are listed on both exchanges. Only crypto currencies can be transferred between exchanges.
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct TransferConnectivity {
+    pub from: ExchangeID,
+    pub to: ExchangeID,
+    pub transferable_currencies: BTreeSet<CurrencyID>,
+    /// Shared fiat currencies. These are not transferred by the network.
+    pub fiat_currencies: BTreeSet<CurrencyID>,
+}
+
+impl TransferConnectivity {
+    pub fn is_connected(&self) -> bool {
+        !self.transferable_currencies.is_empty()
+    }
+}
+
+impl CryptoExchangeLexicon {
+    /// True for the exchanges that aren't deprecated
+    pub fn has_exchange(&self, name: &str) -> bool {
+        self.exchanges.get_id(name).is_some()
+    }
+
+    /// True for the currencies that aren't deprecated. The symbol is normalised first e.g. 'XBT' -> 'BTC'.
+    pub fn has_currency(&self, symbol: &str) -> bool {
+        self.normalised_currency_id(None, symbol).is_some()
+    }
+
+    /// Currency ID of the symbol after normalisation. The overrides of the exchange are applied when it's given.
+    pub fn normalised_currency_id(&self, exchange: Option<&str>, symbol: &str) -> Option<CurrencyID> {
+        let canonical = self.normalisation.canonical(exchange.unwrap_or_default(), symbol);
+        self.currencies.get_id(canonical).copied()
+    }
+
+    pub fn exchange_has_pair(&self, e: &ExchangeID, pair: &CurrencyIDPair) -> bool {
+        self.exchange_currency_pairs
+            .get(e)
+            .is_some_and(|pairs| pairs.contains(pair))
+    }
+
+    pub fn exchange_has_currency(&self, e: &ExchangeID, c: &CurrencyID) -> bool {
+        self.exchange_currency_pairs
+            .get(e)
+            .is_some_and(|pairs| pairs.iter().any(|pair| pair.cointains(c)))
+    }
+
+    /// All currencies traded on the exchange
+    pub fn exchange_currencies(&self, e: &ExchangeID) -> BTreeSet<CurrencyID> {
+        self.exchange_currency_pairs
+            .get(e)
+            .map(|pairs| pairs.iter().flat_map(|pair| [pair.first, pair.second]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Exchanges that list at least one pair with the currency
+    pub fn exchanges_with_currency(&self, c: &CurrencyID) -> Vec<ExchangeID> {
+        self.exchange_currency_pairs
+            .iter()
+            .filter(|(_, pairs)| pairs.iter().any(|pair| pair.cointains(c)))
+            .map(|(e, _)| *e)
+            .collect()
+    }
+
+    /// Pairs of the exchange that contain the currency as base or quote
+    pub fn exchange_currency_pairs_with(&self, e: &ExchangeID, c: &CurrencyID) -> Vec<CurrencyIDPair> {
+        self.exchange_currency_pairs
+            .get(e)
+            .map(|pairs| pairs.iter().filter(|pair| pair.cointains(c)).copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Currencies traded on every given exchange. Empty for no exchanges.
+    pub fn common_currencies(&self, exchanges: &[ExchangeID]) -> BTreeSet<CurrencyID> {
+        let mut exchanges = exchanges.iter();
+        let first = match exchanges.next() {
+            Some(e) => self.exchange_currencies(e),
+            None => return BTreeSet::new(),
+        };
+        exchanges.fold(first, |common, e| {
+            let currencies = self.exchange_currencies(e);
+            common.intersection(&currencies).copied().collect()
+        })
+    }
+
+    pub fn transfer_connectivity(&self, from: &ExchangeID, to: &ExchangeID) -> TransferConnectivity {
+        let (fiat_currencies, transferable_currencies) = self
+            .common_currencies(&[*from, *to])
+            .into_iter()
+            .partition(|c| self.fiat_currencies.contains(c));
+        TransferConnectivity {
+            from: *from,
+            to: *to,
+            transferable_currencies,
+            fiat_currencies,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn lexicon_query_test() {
+        let (lexicon, _) = CryptoExchangeLexicon::create_from_exchange_symbols_full(
+            &[
+                ExchangeSymbolsJson {
+                    exchange: "kraken".to_string(),
+                    symbols: vec!["BTC/USD".to_string(), "ETH/BTC".to_string(), "ETH/USD".to_string()],
+                },
+                ExchangeSymbolsJson {
+                    exchange: "bitstamp".to_string(),
+                    symbols: vec!["BTC/USD".to_string(), "XRP/USD".to_string()],
+                },
+            ],
+            &SymbolNormalisationJson {
+                aliases: BTreeMap::from([("XBT".to_string(), "BTC".to_string())]),
+                exchange_overrides: BTreeMap::from([(
+                    "bitstamp".to_string(),
+                    BTreeMap::from([("RIPPLE".to_string(), "XRP".to_string())]),
+                )]),
+            },
+            &["USD".into()],
+            &[],
+        ).unwrap();
+        let (kraken, bitstamp) = (ExchangeID(0), ExchangeID(1));
+        let (btc, usd, eth, xrp) = (CurrencyID(0), CurrencyID(1), CurrencyID(2), CurrencyID(3));
+
+        assert!(lexicon.has_exchange("kraken"));
+        assert!(!lexicon.has_exchange("binance"));
+        assert!(lexicon.has_currency("BTC"));
+        assert!(lexicon.has_currency("XBT"));
+        assert!(!lexicon.has_currency("RIPPLE"));
+        assert_eq!(lexicon.normalised_currency_id(Some("bitstamp"), "RIPPLE"), Some(xrp));
+        assert!(lexicon.exchange_has_pair(&kraken, &CurrencyIDPair::new(eth, btc)));
+        assert!(!lexicon.exchange_has_pair(&kraken, &CurrencyIDPair::new(btc, eth)));
+        assert!(!lexicon.exchange_has_currency(&bitstamp, &eth));
+        assert_eq!(lexicon.exchanges_with_currency(&btc), vec![kraken, bitstamp]);
+        assert_eq!(lexicon.exchanges_with_currency(&xrp), vec![bitstamp]);
+        assert_eq!(
+            lexicon.exchange_currency_pairs_with(&kraken, &eth),
+            vec![CurrencyIDPair::new(eth, btc), CurrencyIDPair::new(eth, usd)]
+        );
+        assert_eq!(lexicon.common_currencies(&[kraken, bitstamp]), BTreeSet::from([btc, usd]));
+
+        let connectivity = lexicon.transfer_connectivity(&kraken, &bitstamp);
+        assert!(connectivity.is_connected());
+        assert_eq!(connectivity.transferable_currencies, BTreeSet::from([btc]));
+        assert_eq!(connectivity.fiat_currencies, BTreeSet::from([usd]));
+    }
+}
@@ -0,0 +1,334 @@
+use crate::id_types::*;
+use crate::lexicon::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+use string_to_int_mapper::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PairIssue {
+    pub exchange: ExchangeID,
+    pub exchange_name: String,
+    pub pair: CurrencyIDPair,
+    pub pair_name: String,
+}
+
+/// Both `pair` and `inverse` are listed on the exchange. Fixing keeps `pair` and drops `inverse`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InversePairIssue {
+    pub exchange: ExchangeID,
+    pub exchange_name: String,
+    pub pair: CurrencyIDPair,
+    pub inverse: CurrencyIDPair,
+    pub pair_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateName {
+    pub name: String,
+    pub ids: Vec<usize>,
+}
+
+/// Structured result of verifying a lexicon. The lexicon is valid if every list is empty.
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct LexiconVerificationReport {
+    pub same_currency_pairs: Vec<PairIssue>,
+    pub inverse_pairs: Vec<InversePairIssue>,
+    /// Live currencies that aren't used by any exchange pair
+    pub orphan_currencies: Vec<(CurrencyID, String)>,
+    /// Live exchanges that don't have any pairs
+    pub exchanges_without_pairs: Vec<(ExchangeID, String)>,
+    /// Exchange IDs used by the exchange pairs that aren't in the exchanges mapper
+    pub exchange_ids_out_of_range: Vec<ExchangeID>,
+    /// Currency IDs used by the exchange pairs, fiat or stable currencies that aren't in the currencies mapper
+    pub currency_ids_out_of_range: Vec<CurrencyID>,
+    /// Mapper positions and names of the exchanges that the exchange ID type can't address
+    pub exchange_names_out_of_range: Vec<(usize, String)>,
+    /// Mapper positions and names of the currencies that the currency ID type can't address
+    pub currency_names_out_of_range: Vec<(usize, String)>,
+    pub duplicate_exchange_names: Vec<DuplicateName>,
+    pub duplicate_currency_names: Vec<DuplicateName>,
+}
+
+impl LexiconVerificationReport {
+    pub fn is_valid(&self) -> bool {
+        self.same_currency_pairs.is_empty()
+            && self.inverse_pairs.is_empty()
+            && self.orphan_currencies.is_empty()
+            && self.exchanges_without_pairs.is_empty()
+            && self.exchange_ids_out_of_range.is_empty()
+            && self.currency_ids_out_of_range.is_empty()
+            && self.exchange_names_out_of_range.is_empty()
+            && self.currency_names_out_of_range.is_empty()
+            && self.duplicate_exchange_names.is_empty()
+            && self.duplicate_currency_names.is_empty()
+    }
+
+    /// Duplicate names and names out of the ID range can't be fixed without changing the IDs of the lexicon
+    pub fn is_fixable(&self) -> bool {
+        self.duplicate_exchange_names.is_empty()
+            && self.duplicate_currency_names.is_empty()
+            && self.exchange_names_out_of_range.is_empty()
+            && self.currency_names_out_of_range.is_empty()
+    }
+}
+
+impl fmt::Display for LexiconVerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.same_currency_pairs {
+            writeln!(
+                f,
+                "Exchange id '{}' name '{}'\tBad currency pair '{} : {}'\tname '{}'",
+                issue.exchange.0, issue.exchange_name, issue.pair.first.0, issue.pair.second.0, issue.pair_name
+            )?;
+        }
+        for issue in &self.inverse_pairs {
+            writeln!(
+                f,
+                "Exchange id '{}' name '{}'\tInverse currency pairs '{} : {}' and '{} : {}'\tname '{}'",
+                issue.exchange.0,
+                issue.exchange_name,
+                issue.pair.first.0,
+                issue.pair.second.0,
+                issue.inverse.first.0,
+                issue.inverse.second.0,
+                issue.pair_name
+            )?;
+        }
+        for (c, name) in &self.orphan_currencies {
+            writeln!(f, "Orphan currency id '{}' name '{}'", c.0, name)?;
+        }
+        for (e, name) in &self.exchanges_without_pairs {
+            writeln!(f, "Exchange without pairs id '{}' name '{}'", e.0, name)?;
+        }
+        for e in &self.exchange_ids_out_of_range {
+            writeln!(f, "Exchange id '{}' out of range", e.0)?;
+        }
+        for c in &self.currency_ids_out_of_range {
+            writeln!(f, "Currency id '{}' out of range", c.0)?;
+        }
+        for (id, name) in &self.exchange_names_out_of_range {
+            writeln!(f, "Exchange name '{}' at {} out of the exchange ID range", name, id)?;
+        }
+        for (id, name) in &self.currency_names_out_of_range {
+            writeln!(f, "Currency name '{}' at {} out of the currency ID range", name, id)?;
+        }
+        for duplicate in &self.duplicate_exchange_names {
+            writeln!(f, "Duplicate exchange name '{}' ids {:?}", duplicate.name, duplicate.ids)?;
+        }
+        for duplicate in &self.duplicate_currency_names {
+            writeln!(f, "Duplicate currency name '{}' ids {:?}", duplicate.name, duplicate.ids)?;
+        }
+        Ok(())
+    }
+}
+
+fn duplicate_names<'a>(names: impl Iterator<Item = &'a str>) -> Vec<DuplicateName> {
+    let mut ids_by_name: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    names.enumerate().for_each(|(id, name)| ids_by_name.entry(name).or_default().push(id));
+    ids_by_name
+        .into_iter()
+        .filter(|(_, ids)| ids.len() > 1)
+        .map(|(name, ids)| DuplicateName {
+            name: name.to_string(),
+            ids,
+        })
+        .collect()
+}
+
+impl CryptoExchangeLexicon {
+    fn is_live_exchange(&self, e: &ExchangeID) -> bool {
+        self.exchanges.get_key(e).is_some_and(|name| !name.is_tombstoned())
+    }
+
+    fn is_live_currency(&self, c: &CurrencyID) -> bool {
+        self.currencies.get_key(c).is_some_and(|name| !name.is_tombstoned())
+    }
+
+    pub fn verify(&self) -> LexiconVerificationReport {
+        let mut report = LexiconVerificationReport::default();
+        let mut used_currencies: BTreeSet<CurrencyID> = BTreeSet::new();
+        let mut currency_ids_out_of_range: BTreeSet<CurrencyID> = BTreeSet::new();
+        for (exchange, pairs) in self.exchange_currency_pairs.iter() {
+            if self.exchanges.get_key(exchange).is_none() {
+                report.exchange_ids_out_of_range.push(*exchange);
+            }
+            let exchange_name = self.exchange_to_string(exchange).to_string();
+            for pair in pairs {
+                used_currencies.insert(pair.first);
+                used_currencies.insert(pair.second);
+                let pair_issue = || PairIssue {
+                    exchange: *exchange,
+                    exchange_name: exchange_name.clone(),
+                    pair: *pair,
+                    pair_name: self.currency_pair_to_string(pair),
+                };
+                if pair.has_same_currencies() {
+                    report.same_currency_pairs.push(pair_issue());
+                    continue;
+                }
+                // every inverse couple is reported once by its smaller pair
+                let inverse = CurrencyIDPair::new(pair.second, pair.first);
+                if pair < &inverse && pairs.contains(&inverse) {
+                    report.inverse_pairs.push(InversePairIssue {
+                        exchange: *exchange,
+                        exchange_name: exchange_name.clone(),
+                        pair: *pair,
+                        inverse,
+                        pair_name: self.currency_pair_to_string(pair),
+                    });
+                }
+            }
+        }
+        used_currencies
+            .iter()
+            .chain(self.fiat_currencies.iter())
+            .chain(self.stable_currencies.iter())
+            .filter(|c| self.currencies.get_key(c).is_none())
+            .for_each(|c| {
+                currency_ids_out_of_range.insert(*c);
+            });
+        report.currency_ids_out_of_range = currency_ids_out_of_range.into_iter().collect();
+        for (id, name) in self.currencies.iter_with_status().enumerate() {
+            match u16::try_from(id).map(CurrencyID) {
+                Ok(c) if self.is_live_currency(&c) && !used_currencies.contains(&c) => {
+                    report.orphan_currencies.push((c, name.key().to_string()))
+                }
+                Ok(_) => (),
+                Err(_) => report.currency_names_out_of_range.push((id, name.key().to_string())),
+            }
+        }
+        for (id, name) in self.exchanges.iter_with_status().enumerate() {
+            match u8::try_from(id).map(ExchangeID) {
+                Ok(e) if self.is_live_exchange(&e)
+                    && self.exchange_currency_pairs.get(&e).is_none_or(|pairs| pairs.is_empty()) =>
+                {
+                    report.exchanges_without_pairs.push((e, name.key().to_string()))
+                }
+                Ok(_) => (),
+                Err(_) => report.exchange_names_out_of_range.push((id, name.key().to_string())),
+            }
+        }
+        report.duplicate_exchange_names = duplicate_names(self.exchanges.iter_with_status().map(|name| name.key()));
+        report.duplicate_currency_names = duplicate_names(self.currencies.iter_with_status().map(|name| name.key()));
+        report
+    }
+
+    /// Cleans up the fixable issues of `verify`. Same currency pairs, inverse pairs (the larger pair of
+    /// every couple) and pairs with unknown IDs are dropped. Orphan currencies and exchanges without
+    /// pairs are marked as deprecated so the IDs of the lexicon stay stable. The exchange symbols, market info
+    /// and networks of the dropped markets and currencies are removed. Duplicate names are not fixed.
+    pub fn fix(mut self) -> Self {
+        let exchanges = &self.exchanges;
+        let currencies = &self.currencies;
+        self.exchange_currency_pairs.retain(|e, _| exchanges.get_key(e).is_some());
+        self.exchange_currency_pairs.values_mut().for_each(|pairs| {
+            let inverses: BTreeSet<CurrencyIDPair> = pairs
+                .iter()
+                .filter(|pair| {
+                    let inverse = CurrencyIDPair::new(pair.second, pair.first);
+                    pair > &&inverse && pairs.contains(&inverse)
+                })
+                .copied()
+                .collect();
+            pairs.retain(|pair| {
+                !pair.has_same_currencies()
+                    && !inverses.contains(pair)
+                    && currencies.get_key(&pair.first).is_some()
+                    && currencies.get_key(&pair.second).is_some()
+            });
+        });
+        self.exchange_currency_pairs.retain(|_, pairs| !pairs.is_empty());
+
+        let report = self.verify();
+        let mut exchanges = std::mem::replace(&mut self.exchanges, StringToIntMapper::new_reading()).to_editer();
+        report.exchanges_without_pairs.iter().for_each(|(_, name)| exchanges.remove(name));
+        self.exchanges = exchanges.to_reader();
+        let mut currencies = std::mem::replace(&mut self.currencies, StringToIntMapper::new_reading()).to_editer();
+        report.orphan_currencies.iter().for_each(|(_, name)| currencies.remove(name));
+        self.currencies = currencies.to_reader();
+
+        let currencies = &self.currencies;
+        let is_live = |c: &CurrencyID| currencies.get_key(c).is_some_and(|name| !name.is_tombstoned());
+        self.fiat_currencies.retain(is_live);
+        self.stable_currencies.retain(is_live);
+
+        let exchange_symbols = std::mem::take(&mut self.exchange_symbols)
+            .into_iter()
+            .filter(|(ec, _)| self.exchange_has_currency(&ec.exchange, &ec.currency))
+            .collect();
+        self.exchange_symbols = exchange_symbols;
+        let exchange_currency_networks = std::mem::take(&mut self.exchange_currency_networks)
+            .into_iter()
+            .filter(|(ec, _)| self.exchange_has_currency(&ec.exchange, &ec.currency))
+            .collect();
+        self.exchange_currency_networks = exchange_currency_networks;
+        let mut markets = std::mem::take(&mut self.markets);
+        markets.iter_mut().for_each(|(e, markets)| markets.retain(|pair, _| self.exchange_has_pair(e, pair)));
+        markets.retain(|_, markets| !markets.is_empty());
+        self.markets = markets;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_and_fix_test() {
+        let (mut lexicon, _) = CryptoExchangeLexicon::create_from_exchange_symbols_full(
+            &[
+                ExchangeSymbolsJson {
+                    exchange: "kraken".to_string(),
+                    symbols: vec!["BTC/USD".to_string(), "USD/BTC".to_string(), "ETH/USD".to_string()],
+                },
+                ExchangeSymbolsJson {
+                    exchange: "bitstamp".to_string(),
+                    symbols: vec!["BTC/EUR".to_string()],
+                },
+            ],
+            &SymbolNormalisationJson::default(),
+            &[],
+            &[],
+        ).unwrap();
+        let (kraken, bitstamp) = (ExchangeID(0), ExchangeID(1));
+        let (btc, usd, eth, eur) = (CurrencyID(0), CurrencyID(1), CurrencyID(2), CurrencyID(3));
+        lexicon.exchange_currency_pairs.remove(&bitstamp);
+        // generate skips same currency markets, corrupt the lexicon directly
+        let kraken_pairs = lexicon.exchange_currency_pairs.get_mut(&kraken).unwrap();
+        assert!(kraken_pairs.remove(&CurrencyIDPair::new(eth, usd)));
+        kraken_pairs.insert(CurrencyIDPair::new(eth, eth));
+        lexicon.fiat_currencies.insert(CurrencyID(42));
+        lexicon.exchange_symbols.insert(kraken.with_currency(&eth), "XETH".to_string());
+        lexicon.exchange_symbols.insert(kraken.with_currency(&btc), "XXBT".to_string());
+        lexicon.exchange_currency_networks.insert(bitstamp.with_currency(&eur), BTreeSet::from([NetworkID(0)]));
+        for pair in [CurrencyIDPair::new(btc, usd), CurrencyIDPair::new(usd, btc)] {
+            lexicon.markets.entry(kraken).or_default().insert(pair, Default::default());
+        }
+
+        let report = lexicon.verify();
+        assert!(!report.is_valid());
+        assert!(report.is_fixable());
+        assert_eq!(report.same_currency_pairs.len(), 1);
+        assert_eq!(report.same_currency_pairs[0].pair, CurrencyIDPair::new(eth, eth));
+        assert_eq!(report.inverse_pairs.len(), 1);
+        assert_eq!(report.inverse_pairs[0].pair, CurrencyIDPair::new(btc, usd));
+        assert_eq!(report.inverse_pairs[0].inverse, CurrencyIDPair::new(usd, btc));
+        assert_eq!(report.orphan_currencies, vec![(eur, "EUR".to_string())]);
+        assert_eq!(report.exchanges_without_pairs, vec![(bitstamp, "bitstamp".to_string())]);
+        assert_eq!(report.currency_ids_out_of_range, vec![CurrencyID(42)]);
+
+        let lexicon = lexicon.fix();
+        assert!(lexicon.verify().is_valid());
+        assert_eq!(lexicon.exchange_currency_pairs[&kraken].len(), 1);
+        assert!(lexicon.exchange_currency_pairs[&kraken].contains(&CurrencyIDPair::new(btc, usd)));
+        assert_eq!(lexicon.deprecated_exchanges(), &[bitstamp]);
+        assert_eq!(lexicon.deprecated_currencies(), &[eth, eur]);
+        assert!(lexicon.fiat_currencies.is_empty());
+        assert_eq!(lexicon.exchange_symbols.keys().collect::<Vec<_>>(), vec![&kraken.with_currency(&btc)]);
+        assert!(lexicon.exchange_currency_networks.is_empty());
+        assert_eq!(lexicon.markets[&kraken].keys().collect::<Vec<_>>(), vec![&CurrencyIDPair::new(btc, usd)]);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::id_types::*;
+use crate::lexicon::*;
+use crate::render::TableRow;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CurrencyView {
+    pub id: CurrencyID,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExchangeView {
+    pub id: ExchangeID,
+    pub name: String,
+    pub pairs: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExchangePairView {
+    pub exchange_id: ExchangeID,
+    pub exchange: String,
+    pub base_id: CurrencyID,
+    pub quote_id: CurrencyID,
+    pub base: String,
+    pub quote: String,
+}
+
+impl TableRow for CurrencyView {
+    fn headers() -> &'static [&'static str] {
+        &["id", "name"]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.id.0.to_string(), self.name.clone()]
+    }
+}
+
+impl TableRow for ExchangeView {
+    fn headers() -> &'static [&'static str] {
+        &["id", "name", "pairs"]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.id.0.to_string(), self.name.clone(), self.pairs.to_string()]
+    }
+}
+
+impl TableRow for ExchangePairView {
+    fn headers() -> &'static [&'static str] {
+        &["exchange_id", "exchange", "base_id", "quote_id", "base", "quote"]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.exchange_id.0.to_string(),
+            self.exchange.clone(),
+            self.base_id.0.to_string(),
+            self.quote_id.0.to_string(),
+            self.base.clone(),
+            self.quote.clone(),
+        ]
+    }
+}
+
+impl CryptoExchangeLexicon {
+    fn currency_view(&self, c: &CurrencyID) -> CurrencyView {
+        CurrencyView {
+            id: *c,
+            name: self.currency_to_string(c).to_string(),
+        }
+    }
+
+    fn exchange_pair_view(&self, e: &ExchangeID, pair: &CurrencyIDPair) -> ExchangePairView {
+        ExchangePairView {
+            exchange_id: *e,
+            exchange: self.exchange_to_string(e).to_string(),
+            base_id: pair.first,
+            quote_id: pair.second,
+            base: self.currency_to_string(&pair.first).to_string(),
+            quote: self.currency_to_string(&pair.second).to_string(),
+        }
+    }
+
+    /// All currencies in ID order including the deprecated ones. Fails if the currencies mapper holds
+    /// more currencies than the currency ID type can address.
+    pub fn currencies_view(&self) -> Result<Vec<CurrencyView>, CryptoTypesError> {
+        (0..self.currencies.iter_with_status().count())
+            .map(|id| {
+                let c = u16::try_from(id).map_err(|_| CryptoTypesError::CurrencyIdOutOfRange(id))?;
+                Ok(self.currency_view(&CurrencyID(c)))
+            })
+            .collect()
+    }
+
+    pub fn fiat_currencies_view(&self) -> Vec<CurrencyView> {
+        self.fiat_currencies.iter().map(|c| self.currency_view(c)).collect()
+    }
+
+    pub fn stable_currencies_view(&self) -> Vec<CurrencyView> {
+        self.stable_currencies.iter().map(|c| self.currency_view(c)).collect()
+    }
+
+    /// Exchanges that have currency pairs
+    pub fn exchanges_view(&self) -> Vec<ExchangeView> {
+        self.exchange_currency_pairs
+            .iter()
+            .map(|(e, pairs)| ExchangeView {
+                id: *e,
+                name: self.exchange_to_string(e).to_string(),
+                pairs: pairs.len(),
+            })
+            .collect()
+    }
+
+    pub fn all_exchange_pairs_view(&self) -> Vec<ExchangePairView> {
+        self.exchange_currency_pairs
+            .iter()
+            .flat_map(|(e, pairs)| pairs.iter().map(move |pair| self.exchange_pair_view(e, pair)))
+            .collect()
+    }
+
+    /// Exchange names to 'BASE-QUOTE' pair names. The JSON shape of the earlier `print-all-exchanges-pairs -j`.
+    pub fn exchange_pair_names(&self) -> BTreeMap<String, Vec<String>> {
+        self.exchange_currency_pairs
+            .iter()
+            .map(|(e, pairs)| {
+                let pairs = pairs
+                    .iter()
+                    .map(|pair| self.currency_pair_to_string(pair).replace(" : ", "-"))
+                    .collect();
+                (self.exchange_to_string(e).to_string(), pairs)
+            })
+            .collect()
+    }
+
+    /// Empty for the known exchanges without pairs
+    pub fn exchange_pairs_view(&self, e: &ExchangeID) -> Result<Vec<ExchangePairView>, CryptoTypesError> {
+        if self.exchanges.get_key(e).is_none() {
+            return Err(CryptoTypesError::UnknownExchange(*e));
+        }
+        Ok(self
+            .exchange_currency_pairs
+            .get(e)
+            .map(|pairs| pairs.iter().map(|pair| self.exchange_pair_view(e, pair)).collect())
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::*;
+
+    #[test]
+    fn exchange_pairs_view_test() {
+        let mut lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&[ExchangeSymbolsJson {
+            exchange: "kraken".to_string(),
+            symbols: vec!["BTC/USD".to_string(), "ETH/BTC".to_string()],
+        }]).unwrap();
+        let pairs = lexicon.exchange_pairs_view(&ExchangeID(0)).unwrap();
+        assert_eq!(pairs, lexicon.all_exchange_pairs_view());
+        assert_eq!(
+            render(&pairs, OutputFormat::Csv).unwrap(),
+            "exchange_id,exchange,base_id,quote_id,base,quote\n0,kraken,0,1,BTC,USD\n0,kraken,2,0,ETH,BTC\n"
+        );
+        assert!(matches!(
+            lexicon.exchange_pairs_view(&ExchangeID(1)),
+            Err(CryptoTypesError::UnknownExchange(ExchangeID(1)))
+        ));
+        assert_eq!(lexicon.exchanges_view()[0].pairs, 2);
+        assert_eq!(
+            serde_json::to_string(&lexicon.exchange_pair_names()).unwrap(),
+            r#"{"kraken":["BTC-USD","ETH-BTC"]}"#
+        );
+
+        lexicon.exchange_currency_pairs.clear();
+        assert_eq!(lexicon.exchange_pairs_view(&ExchangeID(0)).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn fiat_and_stable_currencies_view_test() {
+        let mut lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&[ExchangeSymbolsJson {
+            exchange: "kraken".to_string(),
+            symbols: vec!["BTC/USD".to_string(), "BTC/USDT".to_string()],
+        }]).unwrap();
+        assert!(lexicon.set_fiat_currencies(&["USD".into()]).is_empty());
+        assert!(lexicon.set_stable_currencies(&["USDT".into()]).is_empty());
+        let usd = *lexicon.currencies.get_id("USD").unwrap();
+        let usdt = *lexicon.currencies.get_id("USDT").unwrap();
+        assert_eq!(lexicon.fiat_currencies_view(), vec![CurrencyView { id: usd, name: "USD".to_string() }]);
+        assert_eq!(lexicon.stable_currencies_view(), vec![CurrencyView { id: usdt, name: "USDT".to_string() }]);
+        assert_eq!(
+            render(&lexicon.stable_currencies_view(), OutputFormat::Csv).unwrap(),
+            format!("id,name\n{},USDT\n", usdt.0)
+        );
+    }
+}
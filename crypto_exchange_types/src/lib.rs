@@ -1,13 +1,43 @@
 mod arbitrage_paths;
+mod ccxt_markets;
+mod compact_path;
 mod exchange_operations_and_filters;
+mod execution_plan;
 mod id_types;
 mod lexicon;
+mod lexicon_diff;
+mod lexicon_names;
+mod lexicon_networks;
+mod lexicon_query;
+mod lexicon_verification;
+mod lexicon_views;
 mod order_book;
+mod portfolio;
 mod price_amounts;
+mod render;
+mod simulated_exchange;
+mod valuation;
 
 #[cfg(test)]
 mod tests;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 
 pub use arbitrage_paths::*;
+pub use ccxt_markets::*;
+pub use compact_path::*;
+pub use execution_plan::*;
 pub use id_types::*;
 pub use lexicon::*;
+pub use lexicon_diff::*;
+pub use lexicon_names::*;
+pub use lexicon_networks::*;
+pub use lexicon_query::*;
+pub use lexicon_verification::*;
+pub use lexicon_views::*;
+pub use order_book::*;
+pub use portfolio::*;
+pub use price_amounts::*;
+pub use render::*;
+pub use simulated_exchange::*;
+pub use valuation::*;
@@ -35,6 +35,27 @@ impl ExchangeMarketOrderBook {
     pub fn sort_asks(&mut self) {
         self.asks.sort_by(|a, b| a.partial_cmp(&b).unwrap());
     }
+
+    /// Highest bid price. The bids don't have to be sorted.
+    pub fn best_bid(&self) -> Option<BidPriceAmount> {
+        self.bids.iter().copied().reduce(|a, b| if b.price.0 > a.price.0 { b } else { a })
+    }
+
+    /// Lowest ask price. The asks don't have to be sorted.
+    pub fn best_ask(&self) -> Option<AskPriceAmount> {
+        self.asks.iter().copied().reduce(|a, b| if b.price.0 < a.price.0 { b } else { a })
+    }
+
+    /// Mid price between the best bid and best ask. The price is expressed in the second/quote currency
+    /// for one unit of the first/base currency. If one side of the book is empty the other side is used.
+    pub fn mid_price(&self) -> Option<f64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some((bid.price.0 + ask.price.0) / 2.0),
+            (Some(bid), None) => Some(bid.price.0),
+            (None, Some(ask)) => Some(ask.price.0),
+            (None, None) => None,
+        }
+    }
 }
 
 /// This is the ported code from Go and this could be flattened to a ExchangeMarketKey
@@ -97,26 +118,27 @@ pub struct ExchangeMarketKey {
 }
 
 impl ExchangeMarketKey {
+    /// Fails if both currencies are the same, such a market can't exist
     #[inline]
-    pub fn create(exchange: ExchangeID, c1: CurrencyID, c2: CurrencyID) -> Self {
+    pub fn create(exchange: ExchangeID, c1: CurrencyID, c2: CurrencyID) -> Result<Self, CryptoTypesError> {
         if c1.0 < c2.0 {
-            ExchangeMarketKey {
+            Ok(ExchangeMarketKey {
                 exchange: exchange,
                 sorted_currency_pair_less: c1,
                 sorted_currency_pair_greater: c2,
-            }
+            })
         } else if c1.0 > c2.0 {
-            ExchangeMarketKey {
+            Ok(ExchangeMarketKey {
                 exchange: exchange,
                 sorted_currency_pair_less: c2,
                 sorted_currency_pair_greater: c1,
-            }
+            })
         } else {
-            panic!("Invalid ExchangeMarketKey construction!!! Problem c1 == c2!");
+            Err(CryptoTypesError::InvalidMarket(exchange, CurrencyIDPair::new(c1, c2)))
         }
     }
     #[inline]
-    pub fn create_from_pair(exchange: ExchangeID, pair: CurrencyIDPair) -> Self {
+    pub fn create_from_pair(exchange: ExchangeID, pair: CurrencyIDPair) -> Result<Self, CryptoTypesError> {
         Self::create(exchange, pair.first, pair.second)
     }
 }
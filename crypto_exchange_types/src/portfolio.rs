@@ -0,0 +1,321 @@
+use crate::arbitrage_paths::*;
+use crate::id_types::*;
+use crate::price_amounts::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Holding is the amount of a single currency on a single exchange split by what we can do with it.
+#[derive(Default, Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct Holding {
+    /// Free to be used for transactions and transfers
+    pub available: CurrencyAmount,
+    /// Locked for pending operations e.g. a transfer that is waiting to be withdrawn
+    pub reserved: CurrencyAmount,
+    /// Withdrawn from another exchange and not yet deposited on this exchange
+    pub in_transit: CurrencyAmount,
+}
+
+impl Holding {
+    pub fn total(&self) -> CurrencyAmount {
+        CurrencyAmount(self.available.0 + self.reserved.0 + self.in_transit.0)
+    }
+}
+
+/// Portfolio holds our balances/inventory over multiple exchanges.
+/// The BalanceExchangeCurrencyInfo (ExchangeIDCurrencyIDPair) only identifies a balance,
+/// the Portfolio maps it to the actual amounts we hold.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Portfolio {
+    holdings: BTreeMap<ExchangeIDCurrencyIDPair, Holding>,
+}
+
+/// A negative amount would turn a debit into a credit and a NaN amount passes every comparison
+fn check_amount(key: ExchangeIDCurrencyIDPair, amount: CurrencyAmount) -> Result<(), CryptoTypesError> {
+    if amount.0 < 0.0 || amount.0.is_nan() {
+        return Err(CryptoTypesError::InvalidPortfolioAmount(key, amount.0));
+    }
+    Ok(())
+}
+
+fn credit(
+    key: ExchangeIDCurrencyIDPair,
+    to: &mut CurrencyAmount,
+    amount: CurrencyAmount,
+) -> Result<(), CryptoTypesError> {
+    check_amount(key, amount)?;
+    to.0 += amount.0;
+    Ok(())
+}
+
+fn debit(
+    key: ExchangeIDCurrencyIDPair,
+    from: &mut CurrencyAmount,
+    amount: CurrencyAmount,
+) -> Result<(), CryptoTypesError> {
+    check_amount(key, amount)?;
+    if from.0 < amount.0 {
+        return Err(CryptoTypesError::InsufficientFunds(key, amount.0, from.0));
+    }
+    from.0 -= amount.0;
+    Ok(())
+}
+
+impl Portfolio {
+    pub fn new() -> Self {
+        Portfolio {
+            holdings: BTreeMap::new(),
+        }
+    }
+
+    pub fn holding(&self, key: &ExchangeIDCurrencyIDPair) -> Holding {
+        self.holdings.get(key).copied().unwrap_or_default()
+    }
+
+    pub fn available(&self, key: &ExchangeIDCurrencyIDPair) -> CurrencyAmount {
+        self.holding(key).available
+    }
+
+    pub fn iter(&self) -> std::collections::btree_map::Iter<'_, ExchangeIDCurrencyIDPair, Holding> {
+        self.holdings.iter()
+    }
+
+    fn holding_mut(&mut self, key: ExchangeIDCurrencyIDPair) -> &mut Holding {
+        self.holdings.entry(key).or_default()
+    }
+
+    /// Adds the amount to the available balance
+    pub fn deposit(&mut self, key: ExchangeIDCurrencyIDPair, amount: CurrencyAmount) -> Result<(), CryptoTypesError> {
+        credit(key, &mut self.holding_mut(key).available, amount)
+    }
+
+    /// Removes the amount from the available balance
+    pub fn withdraw(&mut self, key: ExchangeIDCurrencyIDPair, amount: CurrencyAmount) -> Result<(), CryptoTypesError> {
+        debit(key, &mut self.holding_mut(key).available, amount)
+    }
+
+    /// Moves the amount from available to reserved so it can't be used by other operations
+    pub fn reserve(&mut self, key: ExchangeIDCurrencyIDPair, amount: CurrencyAmount) -> Result<(), CryptoTypesError> {
+        let holding = self.holding_mut(key);
+        debit(key, &mut holding.available, amount)?;
+        credit(key, &mut holding.reserved, amount)
+    }
+
+    /// Moves the amount from reserved back to available
+    pub fn release(&mut self, key: ExchangeIDCurrencyIDPair, amount: CurrencyAmount) -> Result<(), CryptoTypesError> {
+        let holding = self.holding_mut(key);
+        debit(key, &mut holding.reserved, amount)?;
+        credit(key, &mut holding.available, amount)
+    }
+
+    /// Reserves the funds on the withdraw exchange for a pending transfer
+    pub fn reserve_transfer(
+        &mut self,
+        tr: &TransferExchangeToExchangeCurrencyInfo,
+        amount: CurrencyAmount,
+    ) -> Result<(), CryptoTypesError> {
+        self.reserve(tr.withdraw_exchange.with_currency(&tr.currency), amount)
+    }
+
+    /// Withdraws the reserved funds of a pending transfer. The received amount (without the transfer fees)
+    /// is in transit on the deposit exchange until the transfer is completed.
+    pub fn start_reserved_transfer(
+        &mut self,
+        tr: &TransferExchangeToExchangeCurrencyInfo,
+        sent: CurrencyAmount,
+        received: CurrencyAmount,
+    ) -> Result<(), CryptoTypesError> {
+        let withdraw_key = tr.withdraw_exchange.with_currency(&tr.currency);
+        let deposit_key = tr.deposit_exchange.with_currency(&tr.currency);
+        check_amount(deposit_key, received)?;
+        debit(withdraw_key, &mut self.holding_mut(withdraw_key).reserved, sent)?;
+        credit(deposit_key, &mut self.holding_mut(deposit_key).in_transit, received)
+    }
+
+    /// Moves the transfered funds from in transit to available on the deposit exchange
+    pub fn complete_transfer(
+        &mut self,
+        tr: &TransferExchangeToExchangeCurrencyInfo,
+        amount: CurrencyAmount,
+    ) -> Result<(), CryptoTypesError> {
+        let deposit_key = tr.deposit_exchange.with_currency(&tr.currency);
+        let holding = self.holding_mut(deposit_key);
+        debit(deposit_key, &mut holding.in_transit, amount)?;
+        credit(deposit_key, &mut holding.available, amount)
+    }
+
+    /// Applies a single executed operation where `amount_in` is what we spent and `amount_out` what we received.
+    /// Transfers are left in transit on the deposit exchange until `complete_transfer` is called.
+    pub fn apply_operation(
+        &mut self,
+        op: &ExchangeOperationType,
+        amount_in: CurrencyAmount,
+        amount_out: CurrencyAmount,
+    ) -> Result<(), CryptoTypesError> {
+        match op {
+            ExchangeOperationType::Balance(_) => Ok(()),
+            ExchangeOperationType::Transaction(tx) => {
+                let deposit_key = tx.exchange.with_currency(&tx.currency_to);
+                check_amount(deposit_key, amount_out)?;
+                self.withdraw(tx.exchange.with_currency(&tx.currency_from), amount_in)?;
+                self.deposit(deposit_key, amount_out)
+            }
+            ExchangeOperationType::Transfer(tr) => {
+                let deposit_key = tr.deposit_exchange.with_currency(&tr.currency);
+                check_amount(deposit_key, amount_out)?;
+                self.withdraw(tr.withdraw_exchange.with_currency(&tr.currency), amount_in)?;
+                credit(deposit_key, &mut self.holding_mut(deposit_key).in_transit, amount_out)
+            }
+        }
+    }
+
+    /// Applies an executed path step by step. The `balance_amounts` are the executed amounts
+    /// for every balance node in the path (the first one is the start amount).
+    /// The portfolio is left unchanged if any of the steps fail.
+    pub fn apply_path(&mut self, path: &ArbitragePath, balance_amounts: &[CurrencyAmount]) -> Result<(), CryptoTypesError> {
+        let steps = path.operation_steps()?;
+        let balance_count = if path.0.is_empty() { 0 } else { steps.len() + 1 };
+        if balance_count != balance_amounts.len() {
+            return Err(CryptoTypesError::PathAmountsMismatch(balance_count, balance_amounts.len()));
+        }
+        let mut applied = self.clone();
+        for (i, (_, op, _)) in steps.iter().enumerate() {
+            applied.apply_operation(op, balance_amounts[i], balance_amounts[i + 1])?;
+        }
+        *self = applied;
+        Ok(())
+    }
+
+    /// Returns the targets we can start from with our current available balances
+    pub fn startable_targets<'a, I>(&self, targets: I, min_available: CurrencyAmount) -> Vec<Target>
+    where
+        I: IntoIterator<Item = &'a Target>,
+    {
+        targets
+            .into_iter()
+            .filter(|target| {
+                let available = self.available(target);
+                available.0 > 0.0 && available.0 >= min_available.0
+            })
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(e: u8, c: u16) -> ExchangeIDCurrencyIDPair {
+        ExchangeID(e).with_currency(&CurrencyID(c))
+    }
+
+    fn transfer_path() -> ArbitragePath {
+        // BTC(0) on e0 -> ETH(1) on e0 -> transfer ETH to e1 -> BTC on e1
+        let mut path = ArbitragePath::new();
+        path.0.push_back(key(0, 0).into());
+        path.0.push_back(
+            TransactionExchangeCurrenciesBuySellInfo {
+                exchange: ExchangeID(0),
+                side: TransactionSide::SELL,
+                currency_from: CurrencyID(0),
+                currency_to: CurrencyID(1),
+            }
+            .into(),
+        );
+        path.0.push_back(key(0, 1).into());
+        path.0.push_back(
+            TransferExchangeToExchangeCurrencyInfo {
+                withdraw_exchange: ExchangeID(0),
+                deposit_exchange: ExchangeID(1),
+                currency: CurrencyID(1),
+                network: None,
+            }
+            .into(),
+        );
+        path.0.push_back(key(1, 1).into());
+        path
+    }
+
+    #[test]
+    fn reserve_and_release_test() {
+        let mut portfolio = Portfolio::new();
+        portfolio.deposit(key(0, 0), CurrencyAmount(10.0)).unwrap();
+        portfolio.reserve(key(0, 0), CurrencyAmount(4.0)).unwrap();
+        assert_eq!(portfolio.available(&key(0, 0)), CurrencyAmount(6.0));
+        assert_eq!(portfolio.holding(&key(0, 0)).reserved, CurrencyAmount(4.0));
+        assert!(portfolio.reserve(key(0, 0), CurrencyAmount(7.0)).is_err());
+        portfolio.release(key(0, 0), CurrencyAmount(4.0)).unwrap();
+        assert_eq!(portfolio.available(&key(0, 0)), CurrencyAmount(10.0));
+        assert_eq!(portfolio.holding(&key(0, 0)).total(), CurrencyAmount(10.0));
+    }
+
+    #[test]
+    fn invalid_amounts_test() {
+        let mut portfolio = Portfolio::new();
+        portfolio.deposit(key(0, 0), CurrencyAmount(1.0)).unwrap();
+        assert!(matches!(
+            portfolio.deposit(key(0, 0), CurrencyAmount(f64::NAN)),
+            Err(CryptoTypesError::InvalidPortfolioAmount(_, _))
+        ));
+        // a negative withdraw would add funds
+        assert!(matches!(
+            portfolio.withdraw(key(0, 0), CurrencyAmount(-1.0)),
+            Err(CryptoTypesError::InvalidPortfolioAmount(_, _))
+        ));
+        assert!(portfolio.reserve(key(0, 0), CurrencyAmount(f64::NAN)).is_err());
+        assert!(portfolio.release(key(0, 0), CurrencyAmount(-1.0)).is_err());
+        let amounts = [CurrencyAmount(1.0), CurrencyAmount(f64::NAN), CurrencyAmount(1.0)];
+        assert!(portfolio.apply_path(&transfer_path(), &amounts).is_err());
+        assert_eq!(portfolio.holding(&key(0, 0)).total(), CurrencyAmount(1.0));
+        assert_eq!(portfolio.available(&key(0, 1)), CurrencyAmount(0.0));
+    }
+
+    #[test]
+    fn apply_path_test() {
+        let mut portfolio = Portfolio::new();
+        portfolio.deposit(key(0, 0), CurrencyAmount(2.0)).unwrap();
+        let path = transfer_path();
+        let amounts = [CurrencyAmount(1.0), CurrencyAmount(30.0), CurrencyAmount(29.5)];
+        portfolio.apply_path(&path, &amounts).unwrap();
+        assert_eq!(portfolio.available(&key(0, 0)), CurrencyAmount(1.0));
+        assert_eq!(portfolio.available(&key(0, 1)), CurrencyAmount(0.0));
+        assert_eq!(portfolio.holding(&key(1, 1)).in_transit, CurrencyAmount(29.5));
+
+        let tr = TransferExchangeToExchangeCurrencyInfo {
+            withdraw_exchange: ExchangeID(0),
+            deposit_exchange: ExchangeID(1),
+            currency: CurrencyID(1),
+            network: None,
+        };
+        portfolio.complete_transfer(&tr, CurrencyAmount(29.5)).unwrap();
+        assert_eq!(portfolio.available(&key(1, 1)), CurrencyAmount(29.5));
+    }
+
+    #[test]
+    fn apply_path_is_atomic_test() {
+        let mut portfolio = Portfolio::new();
+        portfolio.deposit(key(0, 0), CurrencyAmount(1.0)).unwrap();
+        let mut path = transfer_path();
+        // the transfer doesn't deposit to the last balance so the second step fails
+        path.0.pop_back();
+        path.0.push_back(key(1, 0).into());
+        let amounts = [CurrencyAmount(1.0), CurrencyAmount(30.0), CurrencyAmount(29.5)];
+        assert!(matches!(
+            portfolio.apply_path(&path, &amounts),
+            Err(CryptoTypesError::DisconnectedPath(3, _))
+        ));
+        assert_eq!(portfolio.available(&key(0, 0)), CurrencyAmount(1.0));
+        assert_eq!(portfolio.available(&key(0, 1)), CurrencyAmount(0.0));
+        assert!(portfolio.apply_path(&path, &amounts[..2]).is_err());
+    }
+
+    #[test]
+    fn startable_targets_test() {
+        let mut portfolio = Portfolio::new();
+        portfolio.deposit(key(0, 0), CurrencyAmount(1.0)).unwrap();
+        portfolio.deposit(key(1, 0), CurrencyAmount(0.01)).unwrap();
+        let targets = [key(0, 0), key(1, 0), key(2, 0)];
+        assert_eq!(portfolio.startable_targets(&targets, CurrencyAmount(0.0)), vec![key(0, 0), key(1, 0)]);
+        assert_eq!(portfolio.startable_targets(&targets, CurrencyAmount(0.1)), vec![key(0, 0)]);
+    }
+}
@@ -1,7 +1,8 @@
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
 /// Currency amount is for indicating a
-#[derive(PartialEq, PartialOrd, Copy, Clone)]
+#[derive(Default, Debug, PartialEq, PartialOrd, Copy, Clone, Serialize, Deserialize)]
 pub struct CurrencyAmount(pub f64);
 
 /// Price amount for a given BUY/SELL side
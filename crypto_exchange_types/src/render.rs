@@ -0,0 +1,135 @@
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned columns with a header
+    #[default]
+    Table,
+    Json,
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            _ => Err(format!("Unknown output format '{}'. Expected table, json, csv or ndjson", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Ndjson => "ndjson",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// View row that can be rendered as a table or CSV line. JSON formats use the serde representation.
+pub trait TableRow {
+    fn headers() -> &'static [&'static str];
+    fn cells(&self) -> Vec<String>;
+}
+
+fn csv_cell(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    rows.iter().for_each(|row| {
+        row.iter().zip(widths.iter_mut()).for_each(|(cell, width)| *width = (*width).max(cell.chars().count()));
+    });
+    let line = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        line.join("  ").trim_end().to_string() + "\n"
+    };
+    let separators: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    let mut table = line(headers.to_vec());
+    table += &line(separators.iter().map(|separator| separator.as_str()).collect());
+    rows.iter().for_each(|row| table += &line(row.iter().map(|cell| cell.as_str()).collect()));
+    table
+}
+
+/// Renders the view rows in the given format. The output of every format ends with a new line.
+pub fn render<T: Serialize + TableRow>(rows: &[T], format: OutputFormat) -> Result<String, serde_json::Error> {
+    let rendered = match format {
+        OutputFormat::Table => render_table(T::headers(), &rows.iter().map(|row| row.cells()).collect::<Vec<_>>()),
+        OutputFormat::Json => serde_json::to_string_pretty(rows)? + "\n",
+        OutputFormat::Csv => {
+            let mut csv = T::headers().join(",") + "\n";
+            rows.iter().for_each(|row| {
+                let cells: Vec<String> = row.cells().iter().map(|cell| csv_cell(cell)).collect();
+                csv += &(cells.join(",") + "\n");
+            });
+            csv
+        }
+        OutputFormat::Ndjson => {
+            let mut ndjson = String::new();
+            for row in rows {
+                ndjson += &(serde_json::to_string(row)? + "\n");
+            }
+            ndjson
+        }
+    };
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Row {
+        id: u8,
+        name: &'static str,
+    }
+
+    impl TableRow for Row {
+        fn headers() -> &'static [&'static str] {
+            &["id", "name"]
+        }
+
+        fn cells(&self) -> Vec<String> {
+            vec![self.id.to_string(), self.name.to_string()]
+        }
+    }
+
+    #[test]
+    fn render_formats_test() {
+        let rows = [Row { id: 0, name: "kraken" }, Row { id: 10, name: "a, \"b\"" }];
+        assert_eq!(
+            render(&rows, OutputFormat::Table).unwrap(),
+            "id  name\n--  ------\n0   kraken\n10  a, \"b\"\n"
+        );
+        assert_eq!(render(&rows, OutputFormat::Csv).unwrap(), "id,name\n0,kraken\n10,\"a, \"\"b\"\"\"\n");
+        assert_eq!(
+            render(&rows, OutputFormat::Ndjson).unwrap(),
+            "{\"id\":0,\"name\":\"kraken\"}\n{\"id\":10,\"name\":\"a, \\\"b\\\"\"}\n"
+        );
+        assert_eq!("NDJSON".parse::<OutputFormat>(), Ok(OutputFormat::Ndjson));
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
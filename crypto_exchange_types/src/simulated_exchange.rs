@@ -0,0 +1,631 @@
+use crate::execution_plan::*;
+use crate::id_types::*;
+use crate::order_book::*;
+use crate::portfolio::*;
+use crate::price_amounts::*;
+use std::collections::BTreeMap;
+
+/// Maker and taker fees as fractions e.g. 0.001 is 0.1%.
+/// The fee is taken from the received currency.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SimulatedFees {
+    pub maker: f64,
+    pub taker: f64,
+}
+
+/// Withdrawal settings of a currency on an exchange. The fee is a fixed amount of the withdrawn currency
+/// and the delay is the number of simulated milliseconds until the funds are deposited.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SimulatedWithdrawal {
+    pub fee: CurrencyAmount,
+    pub delay_ms: u64,
+}
+
+/// An executed (part of an) order. Quantity is in the base/first currency and price in the quote/second currency.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Fill {
+    pub order_id: u64,
+    pub exchange: ExchangeID,
+    pub pair: CurrencyIDPair,
+    pub side: OrderSide,
+    pub price: f64,
+    pub quantity: f64,
+    /// Fee paid in the received currency
+    pub fee: CurrencyAmount,
+    pub is_maker: bool,
+    pub timestamp_ms: u64,
+}
+
+impl Fill {
+    /// Received amount after the fees
+    pub fn received(&self) -> CurrencyAmount {
+        match self.side {
+            OrderSide::Buy => CurrencyAmount(self.quantity - self.fee.0),
+            OrderSide::Sell => CurrencyAmount(self.quantity * self.price - self.fee.0),
+        }
+    }
+}
+
+/// Limit order waiting in the simulated book for the market to cross its price
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct RestingOrder {
+    pub order_id: u64,
+    pub exchange: ExchangeID,
+    pub pair: CurrencyIDPair,
+    pub side: OrderSide,
+    pub price: f64,
+    pub remaining: f64,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PendingTransfer {
+    pub transfer: TransferExchangeToExchangeCurrencyInfo,
+    pub received: CurrencyAmount,
+    pub arrival_ms: u64,
+}
+
+/// In-process simulated exchange(s) used for testing the execution logic without live venues.
+/// Orders are matched against the order book levels (consuming their liquidity) and the balances
+/// are kept in a Portfolio. Time only moves forward with `advance_time`.
+pub struct SimulatedExchange {
+    books: BTreeMap<(ExchangeID, CurrencyIDPair), ExchangeMarketOrderBook>,
+    fees: BTreeMap<ExchangeID, SimulatedFees>,
+    default_fees: SimulatedFees,
+    withdrawals: BTreeMap<ExchangeIDCurrencyIDPair, SimulatedWithdrawal>,
+    default_withdrawal: SimulatedWithdrawal,
+    portfolio: Portfolio,
+    resting_orders: Vec<RestingOrder>,
+    pending_transfers: Vec<PendingTransfer>,
+    fills: Vec<Fill>,
+    next_order_id: u64,
+    now_ms: u64,
+}
+
+const EPSILON: f64 = 1e-12;
+
+impl SimulatedExchange {
+    pub fn new(default_fees: SimulatedFees, default_withdrawal: SimulatedWithdrawal) -> Self {
+        SimulatedExchange {
+            books: BTreeMap::new(),
+            fees: BTreeMap::new(),
+            default_fees,
+            withdrawals: BTreeMap::new(),
+            default_withdrawal,
+            portfolio: Portfolio::new(),
+            resting_orders: Vec::new(),
+            pending_transfers: Vec::new(),
+            fills: Vec::new(),
+            next_order_id: 0,
+            now_ms: 0,
+        }
+    }
+
+    pub fn set_fees(&mut self, exchange: ExchangeID, fees: SimulatedFees) {
+        self.fees.insert(exchange, fees);
+    }
+
+    pub fn set_withdrawal(&mut self, key: ExchangeIDCurrencyIDPair, withdrawal: SimulatedWithdrawal) {
+        self.withdrawals.insert(key, withdrawal);
+    }
+
+    /// Replaces the market order book. Resting orders crossed by the new book are filled as maker orders.
+    /// Fails if the reserved funds of a filled resting order are missing from the portfolio.
+    pub fn set_order_book(&mut self, mut book: ExchangeMarketOrderBook) -> Result<(), CryptoTypesError> {
+        book.sort_orderbook();
+        self.books.insert((book.exchange, book.pair), book);
+        self.match_resting_orders()
+    }
+
+    pub fn order_book(&self, exchange: ExchangeID, pair: CurrencyIDPair) -> Option<&ExchangeMarketOrderBook> {
+        self.books.get(&(exchange, pair))
+    }
+
+    pub fn deposit(&mut self, key: ExchangeIDCurrencyIDPair, amount: CurrencyAmount) -> Result<(), CryptoTypesError> {
+        self.portfolio.deposit(key, amount)
+    }
+
+    pub fn portfolio(&self) -> &Portfolio {
+        &self.portfolio
+    }
+
+    pub fn fills(&self) -> &[Fill] {
+        &self.fills
+    }
+
+    pub fn resting_orders(&self) -> &[RestingOrder] {
+        &self.resting_orders
+    }
+
+    pub fn pending_transfers(&self) -> &[PendingTransfer] {
+        &self.pending_transfers
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        self.now_ms
+    }
+
+    fn fees(&self, exchange: ExchangeID) -> SimulatedFees {
+        self.fees.get(&exchange).copied().unwrap_or(self.default_fees)
+    }
+
+    fn withdrawal(&self, key: &ExchangeIDCurrencyIDPair) -> SimulatedWithdrawal {
+        self.withdrawals.get(key).copied().unwrap_or(self.default_withdrawal)
+    }
+
+    /// Places a market (`limit_price` is None) or limit order. The part of a limit order that can't be matched
+    /// against the book rests until the book crosses its price. Returns the order ID and the taker fills.
+    /// Fails for quantities that aren't positive.
+    pub fn place_order(
+        &mut self,
+        exchange: ExchangeID,
+        pair: CurrencyIDPair,
+        side: OrderSide,
+        quantity: f64,
+        limit_price: Option<f64>,
+    ) -> Result<(u64, Vec<Fill>), CryptoTypesError> {
+        // a NaN quantity never runs out and would take every crossing level
+        if quantity <= 0.0 || quantity.is_nan() {
+            return Err(CryptoTypesError::InvalidOrderQuantity(quantity));
+        }
+        let taker_fee = self.fees(exchange).taker;
+        let book = self
+            .books
+            .get_mut(&(exchange, pair))
+            .ok_or(CryptoTypesError::UnknownMarket(exchange, pair))?;
+        let crosses = |price: f64| match (side, limit_price) {
+            (_, None) => true,
+            (OrderSide::Buy, Some(limit)) => price <= limit,
+            (OrderSide::Sell, Some(limit)) => price >= limit,
+        };
+        // (level index, price, quantity) taken from the book
+        let book_levels: Vec<(f64, f64)> = match side {
+            OrderSide::Buy => book.asks.iter().map(|a| (a.price.0, a.amount.0)).collect(),
+            OrderSide::Sell => book.bids.iter().map(|b| (b.price.0, b.amount.0)).collect(),
+        };
+        let levels: Vec<(usize, f64, f64)> = book_levels
+            .into_iter()
+            .enumerate()
+            .take_while(|(_, (price, _))| crosses(*price))
+            .scan(quantity, |left, (i, (price, amount))| {
+                if *left <= EPSILON {
+                    return None;
+                }
+                let taken = amount.min(*left);
+                *left -= taken;
+                Some((i, price, taken))
+            })
+            .collect();
+
+        let filled: f64 = levels.iter().map(|(_, _, q)| q).sum();
+        let cost: f64 = levels.iter().map(|(_, p, q)| p * q).sum();
+        let remaining = match limit_price {
+            Some(_) => quantity - filled,
+            None => 0.0,
+        };
+        let (spent_key, spent, received_key) = match side {
+            OrderSide::Buy => (
+                exchange.with_currency(&pair.second),
+                cost + remaining.max(0.0) * limit_price.unwrap_or(0.0),
+                exchange.with_currency(&pair.first),
+            ),
+            OrderSide::Sell => (
+                exchange.with_currency(&pair.first),
+                filled + remaining.max(0.0),
+                exchange.with_currency(&pair.second),
+            ),
+        };
+        let available = self.portfolio.available(&spent_key);
+        if available.0 + EPSILON < spent {
+            return Err(CryptoTypesError::InsufficientFunds(spent_key, spent, available.0));
+        }
+
+        let order_id = self.next_order_id;
+        self.next_order_id += 1;
+        let mut fills = Vec::with_capacity(levels.len());
+        for (i, price, taken) in levels.iter().copied() {
+            match side {
+                OrderSide::Buy => book.asks[i].amount.0 -= taken,
+                OrderSide::Sell => book.bids[i].amount.0 -= taken,
+            }
+            let gross = match side {
+                OrderSide::Buy => taken,
+                OrderSide::Sell => taken * price,
+            };
+            fills.push(Fill {
+                order_id,
+                exchange,
+                pair,
+                side,
+                price,
+                quantity: taken,
+                fee: CurrencyAmount(gross * taker_fee),
+                is_maker: false,
+                timestamp_ms: self.now_ms,
+            });
+        }
+        book.asks.retain(|a| a.amount.0 > EPSILON);
+        book.bids.retain(|b| b.amount.0 > EPSILON);
+
+        self.portfolio.withdraw(spent_key, CurrencyAmount(spent.min(available.0)))?;
+        for fill in fills.iter() {
+            self.portfolio.deposit(received_key, fill.received())?;
+        }
+        if let Some(price) = limit_price.filter(|_| remaining > EPSILON) {
+            let reserved = match side {
+                OrderSide::Buy => remaining * price,
+                OrderSide::Sell => remaining,
+            };
+            // the remaining funds were withdrawn above so they are moved back as reserved
+            self.portfolio.deposit(spent_key, CurrencyAmount(reserved))?;
+            self.portfolio.reserve(spent_key, CurrencyAmount(reserved))?;
+            self.resting_orders.push(RestingOrder {
+                order_id,
+                exchange,
+                pair,
+                side,
+                price,
+                remaining,
+            });
+        }
+        self.fills.extend(fills.iter().copied());
+        Ok((order_id, fills))
+    }
+
+    /// Cancels a resting order and releases its reserved funds
+    pub fn cancel_order(&mut self, order_id: u64) -> Option<RestingOrder> {
+        let i = self.resting_orders.iter().position(|o| o.order_id == order_id)?;
+        let order = self.resting_orders.remove(i);
+        let (key, amount) = Self::resting_reservation(&order, order.remaining);
+        self.portfolio.release(key, amount).ok()?;
+        Some(order)
+    }
+
+    fn resting_reservation(order: &RestingOrder, quantity: f64) -> (ExchangeIDCurrencyIDPair, CurrencyAmount) {
+        match order.side {
+            OrderSide::Buy => (
+                order.exchange.with_currency(&order.pair.second),
+                CurrencyAmount(quantity * order.price),
+            ),
+            OrderSide::Sell => (order.exchange.with_currency(&order.pair.first), CurrencyAmount(quantity)),
+        }
+    }
+
+    fn match_resting_orders(&mut self) -> Result<(), CryptoTypesError> {
+        let mut resting_orders = std::mem::take(&mut self.resting_orders);
+        let result = self.match_orders(&mut resting_orders);
+        resting_orders.retain(|o| o.remaining > EPSILON);
+        self.resting_orders = resting_orders;
+        result
+    }
+
+    fn match_orders(&mut self, resting_orders: &mut [RestingOrder]) -> Result<(), CryptoTypesError> {
+        for order in resting_orders.iter_mut() {
+            let Some(book) = self.books.get_mut(&(order.exchange, order.pair)) else {
+                continue;
+            };
+            let maker_fee = self.fees.get(&order.exchange).copied().unwrap_or(self.default_fees).maker;
+            let mut filled = 0.0;
+            match order.side {
+                OrderSide::Buy => {
+                    for ask in book.asks.iter_mut().filter(|a| a.price.0 <= order.price) {
+                        let taken = ask.amount.0.min(order.remaining - filled);
+                        ask.amount.0 -= taken;
+                        filled += taken;
+                    }
+                }
+                OrderSide::Sell => {
+                    for bid in book.bids.iter_mut().filter(|b| b.price.0 >= order.price) {
+                        let taken = bid.amount.0.min(order.remaining - filled);
+                        bid.amount.0 -= taken;
+                        filled += taken;
+                    }
+                }
+            }
+            book.asks.retain(|a| a.amount.0 > EPSILON);
+            book.bids.retain(|b| b.amount.0 > EPSILON);
+            if filled <= EPSILON {
+                continue;
+            }
+            // resting orders are filled at their own price
+            let gross = match order.side {
+                OrderSide::Buy => filled,
+                OrderSide::Sell => filled * order.price,
+            };
+            let fill = Fill {
+                order_id: order.order_id,
+                exchange: order.exchange,
+                pair: order.pair,
+                side: order.side,
+                price: order.price,
+                quantity: filled,
+                fee: CurrencyAmount(gross * maker_fee),
+                is_maker: true,
+                timestamp_ms: self.now_ms,
+            };
+            let (spent_key, spent) = Self::resting_reservation(order, filled);
+            let received_key = match order.side {
+                OrderSide::Buy => order.exchange.with_currency(&order.pair.first),
+                OrderSide::Sell => order.exchange.with_currency(&order.pair.second),
+            };
+            // the funds were reserved when the order was placed
+            self.portfolio.release(spent_key, spent)?;
+            self.portfolio.withdraw(spent_key, spent)?;
+            self.portfolio.deposit(received_key, fill.received())?;
+            self.fills.push(fill);
+            order.remaining -= filled;
+        }
+        Ok(())
+    }
+
+    /// Withdraws the amount from the withdraw exchange. The amount without the withdrawal fee is in transit
+    /// and deposited on the deposit exchange after the configured delay.
+    pub fn withdraw(
+        &mut self,
+        transfer: &TransferExchangeToExchangeCurrencyInfo,
+        amount: CurrencyAmount,
+    ) -> Result<PendingTransfer, CryptoTypesError> {
+        let withdrawal = self.withdrawal(&transfer.withdraw_exchange.with_currency(&transfer.currency));
+        let received = CurrencyAmount((amount.0 - withdrawal.fee.0).max(0.0));
+        self.portfolio.reserve_transfer(transfer, amount)?;
+        self.portfolio.start_reserved_transfer(transfer, amount, received)?;
+        let pending = PendingTransfer {
+            transfer: *transfer,
+            received,
+            arrival_ms: self.now_ms + withdrawal.delay_ms,
+        };
+        self.pending_transfers.push(pending);
+        Ok(pending)
+    }
+
+    /// Moves the simulated clock forward and completes the arrived transfers.
+    /// Fails if the funds of an arrived transfer are no longer in transit, the transfer is kept pending.
+    pub fn advance_time(&mut self, ms: u64) -> Result<(), CryptoTypesError> {
+        self.now_ms += ms;
+        let now_ms = self.now_ms;
+        let (arrived, pending): (Vec<_>, Vec<_>) =
+            self.pending_transfers.drain(..).partition(|t| t.arrival_ms <= now_ms);
+        self.pending_transfers = pending;
+        let mut arrived = arrived.into_iter();
+        while let Some(t) = arrived.next() {
+            if let Err(err) = self.portfolio.complete_transfer(&t.transfer, t.received) {
+                self.pending_transfers.push(t);
+                self.pending_transfers.extend(arrived);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes the plan steps in order. Orders are sent as market or limit orders depending on the step
+    /// and the clock is advanced until every transfer arrives.
+    pub fn execute_plan(&mut self, plan: &ExecutionPlan) -> Result<(), CryptoTypesError> {
+        for step in plan.steps.iter() {
+            match &step.step {
+                ExecutionStepKind::Order(order) => {
+                    let limit_price = match order.order_type {
+                        OrderType::Limit => Some(order.price),
+                        OrderType::Market => None,
+                    };
+                    self.place_order(order.exchange_id, order.pair, order.side, order.quantity, limit_price)?;
+                }
+                ExecutionStepKind::Transfer(transfer) => {
+                    let tr = TransferExchangeToExchangeCurrencyInfo {
+                        withdraw_exchange: transfer.withdraw_exchange_id,
+                        deposit_exchange: transfer.deposit_exchange_id,
+                        currency: transfer.currency_id,
+                        network: transfer.network_id,
+                    };
+                    let pending = self.withdraw(&tr, CurrencyAmount(transfer.amount))?;
+                    self.advance_time(pending.arrival_ms - self.now_ms)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage_paths::*;
+
+    const BTC: CurrencyID = CurrencyID(0);
+    const USDT: CurrencyID = CurrencyID(1);
+
+    fn pair() -> CurrencyIDPair {
+        CurrencyIDPair::new(BTC, USDT)
+    }
+
+    fn book(exchange: ExchangeID, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> ExchangeMarketOrderBook {
+        let mut book = ExchangeMarketOrderBook::new(exchange, pair());
+        book.bids = bids
+            .iter()
+            .map(|(p, a)| BidPriceAmount {
+                price: BidPrice(*p),
+                amount: PriceAmount(*a),
+            })
+            .collect();
+        book.asks = asks
+            .iter()
+            .map(|(p, a)| AskPriceAmount {
+                price: AskPrice(*p),
+                amount: PriceAmount(*a),
+            })
+            .collect();
+        book
+    }
+
+    fn simulator() -> SimulatedExchange {
+        let fees = SimulatedFees {
+            maker: 0.0,
+            taker: 0.01,
+        };
+        let withdrawal = SimulatedWithdrawal {
+            fee: CurrencyAmount(0.001),
+            delay_ms: 1000,
+        };
+        let mut sim = SimulatedExchange::new(fees, withdrawal);
+        sim.set_order_book(book(ExchangeID(0), &[(99.0, 1.0), (98.0, 2.0)], &[(101.0, 1.0), (102.0, 2.0)])).unwrap();
+        sim
+    }
+
+    #[test]
+    fn market_order_walks_the_book_test() {
+        let mut sim = simulator();
+        let usdt = ExchangeID(0).with_currency(&USDT);
+        let btc = ExchangeID(0).with_currency(&BTC);
+        sim.deposit(usdt, CurrencyAmount(1000.0)).unwrap();
+        let (_, fills) = sim.place_order(ExchangeID(0), pair(), OrderSide::Buy, 2.0, None).unwrap();
+        assert_eq!(fills.len(), 2);
+        assert_eq!((fills[0].price, fills[0].quantity), (101.0, 1.0));
+        assert_eq!((fills[1].price, fills[1].quantity), (102.0, 1.0));
+        assert_eq!(sim.portfolio().available(&usdt), CurrencyAmount(1000.0 - 203.0));
+        assert!((sim.portfolio().available(&btc).0 - 1.98).abs() < 1e-9);
+        // the liquidity was consumed
+        let asks = &sim.order_book(ExchangeID(0), pair()).unwrap().asks;
+        assert_eq!(asks.len(), 1);
+        assert_eq!(asks[0].amount.0, 1.0);
+        // the unfilled part of a limit order must be funded too
+        assert!(matches!(
+            sim.place_order(ExchangeID(0), pair(), OrderSide::Buy, 10.0, Some(200.0)),
+            Err(CryptoTypesError::InsufficientFunds(_, _, _))
+        ));
+        for quantity in [f64::NAN, -1.0, 0.0] {
+            assert!(matches!(
+                sim.place_order(ExchangeID(0), pair(), OrderSide::Buy, quantity, None),
+                Err(CryptoTypesError::InvalidOrderQuantity(_))
+            ));
+        }
+        assert_eq!(sim.fills().len(), 2);
+        assert_eq!(sim.order_book(ExchangeID(0), pair()).unwrap().asks.len(), 1);
+    }
+
+    #[test]
+    fn resting_limit_order_test() {
+        let mut sim = simulator();
+        let usdt = ExchangeID(0).with_currency(&USDT);
+        let btc = ExchangeID(0).with_currency(&BTC);
+        sim.deposit(btc, CurrencyAmount(1.0)).unwrap();
+        let (id, fills) = sim.place_order(ExchangeID(0), pair(), OrderSide::Sell, 1.0, Some(100.0)).unwrap();
+        assert!(fills.is_empty());
+        assert_eq!(sim.portfolio().holding(&btc).reserved, CurrencyAmount(1.0));
+        assert_eq!(sim.resting_orders().len(), 1);
+        // the book moves up and crosses the resting order
+        sim.set_order_book(book(ExchangeID(0), &[(100.5, 0.4)], &[(101.0, 1.0)])).unwrap();
+        assert_eq!(sim.fills().last().unwrap().quantity, 0.4);
+        assert!(sim.fills().last().unwrap().is_maker);
+        assert_eq!(sim.portfolio().available(&usdt), CurrencyAmount(40.0));
+        let cancelled = sim.cancel_order(id).unwrap();
+        assert!((cancelled.remaining - 0.6).abs() < 1e-9);
+        assert!((sim.portfolio().available(&btc).0 - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn delayed_withdrawal_test() {
+        let mut sim = simulator();
+        let tr = TransferExchangeToExchangeCurrencyInfo {
+            withdraw_exchange: ExchangeID(0),
+            deposit_exchange: ExchangeID(1),
+            currency: BTC,
+            network: None,
+        };
+        sim.deposit(ExchangeID(0).with_currency(&BTC), CurrencyAmount(1.0)).unwrap();
+        sim.withdraw(&tr, CurrencyAmount(1.0)).unwrap();
+        let deposit_key = ExchangeID(1).with_currency(&BTC);
+        sim.advance_time(999).unwrap();
+        assert_eq!(sim.portfolio().holding(&deposit_key).in_transit, CurrencyAmount(0.999));
+        sim.advance_time(1).unwrap();
+        assert_eq!(sim.portfolio().available(&deposit_key), CurrencyAmount(0.999));
+        assert!(sim.pending_transfers().is_empty());
+    }
+
+    #[test]
+    fn execute_plan_test() {
+        use crate::lexicon::*;
+        let ex_symbols: Vec<ExchangeSymbolsJson> = ["kraken", "binance"]
+            .iter()
+            .map(|e| ExchangeSymbolsJson {
+                exchange: e.to_string(),
+                symbols: vec!["BTC/USDT".to_string()],
+            })
+            .collect();
+        let lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&ex_symbols).unwrap();
+        let kraken = *lexicon.exchanges.get_id("kraken").unwrap();
+        let binance = *lexicon.exchanges.get_id("binance").unwrap();
+        let btc = *lexicon.currencies.get_id("BTC").unwrap();
+        let usdt = *lexicon.currencies.get_id("USDT").unwrap();
+        let market = CurrencyIDPair::new(btc, usdt);
+
+        // buy BTC on kraken, transfer it to binance and sell it there
+        let mut path = ArbitragePath::new();
+        path.0.push_back(kraken.with_currency(&usdt).into());
+        path.0.push_back(
+            TransactionExchangeCurrenciesBuySellInfo {
+                exchange: kraken,
+                side: TransactionSide::SELL,
+                currency_from: usdt,
+                currency_to: btc,
+            }
+            .into(),
+        );
+        path.0.push_back(kraken.with_currency(&btc).into());
+        path.0.push_back(
+            TransferExchangeToExchangeCurrencyInfo {
+                withdraw_exchange: kraken,
+                deposit_exchange: binance,
+                currency: btc,
+                network: None,
+            }
+            .into(),
+        );
+        path.0.push_back(binance.with_currency(&btc).into());
+        path.0.push_back(
+            TransactionExchangeCurrenciesBuySellInfo {
+                exchange: binance,
+                side: TransactionSide::BUY,
+                currency_from: btc,
+                currency_to: usdt,
+            }
+            .into(),
+        );
+        path.0.push_back(binance.with_currency(&usdt).into());
+        let sizing = PathSizing {
+            balance_amounts: vec![
+                CurrencyAmount(100.0),
+                CurrencyAmount(1.0),
+                CurrencyAmount(0.999),
+                CurrencyAmount(109.89),
+            ],
+        };
+        let mut settings = ExecutionPlanSettings::new_default();
+        settings.order_type = OrderType::Market;
+        let plan = ExecutionPlan::create(&path, &sizing, &lexicon, &settings, None).unwrap();
+
+        let mut sim = SimulatedExchange::new(
+            SimulatedFees { maker: 0.0, taker: 0.0 },
+            SimulatedWithdrawal {
+                fee: CurrencyAmount(0.001),
+                delay_ms: 60_000,
+            },
+        );
+        let mut kraken_book = ExchangeMarketOrderBook::new(kraken, market);
+        kraken_book.asks.push(AskPriceAmount {
+            price: AskPrice(100.0),
+            amount: PriceAmount(5.0),
+        });
+        let mut binance_book = ExchangeMarketOrderBook::new(binance, market);
+        binance_book.bids.push(BidPriceAmount {
+            price: BidPrice(110.0),
+            amount: PriceAmount(5.0),
+        });
+        sim.set_order_book(kraken_book).unwrap();
+        sim.set_order_book(binance_book).unwrap();
+        sim.deposit(kraken.with_currency(&usdt), CurrencyAmount(100.0)).unwrap();
+
+        sim.execute_plan(&plan).unwrap();
+        assert_eq!(sim.fills().len(), 2);
+        assert_eq!(sim.now_ms(), 60_000);
+        assert_eq!(sim.portfolio().available(&kraken.with_currency(&usdt)), CurrencyAmount(0.0));
+        assert!((sim.portfolio().available(&binance.with_currency(&usdt)).0 - 109.89).abs() < 1e-9);
+    }
+}
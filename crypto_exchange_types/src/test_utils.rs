@@ -0,0 +1,9 @@
+//! Fixtures shared by the tests of this crate and the crates depending on it (`test-utils` feature)
+use crate::lexicon::*;
+
+pub fn ex_symbols(exchange: &str, symbols: &[&str]) -> ExchangeSymbolsJson {
+    ExchangeSymbolsJson {
+        exchange: exchange.to_string(),
+        symbols: symbols.iter().map(|s| s.to_string()).collect(),
+    }
+}
@@ -27,6 +27,17 @@ fn ask_price_partial_ord_test() {
     );
 }
 
+#[test]
+fn exchange_market_key_test() {
+    let key = ExchangeMarketKey::create(ExchangeID(0), CurrencyID(2), CurrencyID(1)).unwrap();
+    assert_eq!(key.sorted_currency_pair_less, CurrencyID(1));
+    assert_eq!(key.sorted_currency_pair_greater, CurrencyID(2));
+    assert!(matches!(
+        ExchangeMarketKey::create(ExchangeID(0), CurrencyID(1), CurrencyID(1)),
+        Err(CryptoTypesError::InvalidMarket(ExchangeID(0), _))
+    ));
+}
+
 // #[test]
 // fn order_book_sort_orderbook_test() {
 //     let mut ob = OrderBook::new(CurrencyIDPair::new(CurrencyID(0), CurrencyID(1)));
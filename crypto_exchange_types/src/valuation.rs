@@ -0,0 +1,241 @@
+use crate::arbitrage_paths::*;
+use crate::id_types::*;
+use crate::lexicon::*;
+use crate::order_book::*;
+use crate::price_amounts::*;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+
+/// Reference price of a currency expressed in the valuation (quote) currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferencePrice {
+    /// Value of one unit of the currency in the quote currency
+    pub price: f64,
+    /// Currencies used for the conversion starting with the priced currency and ending with the quote currency
+    pub route: Vec<CurrencyID>,
+}
+
+/// Ranked path with the profit expressed in the valuation (quote) currency
+pub struct RankedPath<'a> {
+    pub path: &'a ArbitragePath,
+    pub start: BalanceExchangeCurrencyInfo,
+    pub valued_profit: CurrencyAmount,
+}
+
+/// PriceOracle derives a reference price for every reachable CurrencyID in a chosen quote currency
+/// from the available order books. Markets of the same currency pair on different exchanges are merged
+/// by taking the median mid price. The conversion routes prefer going through stable coins and fiat
+/// currencies (anchor currencies) and only then the shortest route.
+/// This makes it possible to compare the profits of paths starting from different currencies (BTC vs USDT vs EUR).
+pub struct PriceOracle {
+    quote: CurrencyID,
+    prices: BTreeMap<CurrencyID, ReferencePrice>,
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    } else {
+        Some(values[mid])
+    }
+}
+
+impl PriceOracle {
+    /// Uses the lexicon fiat and stable currencies as the anchor currencies
+    pub fn from_order_books<'a, I>(quote: CurrencyID, books: I, lexicon: &CryptoExchangeLexicon) -> Self
+    where
+        I: IntoIterator<Item = &'a ExchangeMarketOrderBook>,
+    {
+        let anchors: BTreeSet<CurrencyID> = lexicon
+            .fiat_currencies
+            .union(&lexicon.stable_currencies)
+            .copied()
+            .collect();
+        Self::with_anchor_currencies(quote, books, &anchors)
+    }
+
+    pub fn with_anchor_currencies<'a, I>(quote: CurrencyID, books: I, anchors: &BTreeSet<CurrencyID>) -> Self
+    where
+        I: IntoIterator<Item = &'a ExchangeMarketOrderBook>,
+    {
+        // mid prices for every market regardless of the exchange
+        let mut market_mid_prices: BTreeMap<CurrencyIDPair, Vec<f64>> = BTreeMap::new();
+        books
+            .into_iter()
+            .filter(|book| !book.pair.has_same_currencies())
+            .for_each(|book| {
+                if let Some(mid) = book.mid_price().filter(|mid| mid.is_finite() && *mid > 0.0) {
+                    market_mid_prices.entry(book.pair).or_default().push(mid);
+                }
+            });
+        // edges hold the rate for converting one unit of the first currency into the second currency
+        let mut edges: BTreeMap<CurrencyID, Vec<(CurrencyID, f64)>> = BTreeMap::new();
+        market_mid_prices.into_iter().for_each(|(pair, mids)| {
+            if let Some(mid) = median(mids) {
+                edges.entry(pair.first).or_default().push((pair.second, mid));
+                edges.entry(pair.second).or_default().push((pair.first, 1.0 / mid));
+            }
+        });
+
+        // dijkstra from the quote currency with the (non anchor intermediates, hops) route cost
+        let mut prices: BTreeMap<CurrencyID, ReferencePrice> = BTreeMap::new();
+        let mut costs: BTreeMap<CurrencyID, (usize, usize)> = BTreeMap::new();
+        let mut heap = BinaryHeap::new();
+        prices.insert(quote, ReferencePrice { price: 1.0, route: vec![quote] });
+        costs.insert(quote, (0, 0));
+        heap.push(Reverse(((0usize, 0usize), quote)));
+        while let Some(Reverse((cost, currency))) = heap.pop() {
+            if costs.get(&currency).is_some_and(|known| *known < cost) {
+                continue;
+            }
+            let is_intermediate_penalty = usize::from(currency != quote && !anchors.contains(&currency));
+            let (price, route) = {
+                let reference = &prices[&currency];
+                (reference.price, reference.route.clone())
+            };
+            for (next, rate_to_next) in edges.get(&currency).into_iter().flatten() {
+                let next_cost = (cost.0 + is_intermediate_penalty, cost.1 + 1);
+                if costs.get(next).is_some_and(|known| *known <= next_cost) {
+                    continue;
+                }
+                // one unit of next is worth 1/rate_to_next units of the current currency
+                let mut next_route = Vec::with_capacity(route.len() + 1);
+                next_route.push(*next);
+                next_route.extend(route.iter());
+                costs.insert(*next, next_cost);
+                prices.insert(*next, ReferencePrice { price: price / rate_to_next, route: next_route });
+                heap.push(Reverse((next_cost, *next)));
+            }
+        }
+        PriceOracle { quote, prices }
+    }
+
+    pub fn quote(&self) -> CurrencyID {
+        self.quote
+    }
+
+    pub fn reference_price(&self, c: &CurrencyID) -> Option<&ReferencePrice> {
+        self.prices.get(c)
+    }
+
+    pub fn price(&self, c: &CurrencyID) -> Option<f64> {
+        self.prices.get(c).map(|reference| reference.price)
+    }
+
+    /// Expresses the currency amount in the quote currency
+    pub fn value(&self, c: &CurrencyID, amount: CurrencyAmount) -> Option<CurrencyAmount> {
+        self.price(c).map(|price| CurrencyAmount(amount.0 * price))
+    }
+
+    /// Path profit expressed in the quote currency. The start and end currency are read
+    /// from the first and last balance node of the path.
+    pub fn path_profit(
+        &self,
+        path: &ArbitragePath,
+        start_amount: CurrencyAmount,
+        end_amount: CurrencyAmount,
+    ) -> Option<CurrencyAmount> {
+        let (start, end) = match (path.0.front(), path.0.back()) {
+            (Some(ExchangeOperationType::Balance(start)), Some(ExchangeOperationType::Balance(end))) => (start, end),
+            _ => return None,
+        };
+        let start_value = self.value(&start.currency, start_amount)?;
+        let end_value = self.value(&end.currency, end_amount)?;
+        Some(CurrencyAmount(end_value.0 - start_value.0))
+    }
+
+    /// Ranks the paths with their start and end amounts by the profit expressed in the quote currency.
+    /// The most profitable path comes first. Paths that can't be valued are left out.
+    pub fn rank_paths<'a, I>(&self, paths: I) -> Vec<RankedPath<'a>>
+    where
+        I: IntoIterator<Item = (&'a ArbitragePath, CurrencyAmount, CurrencyAmount)>,
+    {
+        let mut ranked: Vec<RankedPath<'a>> = paths
+            .into_iter()
+            .filter_map(|(path, start_amount, end_amount)| {
+                let start = match path.0.front() {
+                    Some(ExchangeOperationType::Balance(start)) => *start,
+                    _ => return None,
+                };
+                let valued_profit = self.path_profit(path, start_amount, end_amount)?;
+                Some(RankedPath { path, start, valued_profit })
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.valued_profit
+                .partial_cmp(&a.valued_profit)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC: CurrencyID = CurrencyID(0);
+    const USDT: CurrencyID = CurrencyID(1);
+    const EUR: CurrencyID = CurrencyID(2);
+    const ETH: CurrencyID = CurrencyID(3);
+    const XYZ: CurrencyID = CurrencyID(4);
+
+    fn book(exchange: u8, first: CurrencyID, second: CurrencyID, bid: f64, ask: f64) -> ExchangeMarketOrderBook {
+        let mut book = ExchangeMarketOrderBook::new(ExchangeID(exchange), CurrencyIDPair::new(first, second));
+        book.bids.push(BidPriceAmount { price: BidPrice(bid), amount: PriceAmount(1.0) });
+        book.asks.push(AskPriceAmount { price: AskPrice(ask), amount: PriceAmount(1.0) });
+        book
+    }
+
+    fn cycle(c: CurrencyID) -> ArbitragePath {
+        let mut path = ArbitragePath::new();
+        path.0.push_back(ExchangeID(0).with_currency(&c).into());
+        path.0.push_back(ExchangeID(0).with_currency(&c).into());
+        path
+    }
+
+    #[test]
+    fn reference_prices_prefer_anchor_routes_test() {
+        let books = vec![
+            book(0, BTC, USDT, 9990.0, 10010.0),
+            book(1, BTC, USDT, 10090.0, 10110.0),
+            book(0, ETH, BTC, 0.0199, 0.0201),
+            book(0, ETH, USDT, 199.0, 201.0),
+            book(0, EUR, USDT, 1.1, 1.1),
+            book(0, XYZ, ETH, 0.5, 0.5),
+        ];
+        let anchors: BTreeSet<_> = [USDT, EUR].iter().copied().collect();
+        let oracle = PriceOracle::with_anchor_currencies(EUR, &books, &anchors);
+        assert_eq!(oracle.price(&EUR), Some(1.0));
+        assert_eq!(oracle.reference_price(&BTC).unwrap().route, vec![BTC, USDT, EUR]);
+        // median of two exchanges mid prices 10000 and 10100
+        assert!((oracle.price(&BTC).unwrap() - 10050.0 / 1.1).abs() < 1e-9);
+        assert_eq!(oracle.reference_price(&ETH).unwrap().route, vec![ETH, USDT, EUR]);
+        assert_eq!(oracle.reference_price(&XYZ).unwrap().route, vec![XYZ, ETH, USDT, EUR]);
+        assert!((oracle.price(&XYZ).unwrap() - 100.0 / 1.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rank_paths_in_quote_currency_test() {
+        let books = vec![book(0, BTC, USDT, 10000.0, 10000.0)];
+        let anchors = BTreeSet::new();
+        let oracle = PriceOracle::with_anchor_currencies(USDT, &books, &anchors);
+        let btc_path = cycle(BTC);
+        let usdt_path = cycle(USDT);
+        let eth_path = cycle(ETH);
+        let ranked = oracle.rank_paths(vec![
+            (&usdt_path, CurrencyAmount(1000.0), CurrencyAmount(1005.0)),
+            (&btc_path, CurrencyAmount(1.0), CurrencyAmount(1.001)),
+            (&eth_path, CurrencyAmount(1.0), CurrencyAmount(2.0)),
+        ]);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].start.currency, BTC);
+        assert!((ranked[0].valued_profit.0 - 10.0).abs() < 1e-6);
+        assert_eq!(ranked[1].start.currency, USDT);
+    }
+}
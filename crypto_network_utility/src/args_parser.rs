@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 use clap::Parser;
+use crypto_exchange_types::OutputFormat;
 
 // // WHAT the utility does???
 //   - Generate dictionary FOR network generation,
@@ -49,28 +50,56 @@ struct Lexicon {
 pub enum LexiconCommand {
     /// Generate a Lexicon file from 'exchanges-currency-pairs JSON definition file'
     #[clap(name = "generate")]
-    Generate(LexiconInOutFile),
+    Generate(LexiconGenerateFiles),
+    /// Regenerate a Lexicon file from a newer 'exchanges-currency-pairs JSON definition file' while keeping the
+    /// IDs of the previous Lexicon file stable. New exchanges and currencies are appended and the missing ones
+    /// are marked as deprecated so previously generated network files stay valid.
+    #[clap(name = "update")]
+    Update(LexiconUpdateFiles),
+    /// Compare two Lexicon files. Reports added/removed exchanges, currencies and exchange pairs
+    /// and the names that are mapped to different IDs.
+    #[clap(name = "diff")]
+    Diff(LexiconDiffFiles),
+    /// Generate a Lexicon file from a full CCXT markets JSON dump. Only active spot markets are used and
+    /// their precision and limits are stored in the Lexicon. Every skipped market is reported.
+    #[clap(name = "import-ccxt")]
+    ImportCcxt(LexiconImportCcxtFiles),
+    /// Set the networks (chains) the exchanges support for the currency transfers. Replaces the previous
+    /// networks while keeping their IDs. Unknown exchanges and currencies are skipped and reported.
+    #[clap(name = "set-networks")]
+    SetNetworks(LexiconSetNetworksFiles),
+    /// Write the exchange, currency and network names of a Lexicon file into a compact read only names file.
+    /// The names file is uncompressed bincode so it can be loaded from a memory mapped buffer.
+    #[clap(name = "freeze-names")]
+    FreezeNames(LexiconFreezeNamesFiles),
+
+    /// Verify a Lexicon file that there are not any invalid exchange currency pair items/markets.
+    /// E.g. equal pairs or inverse pairs on the same exchange indicate a bad 'exchanges-currency-pairs JSON definition file'.
+    /// Also reports orphan currencies, exchanges without pairs, IDs out of range and duplicate names.
+    /// Exits with an error if the Lexicon is invalid.
+    #[clap(name = "verify")]
+    Verify(LexiconVerifyFiles),
+
+    /// Query a Lexicon file e.g. which exchanges list a currency or which currencies can be transferred
+    /// between two exchanges
+    #[clap(name = "query", subcommand)]
+    Query(LexiconQueryCommand),
 
     #[command(flatten)]
     Print(PrintLexiconCommand),
-
-    // // TODO add COMPARE (exact match, full domain but different id match)
-    // // TODO add has exchange
-    // // TODO add exchange has currency exchange has pair
-    // // TODO add print fiat currencies
-    // // TODO add print stable coins
-    // // TODO add checksum for exchange_pairs
 }
 
 #[derive(Parser)]
 pub enum PrintLexiconCommand {
-    /// Verify a Lexicon file that there are not any invalid exchange currency pair items/markets.
-    /// E.g. equal pairs or inverse pairs on the same exchange indicate a bad 'exchanges-currency-pairs JSON definition file'
-    #[clap(name = "verify")]
-    Verify{ in_file_path: PathBuf },
     /// Prints all currencies IDs and names for a given Lexicon file
     #[clap(name = "print-all-currencies")]
     PrintAllCurrencies(LexiconPrintPath),
+    /// Prints all fiat currencies IDs and names for a given Lexicon file
+    #[clap(name = "print-fiat-currencies")]
+    PrintFiatCurrencies(LexiconPrintPath),
+    /// Prints all stable currencies IDs and names for a given Lexicon file
+    #[clap(name = "print-stable-currencies")]
+    PrintStableCurrencies(LexiconPrintPath),
     /// Prints all exchanges currency pairs for a given Lexicon file
     #[clap(name = "print-all-exchanges-pairs")]
     PrintAllExchangesPairs(LexiconPrintPath),
@@ -79,15 +108,162 @@ pub enum PrintLexiconCommand {
     PrintExchanges(LexiconPrintPath),
     /// Prints all currency pairs just for the selected exchange for a given Lexicon file
     #[clap(name = "print-exchange-pairs")]
-    PrintExchangePairs(LexiconPrintExchangePairsInFile), // TODO ADD JSON version as well
+    PrintExchangePairs(LexiconPrintExchangePairsInFile),
+}
+
+#[derive(Parser)]
+pub enum LexiconQueryCommand {
+    /// Checks if the exchange is part of the Lexicon and not deprecated
+    #[clap(name = "has-exchange")]
+    HasExchange {
+        in_file_path: PathBuf,
+        #[clap(short = 'e', long = "exchange")]
+        exchange: String,
+        #[clap(short = 'j', long = "json")]
+        json: bool,
+    },
+    /// Checks if the currency is part of the Lexicon and not deprecated. Aliases e.g. 'XBT' are normalised.
+    #[clap(name = "has-currency")]
+    HasCurrency {
+        in_file_path: PathBuf,
+        #[clap(short = 'c', long = "currency")]
+        currency: String,
+        #[clap(short = 'j', long = "json")]
+        json: bool,
+    },
+    /// Lists the exchanges that have at least one pair with the currency
+    #[clap(name = "exchanges-with-currency")]
+    ExchangesWithCurrency {
+        in_file_path: PathBuf,
+        #[clap(short = 'c', long = "currency")]
+        currency: String,
+        #[clap(short = 'j', long = "json")]
+        json: bool,
+    },
+    /// Lists the pairs of the exchange that contain the currency
+    #[clap(name = "currency-pairs")]
+    CurrencyPairs {
+        in_file_path: PathBuf,
+        #[clap(short = 'e', long = "exchange")]
+        exchange: String,
+        #[clap(short = 'c', long = "currency")]
+        currency: String,
+        #[clap(short = 'j', long = "json")]
+        json: bool,
+    },
+    /// Checks if the exchange has the pair e.g. 'BTC/USD'
+    #[clap(name = "has-pair")]
+    HasPair {
+        in_file_path: PathBuf,
+        #[clap(short = 'e', long = "exchange")]
+        exchange: String,
+        #[clap(short = 'p', long = "pair")]
+        pair: String,
+        #[clap(short = 'j', long = "json")]
+        json: bool,
+    },
+    /// Lists the currencies traded on every given exchange
+    #[clap(name = "common-currencies")]
+    CommonCurrencies {
+        in_file_path: PathBuf,
+        #[clap(short = 'e', long = "exchange", required = true)]
+        exchanges: Vec<String>,
+        #[clap(short = 'j', long = "json")]
+        json: bool,
+    },
+    /// Lists the currencies that can be transferred between two exchanges
+    #[clap(name = "transfers")]
+    Transfers {
+        in_file_path: PathBuf,
+        #[clap(long = "from")]
+        from_exchange: String,
+        #[clap(long = "to")]
+        to_exchange: String,
+        #[clap(short = 'j', long = "json")]
+        json: bool,
+    },
 }
 
 #[derive(Parser)]
-pub struct LexiconInOutFile {
+pub struct LexiconGenerateFiles {
     // #[clap(short = 'i', long = "input-file")]
     pub in_file_path: PathBuf,
     // #[clap(short = 'o', long = "output-file")]
     pub out_file_path: PathBuf,
+    #[command(flatten)]
+    pub definitions: LexiconDefinitionFiles,
+}
+
+#[derive(Parser)]
+pub struct LexiconImportCcxtFiles {
+    /// Path to the CCXT markets JSON file e.g. '[{"exchange": "kraken", "markets": {"BTC/USD": {...}}}]'
+    pub in_file_path: PathBuf,
+    pub out_file_path: PathBuf,
+    #[command(flatten)]
+    pub definitions: LexiconDefinitionFiles,
+    /// Write the skipped markets and the reasons into a JSON report file
+    #[clap(long = "report")]
+    pub report_file_path: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct LexiconDefinitionFiles {
+    /// Fiat currencies JSON definition file e.g. '[{"name": "USD", "aliases": ["ZUSD"]}]'
+    #[clap(long = "fiat")]
+    pub fiat_file_path: Option<PathBuf>,
+    /// Stable currencies JSON definition file e.g. '[{"name": "USDT"}]'
+    #[clap(long = "stable")]
+    pub stable_file_path: Option<PathBuf>,
+    /// Symbol normalisation JSON file mapping exchange symbols to canonical currency names
+    /// e.g. '{"aliases": {"XBT": "BTC"}, "exchange_overrides": {"hitbtc": {"BCH": "BCHABC"}}}'
+    #[clap(long = "normalisation")]
+    pub normalisation_file_path: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct LexiconUpdateFiles {
+    /// Path to the previous Lexicon file
+    pub in_file_lexicon_path: PathBuf,
+    /// Path to the newer 'exchanges-currency-pairs JSON definition file'
+    pub in_file_path: PathBuf,
+    pub out_file_path: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct LexiconFreezeNamesFiles {
+    /// Path to the Lexicon file
+    pub in_file_path: PathBuf,
+    pub out_file_path: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct LexiconSetNetworksFiles {
+    /// Path to the Lexicon file
+    pub in_file_lexicon_path: PathBuf,
+    /// Path to the networks JSON file e.g. '[{"exchange": "kraken", "currencies": {"USDT": ["ERC20", "TRC20"]}}]'
+    pub in_file_path: PathBuf,
+    pub out_file_path: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct LexiconVerifyFiles {
+    pub in_file_path: PathBuf,
+    #[clap(short = 'j', long = "json")]
+    pub json: bool,
+    /// Write a cleaned Lexicon file with the fixable issues removed. IDs stay stable, orphan currencies
+    /// and exchanges without pairs are marked as deprecated.
+    #[clap(long = "fix")]
+    pub fix_out_file_path: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct LexiconDiffFiles {
+    /// Path to the old Lexicon file
+    pub a_file_path: PathBuf,
+    /// Path to the new Lexicon file
+    pub b_file_path: PathBuf,
+    #[clap(short = 'j', long = "json")]
+    pub json: bool,
 }
 
 #[derive(Parser)]
@@ -95,13 +271,39 @@ pub struct LexiconPrintExchangePairsInFile {
     pub in_file_path: PathBuf,
     #[clap(short = 'e', long = "exchange")]
     pub exchange: String,
+    #[command(flatten)]
+    pub output: LexiconOutputFormat,
 }
 
 #[derive(Parser)]
 pub struct LexiconPrintPath {
     pub in_file_path: PathBuf,
-    #[clap(short = 'j', long = "json")]
-    pub json: bool,
+    #[command(flatten)]
+    pub output: LexiconOutputFormat,
+}
+
+#[derive(Parser)]
+pub struct LexiconOutputFormat {
+    /// Output format: table, json, csv or ndjson
+    #[clap(short = 'f', long = "format", default_value = "table")]
+    format: OutputFormat,
+    /// JSON with names only as printed by the earlier versions. Use '--format json' for the full rows.
+    #[clap(short = 'j', long = "json", conflicts_with = "format")]
+    json: bool,
+}
+
+impl LexiconOutputFormat {
+    pub fn format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.format
+        }
+    }
+
+    pub fn is_legacy_json(&self) -> bool {
+        self.json
+    }
 }
 
 /// A subcommand for Network operations
@@ -133,11 +335,28 @@ pub enum NetworkCommand {
     },
     #[clap(name = "merge-into-network")]
     MergeIntoNetwork { path: PathBuf },
+    /// Migrate a merged network file to a new Lexicon. The IDs of the stored paths are remapped by names
+    /// and paths whose markets no longer exist are dropped. Reports the targets that should be searched again.
+    #[clap(name = "migrate")]
+    Migrate {
+        /// Path to the merged network file
+        in_file_network_path: PathBuf,
+        /// Path to the new Lexicon file
+        in_file_lexicon_path: PathBuf,
+        out_file_network_path: PathBuf,
+    },
     #[clap(name = "print-all-paths")]
     PrintAllPaths { path: PathBuf },
 
     #[clap(name = "print-stats")]
-    PrintStatistics { path: PathBuf },
+    PrintStatistics {
+        path: PathBuf,
+
+        /// Frozen lexicon names file (see 'lexicon freeze-names') used to print the chunk targets by name.
+        /// The chunks must be generated from the same lexicon.
+        #[clap(short = 'n', long = "names")]
+        names_file_path: Option<PathBuf>,
+    },
 }
 
 #[derive(Parser)]
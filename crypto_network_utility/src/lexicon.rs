@@ -1,16 +1,31 @@
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 use crate::args_parser;
 use crate::file_utils;
-use ::crypto_exchange_types::{CryptoExchangeLexicon, ExchangeSymbolsJson};
+use ::crypto_exchange_types::{
+    render, CcxtExchangeMarketsJson, CryptoExchangeLexicon, CurrencyDefinitionJson, CurrencyID, CurrencyIDPair,
+    ExchangeCurrencyNetworksJson, ExchangeID, ExchangeSymbolsJson, FrozenLexiconNames, IdRemap, LexiconDiff,
+    SymbolNormalisationJson,
+};
 use colored::*;
-use args_parser::{LexiconCommand, PrintLexiconCommand};
+use args_parser::{
+    LexiconCommand, LexiconDefinitionFiles, LexiconGenerateFiles, LexiconImportCcxtFiles, LexiconQueryCommand,
+    LexiconSetNetworksFiles, LexiconVerifyFiles, PrintLexiconCommand,
+};
 
 pub fn handle_lexicon_command(lexicon_command: args_parser::LexiconCommand) -> anyhow::Result<()> {
     match lexicon_command {
-        LexiconCommand::Generate(opts) => {
-            generate_lexicon_file(&opts.in_file_path, &opts.out_file_path)
+        LexiconCommand::Generate(opts) => generate_lexicon_file(opts),
+        LexiconCommand::Update(opts) => {
+            update_lexicon_file(&opts.in_file_lexicon_path, &opts.in_file_path, &opts.out_file_path)
         }
+        LexiconCommand::Verify(opts) => verify_lexicon_file(opts),
+        LexiconCommand::Query(query) => query_lexicon_file(query),
+        LexiconCommand::ImportCcxt(opts) => import_ccxt_lexicon_file(opts),
+        LexiconCommand::SetNetworks(opts) => set_networks_lexicon_file(opts),
+        LexiconCommand::FreezeNames(opts) => freeze_lexicon_names_file(&opts.in_file_path, &opts.out_file_path),
+        LexiconCommand::Diff(opts) => diff_lexicon_files(&opts.a_file_path, &opts.b_file_path, opts.json),
         LexiconCommand::Print(r) => print_lexicon_file(r),
     }
 }
@@ -23,7 +38,58 @@ pub fn read_lexicon_file(file_path: &Path) -> anyhow::Result<CryptoExchangeLexic
     file_utils::read_cbor_lz4_file(&file_path)
 }
 
-fn generate_lexicon_file(in_file_path: &Path, out_file_path: &Path) -> anyhow::Result<()> {
+/// The names borrow from the bytes e.g. a memory mapped names file
+pub fn read_frozen_lexicon_names(bytes: &[u8]) -> anyhow::Result<FrozenLexiconNames<'_>> {
+    Ok(crypto_serializer::bincode_from_slice(bytes)?)
+}
+
+fn freeze_lexicon_names_file(in_file_path: &Path, out_file_path: &Path) -> anyhow::Result<()> {
+    file_utils::file_must_exist(in_file_path)?;
+    file_utils::file_must_not_exist(out_file_path)?;
+    println!(
+        "Freezing lexicon names of '{}' into '{}'",
+        in_file_path.display(), out_file_path.display()
+    );
+    let lexicon = read_lexicon_file(in_file_path)?;
+    let bytes = crypto_serializer::bincode_to_vec(&lexicon.freeze_names()?)?;
+    // the written names must load back through the read only loader
+    let names = read_frozen_lexicon_names(&bytes)?;
+    println!(
+        "Exchanges: {} Currencies: {} Networks: {}",
+        names.exchanges.len(), names.currencies.len(), names.networks.len()
+    );
+    file_utils::write_to_file(&out_file_path, &bytes)?;
+    Ok(())
+}
+
+fn read_currency_definitions_file(file_path: &Option<PathBuf>) -> anyhow::Result<Vec<CurrencyDefinitionJson>> {
+    match file_path {
+        Some(file_path) => {
+            file_utils::file_must_exist(file_path)?;
+            file_utils::read_json_file(file_path)
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+fn read_definition_files(
+    opts: &LexiconDefinitionFiles,
+) -> anyhow::Result<(SymbolNormalisationJson, Vec<CurrencyDefinitionJson>, Vec<CurrencyDefinitionJson>)> {
+    let fiat_currencies = read_currency_definitions_file(&opts.fiat_file_path)?;
+    let stable_currencies = read_currency_definitions_file(&opts.stable_file_path)?;
+    let normalisation: SymbolNormalisationJson = match &opts.normalisation_file_path {
+        Some(file_path) => {
+            file_utils::file_must_exist(file_path)?;
+            file_utils::read_json_file(file_path)?
+        }
+        None => SymbolNormalisationJson::default(),
+    };
+    Ok((normalisation, fiat_currencies, stable_currencies))
+}
+
+fn generate_lexicon_file(opts: LexiconGenerateFiles) -> anyhow::Result<()> {
+    let in_file_path = &opts.in_file_path;
+    let out_file_path = &opts.out_file_path;
     file_utils::file_must_exist(&in_file_path)?;
     file_utils::file_must_not_exist(&out_file_path)?;
     println!(
@@ -33,17 +99,301 @@ fn generate_lexicon_file(in_file_path: &Path, out_file_path: &Path) -> anyhow::R
 
     let json_bytes = file_utils::read_from_file(&in_file_path)?;
     let exchanges_pairs: Vec<ExchangeSymbolsJson> = serde_json::from_slice(&json_bytes)?;
-    let lexicon = CryptoExchangeLexicon::create_from_exchange_symbols(&exchanges_pairs);
+    let (normalisation, fiat_currencies, stable_currencies) = read_definition_files(&opts.definitions)?;
+    let (lexicon, warnings) = CryptoExchangeLexicon::create_from_exchange_symbols_full(
+        &exchanges_pairs,
+        &normalisation,
+        &fiat_currencies,
+        &stable_currencies,
+    )?;
+    warnings.iter().for_each(|warning| println!("{}: {}", "Warning".yellow().bold(), warning));
     // lexicon.print_all();
     // lexicon.print_exchanges();
     save_lexicon_file(&out_file_path, &lexicon)
 }
 
+fn import_ccxt_lexicon_file(opts: LexiconImportCcxtFiles) -> anyhow::Result<()> {
+    let in_file_path = &opts.in_file_path;
+    let out_file_path = &opts.out_file_path;
+    file_utils::file_must_exist(in_file_path)?;
+    file_utils::file_must_not_exist(out_file_path)?;
+    if let Some(report_file_path) = &opts.report_file_path {
+        file_utils::file_must_not_exist(report_file_path)?;
+    }
+    println!(
+        "Importing lexicon file '{}' from CCXT markets '{}'",
+        out_file_path.display(), in_file_path.display()
+    );
+
+    let exchanges_markets: Vec<CcxtExchangeMarketsJson> = file_utils::read_json_file(in_file_path)?;
+    let (normalisation, fiat_currencies, stable_currencies) = read_definition_files(&opts.definitions)?;
+    let (lexicon, report) = CryptoExchangeLexicon::create_from_ccxt_markets(
+        &exchanges_markets,
+        &normalisation,
+        &fiat_currencies,
+        &stable_currencies,
+    )?;
+    report.warnings.iter().for_each(|warning| println!("{}: {}", "Warning".yellow().bold(), warning));
+    report.skipped.iter().for_each(|skipped| {
+        println!("Skipped '{}' on '{}': {}", skipped.symbol, skipped.exchange, skipped.reason);
+    });
+    let markets: usize = lexicon.exchange_currency_pairs.values().map(|pairs| pairs.len()).sum();
+    println!("Imported {} markets, skipped {}", markets, report.skipped.len());
+    if let Some(report_file_path) = &opts.report_file_path {
+        file_utils::save_json_file(report_file_path, &report)?;
+    }
+    save_lexicon_file(out_file_path, &lexicon)
+}
+
+fn set_networks_lexicon_file(opts: LexiconSetNetworksFiles) -> anyhow::Result<()> {
+    file_utils::file_must_exist(&opts.in_file_lexicon_path)?;
+    file_utils::file_must_exist(&opts.in_file_path)?;
+    file_utils::file_must_not_exist(&opts.out_file_path)?;
+    println!(
+        "Setting networks of lexicon file '{}' from '{}' into '{}'",
+        opts.in_file_lexicon_path.display(), opts.in_file_path.display(), opts.out_file_path.display()
+    );
+
+    let mut lexicon = read_lexicon_file(&opts.in_file_lexicon_path)?;
+    let exchange_networks: Vec<ExchangeCurrencyNetworksJson> = file_utils::read_json_file(&opts.in_file_path)?;
+    let warnings = lexicon.set_currency_networks(&exchange_networks)?;
+    warnings.iter().for_each(|warning| println!("{}: {}", "Warning".yellow().bold(), warning));
+    println!(
+        "Set {} networks for {} exchange currencies",
+        lexicon.networks.len(),
+        lexicon.exchange_currency_networks.len()
+    );
+    save_lexicon_file(&opts.out_file_path, &lexicon)
+}
+
+fn update_lexicon_file(in_file_lexicon_path: &Path, in_file_path: &Path, out_file_path: &Path) -> anyhow::Result<()> {
+    file_utils::file_must_exist(in_file_lexicon_path)?;
+    file_utils::file_must_exist(in_file_path)?;
+    file_utils::file_must_not_exist(out_file_path)?;
+    println!(
+        "Updating lexicon file '{}' from '{}' into '{}'",
+        in_file_lexicon_path.display(), in_file_path.display(), out_file_path.display()
+    );
+
+    let previous = read_lexicon_file(in_file_lexicon_path)?;
+    let json_bytes = file_utils::read_from_file(&in_file_path)?;
+    let exchanges_pairs: Vec<ExchangeSymbolsJson> = serde_json::from_slice(&json_bytes)?;
+    let (lexicon, warnings) = CryptoExchangeLexicon::regenerate_from_previous(previous, &exchanges_pairs)?;
+    warnings.iter().for_each(|warning| println!("{}: {}", "Warning".yellow().bold(), warning));
+    lexicon.deprecated_exchanges().iter().for_each(|e| {
+        println!("Deprecated exchange id '{}' name '{}'", e.0, lexicon.exchange_to_string(e));
+    });
+    lexicon.deprecated_currencies().iter().for_each(|c| {
+        println!("Deprecated currency id '{}' name '{}'", c.0, lexicon.currency_to_string(c));
+    });
+    save_lexicon_file(out_file_path, &lexicon)
+}
+
+fn diff_lexicon_files(a_file_path: &Path, b_file_path: &Path, json: bool) -> anyhow::Result<()> {
+    file_utils::file_must_exist(a_file_path)?;
+    file_utils::file_must_exist(b_file_path)?;
+    let a = read_lexicon_file(a_file_path)?;
+    let b = read_lexicon_file(b_file_path)?;
+    let diff = LexiconDiff::create(&a, &b);
+    if json {
+        println!("{}", serde_json::to_string(&diff)?);
+        return Ok(());
+    }
+    let print_names = |title: &str, names: &[String], added: bool| {
+        names.iter().for_each(|name| {
+            let line = format!("{}{} '{}'", if added { "+ " } else { "- " }, title, name);
+            println!("{}", if added { line.green() } else { line.red() });
+        })
+    };
+    let print_remaps = |title: &str, remaps: &[IdRemap]| {
+        remaps.iter().for_each(|remap| {
+            println!("{}", format!("~ {} '{}' id '{}' -> '{}'", title, remap.name, remap.from, remap.to).yellow());
+        })
+    };
+    print_names("exchange", &diff.added_exchanges, true);
+    print_names("exchange", &diff.removed_exchanges, false);
+    print_remaps("exchange", &diff.exchange_remaps);
+    print_names("currency", &diff.added_currencies, true);
+    print_names("currency", &diff.removed_currencies, false);
+    print_remaps("currency", &diff.currency_remaps);
+    diff.exchange_pairs.iter().for_each(|pairs| {
+        println!("Exchange '{}' pairs", pairs.exchange);
+        print_names("  pair", &pairs.added, true);
+        print_names("  pair", &pairs.removed, false);
+    });
+    if diff.is_exact_match() {
+        println!("Lexicons are an exact match");
+    } else if diff.is_same_domain() {
+        println!("Lexicons have the same domain but different IDs. Networks must be regenerated or migrated");
+    } else {
+        println!("Lexicons have a different domain");
+    }
+    Ok(())
+}
+
+fn verify_lexicon_file(opts: LexiconVerifyFiles) -> anyhow::Result<()> {
+    file_utils::file_must_exist(&opts.in_file_path)?;
+    if let Some(fix_out_file_path) = &opts.fix_out_file_path {
+        file_utils::file_must_not_exist(fix_out_file_path)?;
+    }
+    let lexicon = read_lexicon_file(&opts.in_file_path)?;
+    let report = lexicon.verify();
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report);
+    }
+    if report.is_valid() {
+        return Ok(());
+    }
+    match &opts.fix_out_file_path {
+        Some(fix_out_file_path) if report.is_fixable() => {
+            let lexicon = lexicon.fix();
+            save_lexicon_file(fix_out_file_path, &lexicon)?;
+            println!("Fixed lexicon written to '{}'", fix_out_file_path.display());
+            Ok(())
+        }
+        Some(_) => anyhow::bail!("Lexicon has duplicate names that can't be fixed"),
+        None => anyhow::bail!("Lexicon is invalid"),
+    }
+}
+
+fn exchange_id(lexicon: &CryptoExchangeLexicon, name: &str) -> anyhow::Result<ExchangeID> {
+    lexicon.exchanges.get_id(name).copied().ok_or_else(|| anyhow::anyhow!("No exchange '{}' found", name))
+}
+
+/// The name is normalised with the aliases and the overrides of the exchange
+fn currency_id(lexicon: &CryptoExchangeLexicon, exchange: Option<&str>, name: &str) -> anyhow::Result<CurrencyID> {
+    lexicon
+        .normalised_currency_id(exchange, name)
+        .ok_or_else(|| anyhow::anyhow!("No currency '{}' found", name))
+}
+
+fn currency_names<'a>(lexicon: &'a CryptoExchangeLexicon, currencies: impl Iterator<Item = &'a CurrencyID>) -> Vec<&'a str> {
+    currencies.map(|c| lexicon.currency_to_string(c)).collect()
+}
+
+fn print_currencies<'a>(lexicon: &CryptoExchangeLexicon, currencies: impl Iterator<Item = &'a CurrencyID>) {
+    currencies.for_each(|c| println!("Currency id '{}' name '{}'", c.0, lexicon.currency_to_string(c)));
+}
+
+fn query_lexicon_file(query: LexiconQueryCommand) -> anyhow::Result<()> {
+    use LexiconQueryCommand::*;
+    let in_file_path = match &query {
+        HasExchange { in_file_path, .. }
+        | HasCurrency { in_file_path, .. }
+        | ExchangesWithCurrency { in_file_path, .. }
+        | CurrencyPairs { in_file_path, .. }
+        | HasPair { in_file_path, .. }
+        | CommonCurrencies { in_file_path, .. }
+        | Transfers { in_file_path, .. } => in_file_path,
+    };
+    file_utils::file_must_exist(in_file_path)?;
+    let lexicon = read_lexicon_file(in_file_path)?;
+    match query {
+        HasExchange { exchange, json, .. } => {
+            let has_exchange = lexicon.has_exchange(&exchange);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&has_exchange)?);
+            } else {
+                println!("Lexicon has exchange '{}': {}", exchange, has_exchange);
+            }
+        }
+        HasCurrency { currency, json, .. } => {
+            let has_currency = lexicon.has_currency(&currency);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&has_currency)?);
+            } else {
+                println!("Lexicon has currency '{}': {}", currency, has_currency);
+            }
+        }
+        ExchangesWithCurrency { currency, json, .. } => {
+            let exchanges = lexicon.exchanges_with_currency(&currency_id(&lexicon, None, &currency)?);
+            if json {
+                let names: Vec<&str> = exchanges.iter().map(|e| lexicon.exchange_to_string(e)).collect();
+                println!("{}", serde_json::to_string_pretty(&names)?);
+            } else {
+                exchanges.iter().for_each(|e| {
+                    println!("Exchange id '{}' name '{}'", e.0, lexicon.exchange_to_string(e));
+                });
+            }
+        }
+        CurrencyPairs { exchange, currency, json, .. } => {
+            let e = exchange_id(&lexicon, &exchange)?;
+            let pairs = lexicon.exchange_currency_pairs_with(&e, &currency_id(&lexicon, Some(&exchange), &currency)?);
+            if json {
+                let names: Vec<String> = pairs
+                    .iter()
+                    .map(|pair| lexicon.currency_pair_to_string(pair).replace(" : ", "-"))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&names)?);
+            } else {
+                pairs.iter().for_each(|pair| {
+                    println!(
+                        "currency pair '{} : {}'\tname '{}'",
+                        pair.first.0, pair.second.0, lexicon.currency_pair_to_string(pair)
+                    )
+                });
+            }
+        }
+        HasPair { exchange, pair, json, .. } => {
+            let e = exchange_id(&lexicon, &exchange)?;
+            let (first, second) = pair
+                .split_once('/')
+                .ok_or_else(|| anyhow::anyhow!("Invalid pair '{}'. Expected e.g. 'BTC/USD'", pair))?;
+            // unknown currencies can't be listed on the exchange
+            let has_pair = match (
+                lexicon.normalised_currency_id(Some(&exchange), first),
+                lexicon.normalised_currency_id(Some(&exchange), second),
+            ) {
+                (Some(first), Some(second)) => lexicon.exchange_has_pair(&e, &CurrencyIDPair::new(first, second)),
+                _ => false,
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(&has_pair)?);
+            } else {
+                println!("Exchange '{}' has pair '{}': {}", exchange, pair, has_pair);
+            }
+        }
+        CommonCurrencies { exchanges, json, .. } => {
+            let exchanges: Vec<ExchangeID> = exchanges
+                .iter()
+                .map(|name| exchange_id(&lexicon, name))
+                .collect::<anyhow::Result<_>>()?;
+            let currencies = lexicon.common_currencies(&exchanges);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&currency_names(&lexicon, currencies.iter()))?);
+            } else {
+                print_currencies(&lexicon, currencies.iter());
+            }
+        }
+        Transfers { from_exchange, to_exchange, json, .. } => {
+            let from = exchange_id(&lexicon, &from_exchange)?;
+            let to = exchange_id(&lexicon, &to_exchange)?;
+            let connectivity = lexicon.transfer_connectivity(&from, &to);
+            if json {
+                let map = BTreeMap::from([
+                    ("transferable_currencies", currency_names(&lexicon, connectivity.transferable_currencies.iter())),
+                    ("fiat_currencies", currency_names(&lexicon, connectivity.fiat_currencies.iter())),
+                ]);
+                println!("{}", serde_json::to_string_pretty(&map)?);
+            } else if connectivity.is_connected() {
+                println!("Transferable currencies from '{}' to '{}'", from_exchange, to_exchange);
+                print_currencies(&lexicon, connectivity.transferable_currencies.iter());
+            } else {
+                println!("No transferable currencies from '{}' to '{}'", from_exchange, to_exchange);
+            }
+        }
+    }
+    Ok(())
+}
+
 fn read_lexicon_file_from_command(read_command: &PrintLexiconCommand) -> anyhow::Result<CryptoExchangeLexicon> {
     use PrintLexiconCommand::*;
     let in_file_path = match read_command {
-        Verify { in_file_path } => in_file_path,
         PrintAllCurrencies(o) => &o.in_file_path,
+        PrintFiatCurrencies(o) => &o.in_file_path,
+        PrintStableCurrencies(o) => &o.in_file_path,
         PrintAllExchangesPairs(o) => &o.in_file_path,
         PrintExchanges(o) => &o.in_file_path,
         PrintExchangePairs(o) => &o.in_file_path,
@@ -52,40 +402,47 @@ fn read_lexicon_file_from_command(read_command: &PrintLexiconCommand) -> anyhow:
     Ok(read_lexicon_file(&in_file_path)?)
 }
 
+/// Names only JSON of the `-j` print options as printed by the earlier versions
+fn legacy_json<T: serde::Serialize>(names: &T) -> anyhow::Result<String> {
+    Ok(format!("{}\n", serde_json::to_string_pretty(names)?))
+}
+
+fn view_names<T>(views: &[T], name: impl Fn(&T) -> &str) -> Vec<&str> {
+    views.iter().map(name).collect()
+}
+
 fn print_lexicon_file(print_command: PrintLexiconCommand) -> anyhow::Result<()> {
     let lexicon = read_lexicon_file_from_command(&print_command)?;
-    match print_command {
-        PrintLexiconCommand::Verify { .. } => {
-            let is_valid = lexicon.verify_exchange_currency_pairs();
-            if !is_valid {
-                println!("{}", "Lexicon has invalid exchange pairs".red().bold());
-            }
-        },
-        PrintLexiconCommand::PrintAllCurrencies(opt) => {
-            if opt.json {
-                lexicon.print_all_currencies_in_json_format()
-            }
-            else {
-                lexicon.print_all_currencies()
-            }
-        },
+    let rendered = match print_command {
+        PrintLexiconCommand::PrintAllCurrencies(opt) if opt.output.is_legacy_json() => {
+            legacy_json(&lexicon.currencies.iter_in_order().collect::<Vec<_>>())?
+        }
+        PrintLexiconCommand::PrintAllCurrencies(opt) => render(&lexicon.currencies_view()?, opt.output.format())?,
+        PrintLexiconCommand::PrintFiatCurrencies(opt) if opt.output.is_legacy_json() => {
+            legacy_json(&view_names(&lexicon.fiat_currencies_view(), |c| &c.name))?
+        }
+        PrintLexiconCommand::PrintFiatCurrencies(opt) => render(&lexicon.fiat_currencies_view(), opt.output.format())?,
+        PrintLexiconCommand::PrintStableCurrencies(opt) if opt.output.is_legacy_json() => {
+            legacy_json(&view_names(&lexicon.stable_currencies_view(), |c| &c.name))?
+        }
+        PrintLexiconCommand::PrintStableCurrencies(opt) => {
+            render(&lexicon.stable_currencies_view(), opt.output.format())?
+        }
+        PrintLexiconCommand::PrintAllExchangesPairs(opt) if opt.output.is_legacy_json() => {
+            legacy_json(&lexicon.exchange_pair_names())?
+        }
         PrintLexiconCommand::PrintAllExchangesPairs(opt) => {
-            if opt.json {
-                lexicon.print_all_exchanges_currencies_in_json_format()
-            }
-            else {
-                lexicon.print_all_exchanges_currencies()
-            }
-        },
-        PrintLexiconCommand::PrintExchanges(opt) => {
-            if opt.json {
-                lexicon.print_exchanges_in_json_format()
-            }
-            else {
-                lexicon.print_exchanges()
-            }
-        },
-        PrintLexiconCommand::PrintExchangePairs(exchange) => lexicon.print_exchange_pairs(&exchange.exchange),
+            render(&lexicon.all_exchange_pairs_view(), opt.output.format())?
+        }
+        PrintLexiconCommand::PrintExchanges(opt) if opt.output.is_legacy_json() => {
+            legacy_json(&view_names(&lexicon.exchanges_view(), |e| &e.name))?
+        }
+        PrintLexiconCommand::PrintExchanges(opt) => render(&lexicon.exchanges_view(), opt.output.format())?,
+        PrintLexiconCommand::PrintExchangePairs(opt) => {
+            let exchange = exchange_id(&lexicon, &opt.exchange)?;
+            render(&lexicon.exchange_pairs_view(&exchange)?, opt.output.format())?
+        }
     };
+    print!("{}", rendered);
     Ok(())
-}
\ No newline at end of file
+}
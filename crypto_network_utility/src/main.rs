@@ -28,7 +28,10 @@ fn main() {
     };
     match result {
         Ok(_) => println!("{}", "Success!".green().bold()),
-        Err(err) => println!("{}: {:?}", "Error!".red().bold(), err)
+        Err(err) => {
+            println!("{}: {:?}", "Error!".red().bold(), err);
+            std::process::exit(1);
+        }
     }
 }
 
@@ -7,14 +7,14 @@ use crypto_exchange_types::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet};
-use std::convert::TryInto;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
-use itertools::Itertools;
+use anyhow::Context;
+use colored::*;
 
 
 pub fn handle_network_command(network_command: NetworkCommand) -> anyhow::Result<()> {
@@ -28,11 +28,14 @@ pub fn handle_network_command(network_command: NetworkCommand) -> anyhow::Result
         MergeIntoNetwork { path } => {
             merge_into_network_for_path(&path)
         }
+        Migrate { in_file_network_path, in_file_lexicon_path, out_file_network_path } => {
+            migrate_network_file(&in_file_network_path, &in_file_lexicon_path, &out_file_network_path)
+        }
         PrintAllPaths { path } => {
             print_all_network_paths(&path)
         }
         
-        PrintStatistics { path } => print_network_stats(&path),
+        PrintStatistics { path, names_file_path } => print_network_stats(&path, names_file_path.as_deref()),
     }
 }
 
@@ -73,6 +76,7 @@ impl WorkingSession {
             &path_root.join("search_stop_settings.json"),
             &search_stop_settings,
         )?;
+        file_utils::save_json_file(&path_root.join(LEXICON_CHECKSUM_FILE_NAME), &lexicon_f.checksum())?;
 
         Ok(WorkingSession{
             in_lexicon_file_path: in_file_path,
@@ -89,6 +93,38 @@ fn read_search_stop_settings(file_path: &Path) -> anyhow::Result<SearchStopSetti
     file_utils::read_json_file(&file_path)
 }
 
+const LEXICON_CHECKSUM_FILE_NAME: &str = "lexicon_checksum.json";
+
+/// Checks the working directory lexicon against the checksum stored when the working directory was created.
+/// Working directories created before checksums were stored are not checked.
+fn verify_working_dir_lexicon(path_root: &Path, lexicon_f: &CryptoExchangeLexicon) -> anyhow::Result<()> {
+    let path_root_checksum = path_root.join(LEXICON_CHECKSUM_FILE_NAME);
+    if !path_root_checksum.exists() {
+        println!("{}: No lexicon checksum found in '{}'", "Warning".yellow().bold(), path_root.display());
+        return Ok(());
+    }
+    let checksum: String = file_utils::read_json_file(&path_root_checksum)?;
+    lexicon_f.verify_checksum(&checksum)?;
+    Ok(())
+}
+
+/// Fails if the chunk was generated from a different lexicon. Chunks created before checksums were stored
+/// are reported and not checked.
+fn verify_chunk_lexicon(
+    chunk_path: &Path,
+    optimized_paths: &OptimizedPreCalcedPaths,
+    lexicon_f: &CryptoExchangeLexicon,
+) -> anyhow::Result<()> {
+    if optimized_paths.lexicon_checksum.is_empty() {
+        println!("{}: No lexicon checksum found in chunk '{}'", "Warning".yellow().bold(), chunk_path.display());
+        return Ok(());
+    }
+    lexicon_f
+        .verify_checksum(&optimized_paths.lexicon_checksum)
+        .with_context(|| format!("Chunk '{}' was generated from a different lexicon", chunk_path.display()))?;
+    Ok(())
+}
+
 fn generate_network_with_lexicon_file(opts: GenerateNetworkInOutFile) -> anyhow::Result<()> {
     let working_session = WorkingSession::try_init(opts, "paths")?;
     generate_network_paths_with_lexicon_file(
@@ -107,6 +143,16 @@ fn resume_network_generation_for_path(resume_file_path: &Path, max_chunk_size: O
     let path_root_lexicon = path_root.join("lexicon.lex");
     let path_root_search_stop_settings = path_root.join("search_stop_settings.json");
     let lexicon_f = lexicon::read_lexicon_file(&path_root_lexicon)?;
+    verify_working_dir_lexicon(path_root, &lexicon_f)?;
+    // the existing chunks are skipped by the search so they must match the lexicon
+    let path_root_chunks = path_root.join("paths");
+    if path_root_chunks.exists() {
+        for p in fs::read_dir(&path_root_chunks)? {
+            let p = p?;
+            let optimized_paths: OptimizedPreCalcedPaths = file_utils::read_cbor_lz4_file(&p.path())?;
+            verify_chunk_lexicon(&p.path(), &optimized_paths, &lexicon_f)?;
+        }
+    }
 
     let search_stop_settings = read_search_stop_settings(&path_root_search_stop_settings)?;
     generate_network_paths_with_lexicon_file(
@@ -188,10 +234,17 @@ fn generate_network_paths_with_lexicon_file(
                 currency_pairs.iter().map(|p| *p).collect();
             n.add_pairs(*exchange, &currency_pairs_vec);
         });
+        lexicon_f.exchange_currency_networks.iter().for_each(|(ec, networks)| {
+            let networks: Vec<NetworkID> = networks.iter().copied().collect();
+            n.add_currency_networks(ec.exchange, ec.currency, &networks);
+        });
         n.update_exchange_hubs();
+        n.invalid_markets().iter().for_each(|err| println!("{}: {}. The market is skipped", "Warning".yellow().bold(), err));
+        n.one_sided_transfers().iter().for_each(|err| println!("{}: {}. The transfer is skipped", "Warning".yellow().bold(), err));
         n
     };
 
+    let lexicon_checksum = lexicon_f.checksum();
     let chunk_size = max_chunk_size.unwrap_or(all_targets.len());
     let path_root_chunks = Path::new(&out_file_path).join("paths");
     for chunk in all_targets.chunks(chunk_size) {
@@ -208,38 +261,32 @@ fn generate_network_paths_with_lexicon_file(
                 continue;
             }
 
-            let unknown_paths: Vec<_> = target_paths.into_iter().collect();
-            let (tr_7_paths, unknown_paths): (Vec<ArbitragePath7Nodes>, Vec<ArbitragePath>) = unknown_paths.into_iter().map(|n_path| n_path.try_into()).partition_result();
-            let (tr_11_paths, unknown_paths): (Vec<_>, Vec<ArbitragePath>) = unknown_paths.into_iter().map(|n_path| n_path.try_into()).partition_result();
-            let (tx_only_3pairs_paths, unknown_paths): (Vec<_>, Vec<ArbitragePath>) = unknown_paths.into_iter().map(|n_path| n_path.try_into()).partition_result();
-            let (tx_only_5pairs_paths, unknown_paths): (Vec<_>, Vec<ArbitragePath>) = unknown_paths.into_iter().map(|n_path| n_path.try_into()).partition_result();
-
-            let tx_only_3pairs_paths = merge_reversed_paths(tx_only_3pairs_paths);
-            let tx_only_5pairs_paths = merge_reversed_paths(tx_only_5pairs_paths);
-
-            let optimized_paths = OptimizedPreCalcedPaths {
-                id,
-                tr_7_paths: tr_7_paths.into(),
-                tr_11_paths: tr_11_paths.into(),
-                tx_only_3pairs_paths: tx_only_3pairs_paths.into(),
-                tx_only_5pairs_paths: tx_only_5pairs_paths.into(),
-                unknown_paths: unknown_paths.into(),
-            };
+            let optimized_paths = OptimizedPreCalcedPaths::create(id, target_paths, lexicon_checksum.clone())?;
             file_utils::save_cbor_lz4_file(&out_file_path_chunk, &optimized_paths)?;
         }
     }
     
     stop_c.store(true, Ordering::SeqCst);
     progress_thread.join().unwrap();
+    net.search_errors().iter().for_each(|err| println!("{}: {}", "Warning".yellow().bold(), err));
 
     Ok(())
 }
 
 
-fn print_network_stats(working_dir_root_path: &Path) -> anyhow::Result<()> {
+fn print_network_stats(working_dir_root_path: &Path, names_file_path: Option<&Path>) -> anyhow::Result<()> {
     file_utils::file_must_exist(&working_dir_root_path)?;
     println!("Stats for network paths '{}'", &working_dir_root_path.display());
 
+    let names_bytes = match names_file_path {
+        Some(names_file_path) => {
+            file_utils::file_must_exist(names_file_path)?;
+            Some(file_utils::read_from_file(&names_file_path)?)
+        }
+        None => None,
+    };
+    let names = names_bytes.as_deref().map(lexicon::read_frozen_lexicon_names).transpose()?;
+
     let paths = fs::read_dir(&working_dir_root_path)?;
     let mut sum = OptimizedPreCalcedPathsStats::default();
     for p in paths {
@@ -247,19 +294,28 @@ fn print_network_stats(working_dir_root_path: &Path) -> anyhow::Result<()> {
 
         let optimized_paths: OptimizedPreCalcedPaths = file_utils::read_cbor_lz4_file(&p.path())?;
         let stat = optimized_paths.stats();
+        println!("format version {}", optimized_paths.format_version);
         println!("{}", p.path().display());
         println!("id {:?}", &optimized_paths.id);
+        if let Some(names) = &names {
+            if !optimized_paths.lexicon_checksum.is_empty() && optimized_paths.lexicon_checksum != names.lexicon_checksum {
+                return Err(CryptoTypesError::LexiconChecksumMismatch(
+                    names.lexicon_checksum.clone(),
+                    optimized_paths.lexicon_checksum.clone(),
+                ))
+                .with_context(|| format!("Chunk '{}' was generated from a different lexicon", p.path().display()));
+            }
+            println!(
+                "target '{}' on '{}'",
+                names.currency_to_string(&optimized_paths.id.currency),
+                names.exchange_to_string(&optimized_paths.id.exchange)
+            );
+        }
         println!("{:?}", stat);
         println!("");
         println!("");
 
-        sum.estimated_size_in_bytes += stat.estimated_size_in_bytes;
-        sum.tr_7_paths += stat.tr_7_paths;
-        sum.tr_11_paths += stat.tr_11_paths;
-        sum.tx_only_3pairs_paths += stat.tx_only_3pairs_paths;
-        sum.tx_only_5pairs_paths += stat.tx_only_5pairs_paths;
-        sum.unknown_paths += stat.unknown_paths;
-        
+        sum.add(&stat);
     }
 
     println!("SUM:");
@@ -276,6 +332,8 @@ fn merge_into_network_for_path(working_dir_root_path: &Path) -> anyhow::Result<(
     let path_root_lexicon = path_root.join("lexicon.lex");
     let path_root_search_stop_settings = path_root.join("search_stop_settings.json");
     let lexicon_f = lexicon::read_lexicon_file(&path_root_lexicon)?;
+    verify_working_dir_lexicon(path_root, &lexicon_f)?;
+    let lexicon_checksum = lexicon_f.checksum();
 
     let search_stop_settings = read_search_stop_settings(&path_root_search_stop_settings)?;
 
@@ -285,7 +343,10 @@ fn merge_into_network_for_path(working_dir_root_path: &Path) -> anyhow::Result<(
     let paths = fs::read_dir(&path_root_chunks)?;
     for p in paths {
         let p = p?;
-        let optimized_paths: OptimizedPreCalcedPaths = file_utils::read_cbor_lz4_file(&p.path())?;
+        let optimized_paths: OptimizedPreCalcedPaths = file_utils::read_cbor_lz4_file(&p.path())
+            .with_context(|| format!("Unable to read chunk '{}'", p.path().display()))?;
+        verify_chunk_lexicon(&p.path(), &optimized_paths, &lexicon_f)
+            .with_context(|| format!("Refusing to merge chunk '{}'", p.path().display()))?;
         merge_pre_calced_paths.insert(optimized_paths.id, optimized_paths);
     }
     
@@ -294,16 +355,55 @@ fn merge_into_network_for_path(working_dir_root_path: &Path) -> anyhow::Result<(
         lexicon: lexicon_f,
         pre_calced_paths: merge_pre_calced_paths,
         search_stop_settings: search_stop_settings,
+        lexicon_checksum,
     };
     file_utils::save_cbor_lz4_file(&lexicon_network_paths, &network_with_lexicon)?;
 
     Ok(())
 }
 
+fn migrate_network_file(
+    in_file_network_path: &Path,
+    in_file_lexicon_path: &Path,
+    out_file_network_path: &Path,
+) -> anyhow::Result<()> {
+    file_utils::file_must_exist(in_file_network_path)?;
+    file_utils::file_must_exist(in_file_lexicon_path)?;
+    file_utils::file_must_not_exist(out_file_network_path)?;
+    println!(
+        "Migrating network file '{}' to lexicon '{}' into '{}'",
+        in_file_network_path.display(), in_file_lexicon_path.display(), out_file_network_path.display()
+    );
+
+    let network_with_lexicon = OptimizedNetworkWithLexicon::read_from_file(&in_file_network_path)?;
+    let lexicon_f = lexicon::read_lexicon_file(in_file_lexicon_path)?;
+    let (migrated, report) = network_with_lexicon.migrate(lexicon_f);
+    println!("Kept paths: {}", report.kept_paths);
+    println!("Dropped paths: {}", report.dropped_paths);
+    report.dropped_targets.iter().for_each(|target| {
+        println!(
+            "Dropped target '{}' '{}'",
+            network_with_lexicon.lexicon.exchange_to_string(&target.exchange),
+            network_with_lexicon.lexicon.currency_to_string(&target.currency)
+        );
+    });
+    report.targets_to_research.iter().for_each(|target| {
+        println!(
+            "Target to search again '{}' '{}'",
+            migrated.lexicon.exchange_to_string(&target.exchange),
+            migrated.lexicon.currency_to_string(&target.currency)
+        );
+    });
+    if !report.is_complete {
+        println!("The new lexicon has new markets. Regenerate the network to find all of the new paths");
+    }
+    file_utils::save_cbor_lz4_file(&out_file_network_path, &migrated)
+}
+
 fn print_all_network_paths(network_lexicon_path: &Path) -> anyhow::Result<()> {
     let (lexicon, pre_calced_paths) = {
         
-        let network_with_lexicon: OptimizedNetworkWithLexicon = file_utils::read_cbor_lz4_file(&network_lexicon_path)?;
+        let network_with_lexicon = OptimizedNetworkWithLexicon::read_from_file(&network_lexicon_path)?;
         (
             network_with_lexicon.lexicon,
             network_with_lexicon.pre_calced_paths,
@@ -313,33 +413,31 @@ fn print_all_network_paths(network_lexicon_path: &Path) -> anyhow::Result<()> {
     pre_calced_paths.into_iter().for_each(|pair| {
 
         let (target, optimized_paths) = pair;
-        let tr_7_paths: BTreeSet<_> = optimized_paths.tr_7_paths.into_iter().flatten().collect();
-        tr_7_paths.into_iter().for_each(|path| {
-            path.print_path_all(&lexicon);
+        let mut unknown_paths = BTreeSet::new();
+        optimized_paths.arbitrage_paths().for_each(|path| match path {
+            Ok(path) => {
+                unknown_paths.insert(path);
+            }
+            Err(err) => println!("{}: skipped path: {}", "Warning".yellow().bold(), err),
         });
-
-        let tr_11_paths: BTreeSet<_> = optimized_paths.tr_11_paths.into_iter().flatten().collect();
-        tr_11_paths.into_iter().for_each(|path| {
-            path.print_path_all(&lexicon);
+        optimized_paths.transfer_paths().for_each(|path| match path {
+            Ok(path) => path.print_path_all(&lexicon),
+            Err(err) => println!("{}: skipped path: {}", "Warning".yellow().bold(), err),
         });
 
-        let tx_only_3pairs_paths: BTreeSet<_> = optimized_paths.tx_only_3pairs_paths.into_iter().flatten().collect();
-        tx_only_3pairs_paths.into_iter().for_each(|path| {
-            let (first, second) = interpolate_reversed_paths(target.exchange, target.currency, &path.0);
-            first.print_path_all(&lexicon);
-            println!("--");
-            second.print_path_all(&lexicon);
-        });
-
-        let tx_only_5pairs_paths: BTreeSet<_> = optimized_paths.tx_only_5pairs_paths.into_iter().flatten().collect();
-        tx_only_5pairs_paths.into_iter().for_each(|path| {
-            let (first, second) = interpolate_reversed_paths(target.exchange, target.currency, &path.0);
+        optimized_paths.tx_only_paths.iter().for_each(|pairs| {
+            let (first, second) = match interpolate_reversed_paths(target.exchange, target.currency, pairs) {
+                Ok(paths) => paths,
+                Err(err) => {
+                    println!("{}: skipped path {:?}: {}", "Warning".yellow().bold(), pairs, err);
+                    return;
+                }
+            };
             first.print_path_all(&lexicon);
             println!("--");
             second.print_path_all(&lexicon);
         });
 
-        let unknown_paths: BTreeSet<_> = optimized_paths.unknown_paths.into_iter().flatten().collect();
         unknown_paths.into_iter().for_each(|path| {
             path.print_path_all(&lexicon);
         });
@@ -83,6 +83,21 @@ where
     cbor_from_slice(&dec)
 }
 
+pub fn bincode_to_vec<T>(value: &T) -> Result<Vec<u8>, CryptoSerializerError>
+where
+    T: Serialize,
+{
+    bincode::serialize(value).map_err(|err| CryptoSerializerError::BincodeSerializeError(err.to_string()))
+}
+
+/// Borrowed strings of `T` reference the slice e.g. a memory mapped file
+pub fn bincode_from_slice<'a, T>(slice: &'a [u8]) -> Result<T, CryptoSerializerError>
+where
+    T: Deserialize<'a>,
+{
+    bincode::deserialize(slice).map_err(|err| CryptoSerializerError::BincodeDeserializeError(err.to_string()))
+}
+
 fn lzzzz_cbor_from_file_err(err: String) -> CryptoSerializerError {
     CryptoSerializerError::LzzzzDecompressionError(err)
 }
@@ -0,0 +1,379 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+/// Concatenates the keys into an arena. The key `i` is `arena[offsets[i]..offsets[i + 1]]`.
+fn pack<'k>(keys: impl Iterator<Item = &'k str>) -> Result<(String, Vec<u32>), StringToIntMapperError> {
+    let mut arena = String::new();
+    let mut offsets = vec![0];
+    for key in keys {
+        arena.push_str(key);
+        let offset = u32::try_from(arena.len()).map_err(|_| StringToIntMapperError::ArenaTooLarge(arena.len()))?;
+        offsets.push(offset);
+    }
+    Ok((arena, offsets))
+}
+
+fn validate_offsets(arena: &str, offsets: &[u32]) -> Result<(), StringToIntMapperError> {
+    let invalid = |reason: String| Err(StringToIntMapperError::InvalidFrozenMapper(reason));
+    if offsets.first() != Some(&0) {
+        return invalid("The offsets must start at 0".to_string());
+    }
+    if let Some(window) = offsets.windows(2).find(|window| window[0] > window[1]) {
+        return invalid(format!("Offset {} is followed by the smaller offset {}", window[0], window[1]));
+    }
+    if let Some(offset) = offsets.iter().find(|offset| !arena.is_char_boundary(**offset as usize)) {
+        return invalid(format!("Offset {} is out of the arena or not on a char boundary", offset));
+    }
+    Ok(())
+}
+
+/// Serialised layout of the frozen mapper. Deserialisation goes through it so the stored offsets and IDs
+/// are validated before the lookups index the arenas with them.
+#[derive(Deserialize)]
+struct StoredFrozenStringToIntMapper<'a, T> {
+    #[serde(borrow)]
+    arena: Cow<'a, str>,
+    offsets: Vec<u32>,
+    sorted_ids: Vec<T>,
+    #[serde(borrow)]
+    normalised_arena: Cow<'a, str>,
+    normalised_offsets: Vec<u32>,
+    removed_ids: Vec<T>,
+    reuse_policy: IdReusePolicy,
+    normaliser: KeyNormaliser,
+}
+
+/// Compact read only mapper. All keys are stored once in a single string arena in ID order,
+/// key lookup is a binary search over a second arena of the normalised keys in sorted order.
+/// Deserialisation borrows the arena from the input buffer (e.g. a memory mapped bincode file) when possible
+/// and fails for corrupted mappers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    try_from = "StoredFrozenStringToIntMapper<'a, T>",
+    bound(deserialize = "T: Deserialize<'de> + Default + Copy + PartialEq + GetCurrentAndIncrementStringToIntMapperId")
+)]
+pub struct FrozenStringToIntMapper<'a, T> {
+    #[serde(borrow)]
+    arena: Cow<'a, str>,
+    offsets: Vec<u32>,             // key of ID `i` is `arena[offsets[i]..offsets[i + 1]]`
+    sorted_ids: Vec<T>,            // IDs sorted by their normalised keys
+    #[serde(borrow)]
+    normalised_arena: Cow<'a, str>,
+    normalised_offsets: Vec<u32>,  // normalised key of `sorted_ids[i]`
+    removed_ids: TombstonedIds<T>, // tombstoned IDs
+    reuse_policy: IdReusePolicy,
+    normaliser: KeyNormaliser,
+}
+
+impl<'a, T> FrozenStringToIntMapper<'a, T>
+where
+    T: Default + Copy + PartialEq + GetCurrentAndIncrementStringToIntMapperId,
+{
+    /// Packs the keys of the mapper into the arenas. Fails if the keys don't fit the 4GB arena.
+    pub fn from_mapper(mapper: &StringToIntMapper<T, Reading>) -> Result<Self, StringToIntMapperError> {
+        let (arena, offsets) = pack(mapper.added_in_order.iter().map(|key| key.as_str()))?;
+        let mut sorted: Vec<(String, T)> = mapper
+            .added_in_order
+            .iter()
+            .map(|key| {
+                let normalised = mapper.normaliser.normalise(key).into_owned();
+                let id = mapper.keys_mapped_to[&normalised];
+                (normalised, id)
+            })
+            .collect();
+        sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let (normalised_arena, normalised_offsets) = pack(sorted.iter().map(|(normalised, _)| normalised.as_str()))?;
+        Ok(FrozenStringToIntMapper {
+            arena: Cow::Owned(arena),
+            offsets,
+            sorted_ids: sorted.into_iter().map(|(_, id)| id).collect(),
+            normalised_arena: Cow::Owned(normalised_arena),
+            normalised_offsets,
+            removed_ids: mapper.removed_ids_in_order.clone(),
+            reuse_policy: mapper.reuse_policy,
+            normaliser: mapper.normaliser,
+        })
+    }
+
+    /// Restores the editable mapper with the same IDs
+    pub fn to_mapper(&self) -> Result<StringToIntMapper<T, Reading>, StringToIntMapperError> {
+        let mut mapper = StringToIntMapper::<T, Editing>::new_with_options(self.reuse_policy, self.normaliser);
+        for key in self.keys() {
+            mapper.add(key)?;
+        }
+        mapper.removed_ids_in_order = self.removed_ids.clone();
+        Ok(mapper.to_reader())
+    }
+
+    /// Detaches the mapper from the deserialisation buffer
+    pub fn into_owned(self) -> FrozenStringToIntMapper<'static, T> {
+        FrozenStringToIntMapper {
+            arena: Cow::Owned(self.arena.into_owned()),
+            offsets: self.offsets,
+            sorted_ids: self.sorted_ids,
+            normalised_arena: Cow::Owned(self.normalised_arena.into_owned()),
+            normalised_offsets: self.normalised_offsets,
+            removed_ids: self.removed_ids,
+            reuse_policy: self.reuse_policy,
+            normaliser: self.normaliser,
+        }
+    }
+
+    /// Checks the offsets and IDs against the arenas. The lookups index the arenas with the offsets
+    /// and binary search the normalised keys so every ID must be stored once under its own normalised key.
+    fn validate(&self) -> Result<(), StringToIntMapperError> {
+        let invalid = |reason: String| Err(StringToIntMapperError::InvalidFrozenMapper(reason));
+        validate_offsets(&self.arena, &self.offsets)?;
+        validate_offsets(&self.normalised_arena, &self.normalised_offsets)?;
+        let keys = self.offsets.len() - 1;
+        if self.normalised_offsets.len() != self.offsets.len() {
+            return invalid(format!(
+                "Expected {} normalised keys but found {}",
+                keys,
+                self.normalised_offsets.len() - 1
+            ));
+        }
+        if self.sorted_ids.len() != keys {
+            return invalid(format!("Expected {} sorted IDs but found {}", keys, self.sorted_ids.len()));
+        }
+        if let Some(id) = self
+            .sorted_ids
+            .iter()
+            .chain(self.removed_ids.as_slice())
+            .find(|id| id.to_usize() >= keys)
+        {
+            return invalid(format!("ID {} is out of range of the {} keys", id.to_usize(), keys));
+        }
+        let mut seen = vec![false; keys];
+        if let Some(id) = self.sorted_ids.iter().find(|id| std::mem::replace(&mut seen[id.to_usize()], true)) {
+            return invalid(format!("ID {} is duplicated", id.to_usize()));
+        }
+        if let Some(position) = (1..keys).find(|i| self.normalised_key(i - 1) >= self.normalised_key(*i)) {
+            return invalid(format!("Normalised key {} isn't sorted after the previous key", position));
+        }
+        if let Some((position, id)) = self
+            .sorted_ids
+            .iter()
+            .enumerate()
+            .find(|(position, id)| self.normaliser.normalise(self.key(id.to_usize())) != self.normalised_key(*position))
+        {
+            return invalid(format!("ID {} at position {} doesn't match its normalised key", id.to_usize(), position));
+        }
+        Ok(())
+    }
+
+    /// True if the arenas reference the deserialisation buffer
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.arena, Cow::Borrowed(_)) && matches!(self.normalised_arena, Cow::Borrowed(_))
+    }
+
+    fn key(&self, index: usize) -> &str {
+        &self.arena[self.offsets[index] as usize..self.offsets[index + 1] as usize]
+    }
+
+    fn normalised_key(&self, position: usize) -> &str {
+        &self.normalised_arena[self.normalised_offsets[position] as usize..self.normalised_offsets[position + 1] as usize]
+    }
+
+    /// All keys including the tombstoned ones in ID order
+    fn keys(&self) -> impl Iterator<Item = &str> + '_ {
+        (0..self.offsets.len().saturating_sub(1)).map(move |index| self.key(index))
+    }
+
+    /// ID of the key including the tombstoned keys
+    fn find(&self, key: &str) -> Option<T> {
+        let key = self.normaliser.normalise(key);
+        let (mut low, mut high) = (0, self.sorted_ids.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.normalised_key(mid).cmp(key.as_ref()) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Some(self.sorted_ids[mid]),
+            }
+        }
+        None
+    }
+
+    /// ID of a live key. Removed keys don't resolve.
+    pub fn get_id(&self, key: &str) -> Option<T> {
+        self.find(key).filter(|id| !self.removed_ids.contains(id))
+    }
+
+    pub fn get_key(&self, id: &T) -> Option<MappedKey<'_>> {
+        let index = id.to_usize();
+        if index + 1 >= self.offsets.len() {
+            return None;
+        }
+        let key = self.key(index);
+        if self.removed_ids.contains(id) {
+            return Some(MappedKey::Tombstoned(key));
+        }
+        Some(MappedKey::Live(key))
+    }
+
+    pub fn is_removed(&self, key: &str) -> bool {
+        self.find(key).is_some_and(|id| self.removed_ids.contains(&id))
+    }
+
+    /// Live keys in ID order
+    pub fn iter_in_order(&self) -> impl Iterator<Item = &str> + '_ {
+        self.iter_with_status().filter_map(MappedKey::live)
+    }
+
+    /// Every key including the tombstoned ones in ID order. The position is the ID.
+    pub fn iter_with_status(&self) -> impl Iterator<Item = MappedKey<'_>> + '_ {
+        self.keys().enumerate().map(move |(id, key)| {
+            if self.removed_ids.contains_index(id) {
+                MappedKey::Tombstoned(key)
+            } else {
+                MappedKey::Live(key)
+            }
+        })
+    }
+
+    pub fn removed_ids_in_order(&self) -> &[T] {
+        self.removed_ids.as_slice()
+    }
+
+    /// Number of live keys
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1) - self.removed_ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a, T> TryFrom<StoredFrozenStringToIntMapper<'a, T>> for FrozenStringToIntMapper<'a, T>
+where
+    T: Default + Copy + PartialEq + GetCurrentAndIncrementStringToIntMapperId,
+{
+    type Error = StringToIntMapperError;
+
+    fn try_from(stored: StoredFrozenStringToIntMapper<'a, T>) -> Result<Self, Self::Error> {
+        let keys = stored.offsets.len().saturating_sub(1);
+        let removed_ids =
+            TombstonedIds::from_ids(stored.removed_ids, keys).map_err(StringToIntMapperError::InvalidFrozenMapper)?;
+        let frozen = FrozenStringToIntMapper {
+            arena: stored.arena,
+            offsets: stored.offsets,
+            sorted_ids: stored.sorted_ids,
+            normalised_arena: stored.normalised_arena,
+            normalised_offsets: stored.normalised_offsets,
+            removed_ids,
+            reuse_policy: stored.reuse_policy,
+            normaliser: stored.normaliser,
+        };
+        frozen.validate()?;
+        Ok(frozen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frozen_mapper_test() {
+        let mut mapper = StringToIntMapper::<i32, Editing>::new_with_normaliser(KeyNormaliser::symbols());
+        ["ETH", "BTC", "USD", "XRP"].iter().for_each(|key| {
+            mapper.add(key).unwrap();
+        });
+        mapper.remove("USD");
+        let mapper = mapper.to_reader();
+        let frozen = FrozenStringToIntMapper::from_mapper(&mapper).unwrap();
+        assert_eq!(Some(0), frozen.get_id("ETH"));
+        assert_eq!(Some(3), frozen.get_id("xrp "));
+        assert_eq!(None, frozen.get_id("USD"));
+        assert_eq!(None, frozen.get_id("N/A"));
+        assert!(frozen.is_removed("USD"));
+        assert_eq!(Some(MappedKey::Tombstoned("USD")), frozen.get_key(&2));
+        assert_eq!(None, frozen.get_key(&4));
+        assert_eq!(vec!["ETH", "BTC", "XRP"], frozen.iter_in_order().collect::<Vec<_>>());
+        assert_eq!(3, frozen.len());
+
+        let bytes = bincode::serialize(&frozen).unwrap();
+        let borrowed: FrozenStringToIntMapper<i32> = bincode::deserialize(&bytes).unwrap();
+        assert!(borrowed.is_borrowed());
+        assert_eq!(Some(1), borrowed.get_id("BTC"));
+        assert!(!borrowed.into_owned().is_borrowed());
+
+        assert_eq!(Ok(()), frozen.validate());
+
+        let restored = frozen.to_mapper().unwrap();
+        assert_eq!(
+            mapper.iter_with_status().collect::<Vec<_>>(),
+            restored.iter_with_status().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(&3), restored.get_id("XRP"));
+    }
+
+    #[test]
+    fn validate_test() {
+        let mut mapper = StringToIntMapper::<i32, Editing>::new();
+        ["ETH", "BTC", "€UR"].iter().for_each(|key| {
+            mapper.add(key).unwrap();
+        });
+        let frozen = FrozenStringToIntMapper::from_mapper(&mapper.to_reader()).unwrap();
+        let invalid = |corrupt: fn(&mut FrozenStringToIntMapper<i32>)| {
+            let mut frozen = frozen.clone();
+            corrupt(&mut frozen);
+            matches!(frozen.validate(), Err(StringToIntMapperError::InvalidFrozenMapper(_)))
+        };
+        assert!(invalid(|frozen| frozen.offsets.clear()));
+        assert!(invalid(|frozen| frozen.offsets.swap(1, 2)));
+        assert!(invalid(|frozen| *frozen.offsets.last_mut().unwrap() += 1));
+        // inside of the multi byte '€'
+        assert!(invalid(|frozen| frozen.offsets[3] = 7));
+        assert!(invalid(|frozen| frozen.sorted_ids[0] = 3));
+        assert!(invalid(|frozen| *frozen.normalised_offsets.last_mut().unwrap() += 1));
+        assert!(invalid(|frozen| {
+            frozen.normalised_offsets.pop();
+        }));
+        assert!(invalid(|frozen| {
+            frozen.sorted_ids.pop();
+        }));
+        assert!(invalid(|frozen| {
+            frozen.removed_ids.insert(5);
+        }));
+        // the binary search would return the wrong IDs
+        assert!(invalid(|frozen| frozen.sorted_ids.swap(0, 1)));
+        assert!(invalid(|frozen| frozen.sorted_ids[1] = frozen.sorted_ids[0]));
+        assert!(invalid(|frozen| frozen.normalised_offsets.swap(1, 2)));
+    }
+
+    #[test]
+    fn deserialise_validates_test() {
+        let mut mapper = StringToIntMapper::<i32, Editing>::new();
+        ["ETH", "BTC", "€UR"].iter().for_each(|key| {
+            mapper.add(key).unwrap();
+        });
+        mapper.remove("BTC");
+        let frozen = FrozenStringToIntMapper::from_mapper(&mapper.to_reader()).unwrap();
+        let deserialised = |frozen: &FrozenStringToIntMapper<i32>| {
+            let json = serde_json::to_string(frozen).unwrap();
+            serde_json::from_str::<FrozenStringToIntMapper<i32>>(&json).map(|frozen| frozen.into_owned())
+        };
+        assert_eq!(Some(2), deserialised(&frozen).unwrap().get_id("€UR"));
+
+        let mut corrupted = frozen.clone();
+        *corrupted.offsets.last_mut().unwrap() += 1;
+        assert!(deserialised(&corrupted).is_err());
+        let mut corrupted = frozen.clone();
+        corrupted.sorted_ids.swap(0, 2);
+        assert!(deserialised(&corrupted).is_err());
+
+        // a duplicated tombstone and a tombstone out of range are rejected before the bitset is allocated
+        let mut json = serde_json::to_value(&frozen).unwrap();
+        for removed_ids in [serde_json::json!([1, 1]), serde_json::json!([u32::MAX])] {
+            json["removed_ids"] = removed_ids;
+            let json = json.to_string();
+            assert!(serde_json::from_str::<FrozenStringToIntMapper<i32>>(&json).is_err());
+        }
+    }
+}
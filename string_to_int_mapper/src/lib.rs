@@ -1,25 +1,147 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::marker::PhantomData;
-use std::slice::Iter;
+use thiserror::Error;
 
-#[derive(Debug, Serialize, Deserialize)]
+mod frozen;
+pub use frozen::*;
+mod merge;
+pub use merge::*;
+mod normaliser;
+pub use normaliser::*;
+mod tombstones;
+use tombstones::TombstonedIds;
+pub use string_to_int_mapper_derive::MapperId;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Editing;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Reading;
 
-pub trait GetCurrentAndIncrementStringToIntMapperId {
-    fn get_and_increment(&mut self) -> Self;
+pub trait GetCurrentAndIncrementStringToIntMapperId: Sized {
+    /// The ID after this one. None for the last ID of the ID type.
+    fn next_id(&self) -> Option<Self>;
     fn to_usize(&self) -> usize;
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct StringToIntMapper<T: Default + GetCurrentAndIncrementStringToIntMapperId, State = Editing> {
-    set_next_id: T,                     // starts at 0
-    added_in_order: Vec<String>,        // to retrive string by ID
-    keys_mapped_to: HashMap<String, T>, // to get ID from String
+#[derive(Error, Debug, PartialEq)]
+pub enum StringToIntMapperError {
+    #[error("Compaction is not allowed. The mapper ID reuse policy is '{0:?}'")]
+    CompactionNotAllowed(IdReusePolicy),
+    #[error("ID capacity exhausted. No ID left for key '{0}'")]
+    CapacityExhausted(String),
+    #[error("The keys don't fit the frozen mapper arena. The arena would be {0} bytes")]
+    ArenaTooLarge(usize),
+    #[error("Invalid frozen mapper. {0}")]
+    InvalidFrozenMapper(String),
+    #[error("Invalid mapper. {0}")]
+    InvalidMapper(String),
+}
+
+/// What happens with the IDs of removed keys
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdReusePolicy {
+    /// Removed keys keep their IDs forever. IDs that were handed out stay valid.
+    #[default]
+    Never,
+    /// Removed keys keep their IDs until `compact` is called. Compaction reassigns the IDs of the live keys.
+    AfterCompaction,
+}
+
+/// Key of an ID. Removed keys are tombstoned, they keep their ID but don't resolve anymore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappedKey<'a> {
+    Live(&'a str),
+    Tombstoned(&'a str),
+}
+
+impl<'a> MappedKey<'a> {
+    pub fn key(&self) -> &'a str {
+        match self {
+            MappedKey::Live(key) | MappedKey::Tombstoned(key) => key,
+        }
+    }
+
+    pub fn live(self) -> Option<&'a str> {
+        match self {
+            MappedKey::Live(key) => Some(key),
+            MappedKey::Tombstoned(_) => None,
+        }
+    }
+
+    pub fn is_tombstoned(&self) -> bool {
+        matches!(self, MappedKey::Tombstoned(_))
+    }
+}
+
+/// Old to new ID table returned by compaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdRemapTable<T> {
+    remapped: Vec<(T, T)>,   // (old ID, new ID) of every live key in ID order
+    removed: Vec<T>,         // old IDs of the tombstoned keys that were dropped
+    new_ids: Vec<Option<T>>, // new ID indexed by the old ID
+}
+
+impl<T: Copy + GetCurrentAndIncrementStringToIntMapperId> IdRemapTable<T> {
+    pub(crate) fn new(remapped: Vec<(T, T)>, removed: Vec<T>) -> Self {
+        let mut new_ids = vec![None; remapped.iter().map(|(old, _)| old.to_usize() + 1).max().unwrap_or(0)];
+        for (old, new) in &remapped {
+            new_ids[old.to_usize()] = Some(*new);
+        }
+        IdRemapTable {
+            remapped,
+            removed,
+            new_ids,
+        }
+    }
+
+    pub fn get(&self, old: &T) -> Option<T> {
+        self.new_ids.get(old.to_usize()).copied().flatten()
+    }
+}
+
+impl<T> IdRemapTable<T> {
+    /// (old ID, new ID) of every live key in ID order
+    pub fn remapped(&self) -> &[(T, T)] {
+        &self.remapped
+    }
+
+    /// Old IDs of the tombstoned keys that were dropped
+    pub fn removed(&self) -> &[T] {
+        &self.removed
+    }
+}
+
+/// Serialised layout of the mapper. Deserialisation goes through it so the tombstones are checked
+/// against the added keys before the bitset is allocated.
+#[derive(Deserialize)]
+struct StoredStringToIntMapper<T> {
+    set_next_id: Option<T>,
+    added_in_order: Vec<String>,
+    keys_mapped_to: HashMap<String, T>,
     removed_ids_in_order: Vec<T>,
+    #[serde(default)]
+    reuse_policy: IdReusePolicy,
+    #[serde(default)]
+    normaliser: KeyNormaliser,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(
+    try_from = "StoredStringToIntMapper<T>",
+    bound(deserialize = "T: Deserialize<'de> + Default + PartialEq + GetCurrentAndIncrementStringToIntMapperId")
+)]
+pub struct StringToIntMapper<T: Default + GetCurrentAndIncrementStringToIntMapperId, State = Editing> {
+    set_next_id: Option<T>,                 // starts at 0, None once the last ID of the type is handed out
+    added_in_order: Vec<String>,            // to retrive string by ID, keeps the original (not normalised) keys
+    keys_mapped_to: HashMap<String, T>,     // to get ID from normalised String, includes the tombstoned keys
+    removed_ids_in_order: TombstonedIds<T>, // tombstoned IDs, serialised as the list of IDs
+    #[serde(default)]
+    reuse_policy: IdReusePolicy,
+    #[serde(default)]
+    normaliser: KeyNormaliser,
     #[serde(skip)]
     state: PhantomData<State>,
 }
@@ -31,11 +153,34 @@ impl<T: Default + GetCurrentAndIncrementStringToIntMapperId> StringToIntMapper<T
             added_in_order: c.added_in_order,
             keys_mapped_to: c.keys_mapped_to,
             removed_ids_in_order: c.removed_ids_in_order,
-            state: PhantomData, 
+            reuse_policy: c.reuse_policy,
+            normaliser: c.normaliser,
+            state: PhantomData,
         }
     }
 }
 
+/// Fails for duplicated tombstones and tombstones of IDs that were never handed out
+impl<T: Default + PartialEq + GetCurrentAndIncrementStringToIntMapperId, State> TryFrom<StoredStringToIntMapper<T>>
+    for StringToIntMapper<T, State>
+{
+    type Error = StringToIntMapperError;
+
+    fn try_from(stored: StoredStringToIntMapper<T>) -> Result<Self, Self::Error> {
+        let removed_ids_in_order = TombstonedIds::from_ids(stored.removed_ids_in_order, stored.added_in_order.len())
+            .map_err(StringToIntMapperError::InvalidMapper)?;
+        Ok(StringToIntMapper {
+            set_next_id: stored.set_next_id,
+            added_in_order: stored.added_in_order,
+            keys_mapped_to: stored.keys_mapped_to,
+            removed_ids_in_order,
+            reuse_policy: stored.reuse_policy,
+            normaliser: stored.normaliser,
+            state: PhantomData,
+        })
+    }
+}
+
 impl<T: Default + GetCurrentAndIncrementStringToIntMapperId> From<StringToIntMapper<T, Reading>> for StringToIntMapper<T, Editing> {
     fn from(item: StringToIntMapper<T, Reading>) -> Self {
         StringToIntMapper::consume_new(item)
@@ -48,41 +193,151 @@ impl<T: Default + GetCurrentAndIncrementStringToIntMapperId> From<StringToIntMap
     }
 }
 
+impl<T: Default + GetCurrentAndIncrementStringToIntMapperId, State> Default for StringToIntMapper<T, State> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Default + GetCurrentAndIncrementStringToIntMapperId, State> StringToIntMapper<T, State> {
     pub fn new() -> Self {
+        Self::new_with_options(IdReusePolicy::default(), KeyNormaliser::default())
+    }
+
+    pub fn new_with_reuse_policy(reuse_policy: IdReusePolicy) -> Self {
+        Self::new_with_options(reuse_policy, KeyNormaliser::default())
+    }
+
+    /// Lookups go through the normaliser, `get_key` returns the key as it was first added
+    pub fn new_with_normaliser(normaliser: KeyNormaliser) -> Self {
+        Self::new_with_options(IdReusePolicy::default(), normaliser)
+    }
+
+    pub fn new_with_options(reuse_policy: IdReusePolicy, normaliser: KeyNormaliser) -> Self {
         StringToIntMapper {
-            set_next_id: Default::default(),
+            set_next_id: Some(Default::default()),
             added_in_order: Vec::new(),
             keys_mapped_to: HashMap::new(),
-            removed_ids_in_order: Vec::new(),
+            removed_ids_in_order: TombstonedIds::default(),
+            reuse_policy,
+            normaliser,
             state: PhantomData,
         }
     }
 
+    pub fn reuse_policy(&self) -> IdReusePolicy {
+        self.reuse_policy
+    }
+
+    pub fn normaliser(&self) -> KeyNormaliser {
+        self.normaliser
+    }
+
+    /// IDs of the removed keys in the order they were removed. Removed keys keep their IDs.
+    pub fn removed_ids_in_order(&self) -> &[T] {
+        self.removed_ids_in_order.as_slice()
+    }
+}
+
+impl<T: Default + PartialEq + GetCurrentAndIncrementStringToIntMapperId, State> StringToIntMapper<T, State> {
+    fn is_tombstoned_id(&self, id: &T) -> bool {
+        self.removed_ids_in_order.contains(id)
+    }
+
+    /// ID of a live key. Removed keys don't resolve.
     pub fn get_id(&self, key: &str) -> Option<&T> {
-        self.keys_mapped_to.get(key)
+        self.find(key).filter(|id| !self.is_tombstoned_id(id))
+    }
+
+    pub fn is_removed(&self, key: &str) -> bool {
+        self.find(key).is_some_and(|id| self.is_tombstoned_id(id))
+    }
+
+    /// ID of the normalised key including the tombstoned keys
+    fn find(&self, key: &str) -> Option<&T> {
+        self.keys_mapped_to.get(self.normaliser.normalise(key).as_ref())
+    }
+
+    /// Number of live keys
+    pub fn len(&self) -> usize {
+        self.added_in_order.len().saturating_sub(self.removed_ids_in_order.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
 
 
-impl<T: Default + Copy + GetCurrentAndIncrementStringToIntMapperId> StringToIntMapper<T, Editing> {
-    pub fn add(&mut self, key: &str) -> Option<T> {
-        // Only add keys that do not already exist
-        if self.get_id(key).is_none() {
-            let id = self.set_next_id.get_and_increment();
-            self.added_in_order.push(String::from(key));
-            self.keys_mapped_to.insert(String::from(key), id);
-            return Some(id);
+impl<T: Default + Copy + PartialEq + GetCurrentAndIncrementStringToIntMapperId> StringToIntMapper<T, Editing> {
+    /// Adds a new key. A removed key is restored with its original ID.
+    /// Returns None if the key is already live and an error if the ID type has no IDs left.
+    pub fn add(&mut self, key: &str) -> Result<Option<T>, StringToIntMapperError> {
+        if let Some(id) = self.find(key).copied() {
+            return Ok(if self.restore(key) { Some(id) } else { None });
         }
-        None
+        let id = self.next_id().ok_or_else(|| StringToIntMapperError::CapacityExhausted(key.to_string()))?;
+        self.keys_mapped_to.insert(self.normaliser.normalise(key).into_owned(), id);
+        self.added_in_order.push(String::from(key));
+        Ok(Some(id))
     }
-    // think about remove here?? do we really want to change the hash id. Don't change it for now
+
+    /// Hands out the next ID. None if every ID of the ID type is taken.
+    fn next_id(&mut self) -> Option<T> {
+        let id = self.set_next_id?;
+        self.set_next_id = id.next_id();
+        Some(id)
+    }
+
+    /// Tombstones the key. The key stops resolving but its ID isn't handed out to another key
+    /// until the mapper is compacted.
     pub fn remove(&mut self, key: &str) {
         match self.get_id(key) {
-            Some(v) => self.removed_ids_in_order.push(v.clone()),
+            Some(v) => {
+                let v = *v;
+                self.removed_ids_in_order.insert(v);
+            }
             None => (),
         }
     }
+    /// Restores a removed key with its original ID. Returns false if the key wasn't removed.
+    pub fn restore(&mut self, key: &str) -> bool {
+        let id = match self.find(key) {
+            Some(id) => *id,
+            None => return false,
+        };
+        self.removed_ids_in_order.remove(&id)
+    }
+
+    pub fn set_reuse_policy(&mut self, reuse_policy: IdReusePolicy) {
+        self.reuse_policy = reuse_policy;
+    }
+
+    /// Drops the tombstoned keys and reassigns the IDs of the live keys densely in their original order.
+    /// Every ID handed out before has to be remapped with the returned table.
+    pub fn compact(&mut self) -> Result<IdRemapTable<T>, StringToIntMapperError> {
+        if self.reuse_policy != IdReusePolicy::AfterCompaction {
+            return Err(StringToIntMapperError::CompactionNotAllowed(self.reuse_policy));
+        }
+        let added_in_order = std::mem::take(&mut self.added_in_order);
+        let mut keys_mapped_to = std::mem::take(&mut self.keys_mapped_to);
+        let removed = std::mem::take(&mut self.removed_ids_in_order);
+        self.set_next_id = Some(Default::default());
+        let mut remapped = Vec::with_capacity(added_in_order.len() - removed.len());
+        for key in added_in_order {
+            let normalised = self.normaliser.normalise(&key).into_owned();
+            let old = keys_mapped_to.remove(&normalised).unwrap();
+            if removed.contains(&old) {
+                continue;
+            }
+            let new = self.next_id().ok_or_else(|| StringToIntMapperError::CapacityExhausted(key.clone()))?;
+            self.keys_mapped_to.insert(normalised, new);
+            self.added_in_order.push(key);
+            remapped.push((old, new));
+        }
+        Ok(IdRemapTable::new(remapped, removed.into_vec()))
+    }
+
     pub fn to_reader(self) -> StringToIntMapper<T, Reading> {
         self.into()
     }
@@ -92,19 +347,36 @@ impl<T: Default + Copy + GetCurrentAndIncrementStringToIntMapperId> StringToIntM
     }
 }
 
-impl<T: Default + GetCurrentAndIncrementStringToIntMapperId> StringToIntMapper<T, Reading> {    
-    pub fn get_key(&self, id: &T) -> Option<&str> {
+impl<T: Default + PartialEq + GetCurrentAndIncrementStringToIntMapperId> StringToIntMapper<T, Reading> {
+    pub fn get_key(&self, id: &T) -> Option<MappedKey<'_>> {
         let uid: usize = id.to_usize();
-        if self.added_in_order.len() > uid {
-            return Some(&self.added_in_order[uid]);
+        let key = self.added_in_order.get(uid)?;
+        if self.is_tombstoned_id(id) {
+            return Some(MappedKey::Tombstoned(key));
         }
-        None
+        Some(MappedKey::Live(key))
+    }
+
+    /// Live keys in ID order
+    pub fn iter_in_order(&self) -> impl Iterator<Item = &String> + '_ {
+        self.added_in_order
+            .iter()
+            .enumerate()
+            .filter(move |(id, _)| !self.removed_ids_in_order.contains_index(*id))
+            .map(|(_, key)| key)
     }
-    
-    pub fn iter_in_order(&self) -> Iter<String> {
-        self.added_in_order.iter()
+
+    /// Every key including the tombstoned ones in ID order. The position is the ID.
+    pub fn iter_with_status(&self) -> impl Iterator<Item = MappedKey<'_>> + '_ {
+        self.added_in_order.iter().enumerate().map(move |(id, key)| {
+            if self.removed_ids_in_order.contains_index(id) {
+                MappedKey::Tombstoned(key)
+            } else {
+                MappedKey::Live(key)
+            }
+        })
     }
-    
+
     pub fn to_editer(self) -> StringToIntMapper<T, Editing> {
         self.into()
     }
@@ -114,18 +386,13 @@ impl<T: Default + GetCurrentAndIncrementStringToIntMapperId> StringToIntMapper<T
     }
 }
 
-
-
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     impl GetCurrentAndIncrementStringToIntMapperId for i32 {
-        fn get_and_increment(&mut self) -> Self {
-            let ret = self.clone();
-            *self = ret + 1i32;
-            ret
+        fn next_id(&self) -> Option<Self> {
+            self.checked_add(1)
         }
         fn to_usize(&self) -> usize {
             *self as usize
@@ -135,14 +402,124 @@ mod tests {
     #[test]
     fn add_and_get_key_test() {
         let mut mapper = StringToIntMapper::<i32, Editing>::new();
-        assert_eq!(Some(0), mapper.add("one"));
-        assert_eq!(None, mapper.add("one"));
-        assert_eq!(Some(1), mapper.add("two"));
-        assert_eq!(Some(2), mapper.add("three"));
+        assert_eq!(Ok(Some(0)), mapper.add("one"));
+        assert_eq!(Ok(None), mapper.add("one"));
+        assert_eq!(Ok(Some(1)), mapper.add("two"));
+        assert_eq!(Ok(Some(2)), mapper.add("three"));
         let mapper = mapper.to_reader();
         assert_eq!(Some(&0), mapper.get_id("one"));
         assert_eq!(None, mapper.get_id("N/A"));
         assert_eq!(Some(&1), mapper.get_id("two"));
         assert_eq!(Some(&2), mapper.get_id("three"));
     }
+
+    #[test]
+    fn remove_and_restore_test() {
+        let mut mapper = StringToIntMapper::<i32, Editing>::new();
+        mapper.add("one").unwrap();
+        mapper.add("two").unwrap();
+        mapper.remove("two");
+        mapper.remove("two");
+        mapper.remove("N/A");
+        assert_eq!(mapper.removed_ids_in_order(), &[1]);
+        assert!(mapper.is_removed("two"));
+        assert_eq!(None, mapper.get_id("two"));
+        assert_eq!(1, mapper.len());
+        assert!(mapper.restore("two"));
+        assert!(!mapper.restore("one"));
+        assert!(mapper.removed_ids_in_order().is_empty());
+        assert_eq!(Some(&1), mapper.get_id("two"));
+        // adding a removed key restores its ID
+        mapper.remove("two");
+        assert_eq!(Ok(Some(1)), mapper.add("two"));
+        assert_eq!(Ok(Some(2)), mapper.add("three"));
+    }
+
+    #[test]
+    fn tombstoned_keys_test() {
+        let mut mapper = StringToIntMapper::<i32, Editing>::new();
+        mapper.add("one").unwrap();
+        mapper.add("two").unwrap();
+        mapper.add("three").unwrap();
+        mapper.remove("two");
+        let mapper = mapper.to_reader();
+        assert_eq!(Some(MappedKey::Live("one")), mapper.get_key(&0));
+        assert_eq!(Some(MappedKey::Tombstoned("two")), mapper.get_key(&1));
+        assert_eq!(None, mapper.get_key(&3));
+        assert_eq!(vec!["one", "three"], mapper.iter_in_order().collect::<Vec<_>>());
+        assert_eq!(3, mapper.iter_with_status().count());
+
+        // the tombstones are serialised as the list of IDs
+        let mut json = serde_json::to_value(&mapper).unwrap();
+        assert_eq!(json["removed_ids_in_order"], serde_json::json!([1]));
+        let mapper: StringToIntMapper<i32, Reading> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(None, mapper.get_id("two"));
+        assert_eq!(Some(MappedKey::Tombstoned("two")), mapper.get_key(&1));
+        assert_eq!(vec!["one", "three"], mapper.iter_in_order().collect::<Vec<_>>());
+        assert_eq!(2, mapper.len());
+
+        // a duplicated tombstone would underflow the length and a huge ID would allocate a huge bitset
+        for removed_ids in [serde_json::json!([1, 1]), serde_json::json!([3]), serde_json::json!([i32::MAX])] {
+            json["removed_ids_in_order"] = removed_ids;
+            assert!(serde_json::from_value::<StringToIntMapper<i32, Reading>>(json.clone()).is_err());
+        }
+    }
+
+    #[test]
+    fn compact_test() {
+        let mut mapper = StringToIntMapper::<i32, Editing>::new();
+        mapper.add("one").unwrap();
+        mapper.add("two").unwrap();
+        mapper.add("three").unwrap();
+        mapper.remove("two");
+        assert_eq!(
+            Err(StringToIntMapperError::CompactionNotAllowed(IdReusePolicy::Never)),
+            mapper.compact()
+        );
+
+        mapper.set_reuse_policy(IdReusePolicy::AfterCompaction);
+        let remap = mapper.compact().unwrap();
+        assert_eq!(remap.remapped(), vec![(0, 0), (2, 1)]);
+        assert_eq!(remap.removed(), vec![1]);
+        assert_eq!(Some(1), remap.get(&2));
+        assert_eq!(None, remap.get(&1));
+        assert_eq!(Some(&1), mapper.get_id("three"));
+        assert!(!mapper.is_removed("two"));
+        // the freed ID is reused
+        assert_eq!(Ok(Some(2)), mapper.add("four"));
+    }
+
+    #[test]
+    fn normalised_keys_test() {
+        let mut mapper = StringToIntMapper::<i32, Editing>::new_with_normaliser(KeyNormaliser::symbols());
+        assert_eq!(Ok(Some(0)), mapper.add("BTC"));
+        assert_eq!(Ok(None), mapper.add(" btc"));
+        assert_eq!(Ok(Some(1)), mapper.add("Eth "));
+        mapper.remove("ETH");
+        assert!(mapper.is_removed("eth"));
+        assert_eq!(Ok(Some(1)), mapper.add("ETH"));
+        let mapper = mapper.to_reader();
+        assert_eq!(Some(&0), mapper.get_id("Btc\n"));
+        assert_eq!(Some(MappedKey::Live("BTC")), mapper.get_key(&0));
+        assert_eq!(Some(MappedKey::Live("Eth ")), mapper.get_key(&1));
+
+        let json = serde_json::to_string(&mapper).unwrap();
+        let mapper: StringToIntMapper<i32, Reading> = serde_json::from_str(&json).unwrap();
+        assert_eq!(KeyNormaliser::symbols(), mapper.normaliser());
+        assert_eq!(Some(&1), mapper.get_id("eth"));
+    }
+
+    #[test]
+    fn capacity_exhausted_test() {
+        let mut mapper = StringToIntMapper::<i32, Editing>::new();
+        mapper.set_next_id = Some(i32::MAX - 1);
+        assert_eq!(Ok(Some(i32::MAX - 1)), mapper.add("one"));
+        assert_eq!(Ok(Some(i32::MAX)), mapper.add("two"));
+        assert_eq!(
+            Err(StringToIntMapperError::CapacityExhausted("three".to_string())),
+            mapper.add("three")
+        );
+        assert_eq!(None, mapper.get_id("three"));
+        assert_eq!(2, mapper.len());
+    }
 }
@@ -0,0 +1,135 @@
+use crate::*;
+
+/// Result of merging two mappers. The IDs of the left mapper are kept, the new keys of the right mapper
+/// are appended in their ID order.
+pub struct MergedMappers<T: Default + GetCurrentAndIncrementStringToIntMapperId> {
+    pub mapper: StringToIntMapper<T, Reading>,
+    /// Live keys of the left mapper. These are always mapped to the same IDs.
+    pub left: IdRemapTable<T>,
+    /// Live keys of the right mapper. The tombstoned keys of the right mapper are not merged.
+    pub right: IdRemapTable<T>,
+}
+
+impl<T: Default + Copy + PartialEq + GetCurrentAndIncrementStringToIntMapperId, State> StringToIntMapper<T, State> {
+    /// (ID, key) of every key including the tombstoned ones in ID order
+    fn ids_in_order(&self) -> impl Iterator<Item = (T, &str)> + '_ {
+        self.added_in_order
+            .iter()
+            .map(move |key| (*self.find(key).unwrap(), key.as_str()))
+    }
+}
+
+impl<T: Default + Copy + PartialEq + GetCurrentAndIncrementStringToIntMapperId> StringToIntMapper<T, Editing> {
+    /// Adds the keys and returns the ID of every key in the input order, including the keys that already exist.
+    pub fn extend<I, S>(&mut self, keys: I) -> Result<Vec<T>, StringToIntMapperError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        keys.into_iter()
+            .map(|key| {
+                let key = key.as_ref();
+                match self.add(key)? {
+                    Some(id) => Ok(id),
+                    None => Ok(*self.get_id(key).unwrap()),
+                }
+            })
+            .collect()
+    }
+
+    /// Removes the live keys that don't satisfy the predicate. With the `AfterCompaction` reuse policy
+    /// the mapper is compacted and the IDs are remapped, otherwise the removed keys are only tombstoned
+    /// and the kept keys keep their IDs.
+    pub fn retain<F>(&mut self, mut keep: F) -> Result<IdRemapTable<T>, StringToIntMapperError>
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let (kept, removed) = self
+            .ids_in_order()
+            .filter(|(id, _)| !self.is_tombstoned_id(id))
+            .map(|(id, key)| (id, key.to_string()))
+            .partition::<Vec<_>, _>(|(_, key)| keep(key));
+        removed.iter().for_each(|(_, key)| self.remove(key));
+        match self.reuse_policy {
+            IdReusePolicy::AfterCompaction => self.compact(),
+            IdReusePolicy::Never => Ok(IdRemapTable::new(
+                kept.into_iter().map(|(id, _)| (id, id)).collect(),
+                removed.into_iter().map(|(id, _)| id).collect(),
+            )),
+        }
+    }
+}
+
+impl<T: Default + Copy + PartialEq + GetCurrentAndIncrementStringToIntMapperId> StringToIntMapper<T, Reading> {
+    /// Merges the live keys of the right mapper into a copy of the left mapper. The merged mapper uses
+    /// the reuse policy and the normaliser of the left mapper, right keys that normalise to a left key
+    /// are mapped to the left ID. A tombstoned left key that is live in the right mapper is restored.
+    pub fn merge(left: &Self, right: &Self) -> Result<MergedMappers<T>, StringToIntMapperError> {
+        let mut mapper = left.clone().to_editer();
+        let left = IdRemapTable::new(
+            left.ids_in_order()
+                .filter(|(id, _)| !left.is_tombstoned_id(id))
+                .map(|(id, _)| (id, id))
+                .collect(),
+            Vec::new(),
+        );
+        let (live, tombstoned) = right
+            .ids_in_order()
+            .partition::<Vec<_>, _>(|(id, _)| !right.is_tombstoned_id(id));
+        let new_ids = mapper.extend(live.iter().map(|(_, key)| key))?;
+        let right = IdRemapTable::new(
+            live.iter().map(|(id, _)| *id).zip(new_ids).collect(),
+            tombstoned.into_iter().map(|(id, _)| id).collect(),
+        );
+        Ok(MergedMappers {
+            mapper: mapper.to_reader(),
+            left,
+            right,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extend_and_merge_test() {
+        let mut left = StringToIntMapper::<i32, Editing>::new();
+        assert_eq!(Ok(vec![0, 1, 0, 2]), left.extend(["BTC", "ETH", "BTC", "USD"].iter()));
+        left.remove("ETH");
+        let mut right = StringToIntMapper::<i32, Editing>::new();
+        right.extend(vec!["XRP".to_string(), "USD".to_string(), "ETH".to_string(), "LTC".to_string()]).unwrap();
+        right.remove("LTC");
+
+        let merged = StringToIntMapper::merge(&left.to_reader(), &right.to_reader()).unwrap();
+        assert_eq!(merged.left.remapped(), vec![(0, 0), (2, 2)]);
+        assert_eq!(merged.right.remapped(), vec![(0, 3), (1, 2), (2, 1)]);
+        assert_eq!(merged.right.removed(), vec![3]);
+        assert_eq!(Some(3), merged.right.get(&0));
+        assert_eq!(None, merged.right.get(&3));
+        assert_eq!(None, merged.right.get(&7));
+        assert_eq!(
+            vec!["BTC", "ETH", "USD", "XRP"],
+            merged.mapper.iter_in_order().collect::<Vec<_>>()
+        );
+        assert_eq!(None, merged.mapper.get_id("LTC"));
+    }
+
+    #[test]
+    fn retain_test() {
+        let mut mapper = StringToIntMapper::<i32, Editing>::new();
+        mapper.extend(["BTC", "ETH", "USD"].iter()).unwrap();
+        let remap = mapper.retain(|key| key != "ETH").unwrap();
+        assert_eq!(remap.remapped(), vec![(0, 0), (2, 2)]);
+        assert_eq!(remap.removed(), vec![1]);
+        assert!(mapper.is_removed("ETH"));
+
+        let mut mapper = StringToIntMapper::<i32, Editing>::new_with_reuse_policy(IdReusePolicy::AfterCompaction);
+        mapper.extend(["BTC", "ETH", "USD"].iter()).unwrap();
+        let remap = mapper.retain(|key| key != "ETH").unwrap();
+        assert_eq!(remap.remapped(), vec![(0, 0), (2, 1)]);
+        assert_eq!(remap.removed(), vec![1]);
+        assert_eq!(Some(&1), mapper.get_id("USD"));
+    }
+}
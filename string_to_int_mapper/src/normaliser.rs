@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use unicode_normalization::{is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+/// Normalisation applied to the keys before lookups. The mapper keeps the original key for display.
+/// The default normaliser keeps the keys as they are.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyNormaliser {
+    /// Unicode NFKC normalisation e.g. full width 'ＢＴＣ' is 'BTC'
+    #[serde(default)]
+    pub nfkc: bool,
+    /// Removes the leading and trailing whitespace
+    #[serde(default)]
+    pub trim: bool,
+    /// Lower case comparison
+    #[serde(default)]
+    pub case_fold: bool,
+}
+
+impl KeyNormaliser {
+    /// NFKC, trimming and case folding e.g. for currency symbols from different sources
+    pub fn symbols() -> Self {
+        KeyNormaliser {
+            nfkc: true,
+            trim: true,
+            case_fold: true,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == KeyNormaliser::default()
+    }
+
+    /// Lookup form of the key. Borrows the key if it is already normalised.
+    pub fn normalise<'k>(&self, key: &'k str) -> Cow<'k, str> {
+        let mut key = Cow::Borrowed(key);
+        if self.nfkc && is_nfkc_quick(key.chars()) != IsNormalized::Yes {
+            key = Cow::Owned(key.nfkc().collect());
+        }
+        if self.trim && key.trim().len() != key.len() {
+            key = match key {
+                Cow::Borrowed(k) => Cow::Borrowed(k.trim()),
+                Cow::Owned(k) => Cow::Owned(k.trim().to_string()),
+            };
+        }
+        if self.case_fold && key.chars().any(char::is_uppercase) {
+            key = Cow::Owned(key.to_lowercase());
+        }
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalise_test() {
+        let normaliser = KeyNormaliser::symbols();
+        assert_eq!("btc", normaliser.normalise(" Btc\t"));
+        assert_eq!("btc", normaliser.normalise("ＢＴＣ"));
+        assert!(matches!(normaliser.normalise("btc"), Cow::Borrowed("btc")));
+        assert_eq!(" Btc", KeyNormaliser::default().normalise(" Btc"));
+        assert_eq!(
+            "Btc",
+            KeyNormaliser {
+                trim: true,
+                ..Default::default()
+            }
+            .normalise(" Btc ")
+        );
+    }
+}
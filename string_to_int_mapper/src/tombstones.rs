@@ -0,0 +1,88 @@
+use crate::GetCurrentAndIncrementStringToIntMapperId;
+use serde::{Serialize, Serializer};
+
+/// Tombstoned IDs in the order they were removed. Lookups go through a bitset indexed by the ID.
+/// Serialised as the plain list of IDs and restored with `from_ids`.
+#[derive(Debug, Clone)]
+pub(crate) struct TombstonedIds<T> {
+    in_order: Vec<T>,
+    bits: Vec<bool>,
+}
+
+impl<T> Default for TombstonedIds<T> {
+    fn default() -> Self {
+        TombstonedIds {
+            in_order: Vec::new(),
+            bits: Vec::new(),
+        }
+    }
+}
+
+impl<T: PartialEq + GetCurrentAndIncrementStringToIntMapperId> TombstonedIds<T> {
+    pub(crate) fn contains(&self, id: &T) -> bool {
+        self.contains_index(id.to_usize())
+    }
+
+    pub(crate) fn contains_index(&self, index: usize) -> bool {
+        self.bits.get(index).copied().unwrap_or(false)
+    }
+
+    /// Returns false if the ID was already tombstoned
+    pub(crate) fn insert(&mut self, id: T) -> bool {
+        let index = id.to_usize();
+        if self.contains_index(index) {
+            return false;
+        }
+        if index >= self.bits.len() {
+            self.bits.resize(index + 1, false);
+        }
+        self.bits[index] = true;
+        self.in_order.push(id);
+        true
+    }
+
+    /// Fails for duplicated IDs and for IDs out of the `id_count` IDs that were handed out
+    pub(crate) fn from_ids(in_order: Vec<T>, id_count: usize) -> Result<Self, String> {
+        let mut tombstones = TombstonedIds::default();
+        for id in in_order {
+            let index = id.to_usize();
+            if index >= id_count {
+                return Err(format!("Tombstoned ID {} is out of range of the {} IDs", index, id_count));
+            }
+            if !tombstones.insert(id) {
+                return Err(format!("Tombstoned ID {} is duplicated", index));
+            }
+        }
+        Ok(tombstones)
+    }
+
+    /// Returns false if the ID wasn't tombstoned
+    pub(crate) fn remove(&mut self, id: &T) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+        self.bits[id.to_usize()] = false;
+        self.in_order.retain(|removed| removed != id);
+        true
+    }
+}
+
+impl<T> TombstonedIds<T> {
+    pub(crate) fn as_slice(&self) -> &[T] {
+        &self.in_order
+    }
+
+    pub(crate) fn into_vec(self) -> Vec<T> {
+        self.in_order
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.in_order.len()
+    }
+}
+
+impl<T: Serialize> Serialize for TombstonedIds<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.in_order.serialize(serializer)
+    }
+}
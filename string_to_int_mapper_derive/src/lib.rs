@@ -0,0 +1,77 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr, Type};
+
+const INTEGER_TYPES: [&str; 5] = ["u8", "u16", "u32", "u64", "usize"];
+
+/// Derives `From<integer>`, `GetCurrentAndIncrementStringToIntMapperId` (overflow checked) and `Display`
+/// for an unsigned integer newtype e.g. `struct CurrencyID(pub u16)`.
+/// `#[mapper_id(prefix = "c")]` displays the ID as `c=1`, without the prefix only the number is displayed.
+#[proc_macro_derive(MapperId, attributes(mapper_id))]
+pub fn derive_mapper_id(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn inner_type(input: &DeriveInput) -> syn::Result<&Type> {
+    let error = || syn::Error::new_spanned(&input.ident, "MapperId can only be derived for an unsigned integer newtype");
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed,
+            _ => return Err(error()),
+        },
+        _ => return Err(error()),
+    };
+    let ty = &fields[0].ty;
+    match ty {
+        Type::Path(path) if INTEGER_TYPES.iter().any(|integer| path.path.is_ident(integer)) => Ok(ty),
+        _ => Err(error()),
+    }
+}
+
+fn display_prefix(input: &DeriveInput) -> syn::Result<Option<LitStr>> {
+    let mut prefix = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("mapper_id")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("prefix") {
+                prefix = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported mapper_id attribute, expected `prefix`"))
+            }
+        })?;
+    }
+    Ok(prefix)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let inner = inner_type(&input)?;
+    let display = match display_prefix(&input)? {
+        Some(prefix) => quote! { write!(f, "{}={}", #prefix, self.0) },
+        None => quote! { write!(f, "{}", self.0) },
+    };
+    Ok(quote! {
+        impl ::std::convert::From<#inner> for #name {
+            fn from(value: #inner) -> Self {
+                #name(value)
+            }
+        }
+
+        impl ::string_to_int_mapper::GetCurrentAndIncrementStringToIntMapperId for #name {
+            fn next_id(&self) -> ::std::option::Option<Self> {
+                self.0.checked_add(1).map(#name)
+            }
+            fn to_usize(&self) -> usize {
+                self.0 as usize
+            }
+        }
+
+        impl ::std::fmt::Display for #name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                #display
+            }
+        }
+    })
+}
@@ -0,0 +1,30 @@
+use string_to_int_mapper::{GetCurrentAndIncrementStringToIntMapperId, MapperId};
+
+#[derive(Debug, PartialEq, Copy, Clone, MapperId)]
+#[mapper_id(prefix = "t")]
+struct PrefixedID(u8);
+
+#[derive(Debug, PartialEq, Copy, Clone, MapperId)]
+struct PlainID(u16);
+
+#[test]
+fn display_test() {
+    assert_eq!("t=7", PrefixedID(7).to_string());
+    assert_eq!("300", PlainID(300).to_string());
+}
+
+#[test]
+fn next_id_test() {
+    assert_eq!(Some(PrefixedID(1)), PrefixedID(0).next_id());
+    assert_eq!(None, PrefixedID(u8::MAX).next_id());
+    assert_eq!(Some(PlainID(u16::MAX)), PlainID(u16::MAX - 1).next_id());
+    assert_eq!(None, PlainID(u16::MAX).next_id());
+    assert_eq!(255, PrefixedID(u8::MAX).to_usize());
+    assert_eq!(PlainID(3), PlainID::from(3));
+}
+
+#[test]
+fn compile_fail_test() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
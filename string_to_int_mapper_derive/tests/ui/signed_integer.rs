@@ -0,0 +1,6 @@
+use string_to_int_mapper::MapperId;
+
+#[derive(MapperId)]
+struct TestID(i32);
+
+fn main() {}
@@ -0,0 +1,7 @@
+use string_to_int_mapper::MapperId;
+
+#[derive(MapperId)]
+#[mapper_id(suffix = "t")]
+struct TestID(u8);
+
+fn main() {}